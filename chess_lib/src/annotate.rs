@@ -0,0 +1,259 @@
+use crate::engine::DEFAULT_HASH_MB;
+use crate::pgn::{wrap_movetext, write_tags, PgnError, PgnGame};
+use crate::search::{Score, SearchLimits, Searcher};
+use crate::{ChessBoard, Color, Move};
+
+/// Scores are capped at this many centipawns when moves are compared, and mates count as the cap,
+/// so that giving back part of a decisive advantage is not called a blunder.
+const SCORE_CAP: i32 = 1000;
+/// How many moves of the engine's line are written in a comment.
+const MAX_LINE: usize = 8;
+
+/// How bad a move was, judged by how much it lost compared to the engine's choice.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+/// A move of an annotated game with the engine's view of it. Scores are from the point of view of
+/// the player who made the move.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnotatedMove {
+    pub mv: Move,
+    pub san: String,
+    /// The score of the position before the move, with the engine's best move.
+    pub best_score: Score,
+    /// The score of the position after the move that was played.
+    pub score: Score,
+    pub judgement: Option<Judgement>,
+    /// The engine's line from the position before the move.
+    pub best_line: Vec<Move>,
+}
+
+/// Runs the engine over the moves of games to point out their mistakes.
+pub struct Annotator {
+    searcher: Searcher,
+    limits: SearchLimits,
+    /// The centipawn losses from which a move is an inaccuracy, a mistake and a blunder.
+    thresholds: [i32; 3],
+}
+
+impl Annotator {
+    pub fn new(limits: SearchLimits) -> Annotator {
+        /// An annotator that searches every position of a game with the given limits, which should
+        /// include a depth, node or time limit.
+        let searcher = Searcher::new(DEFAULT_HASH_MB);
+        Annotator {
+            searcher,
+            limits,
+            thresholds: [50, 100, 300],
+        }
+    }
+    pub fn with_thresholds(mut self, inaccuracy: i32, mistake: i32, blunder: i32) -> Annotator {
+        // Changes the centipawn losses that make a move an inaccuracy, a mistake or a blunder.
+        self.thresholds = [inaccuracy, mistake, blunder];
+        self
+    }
+    pub fn searcher(&mut self) -> &mut Searcher {
+        // The searcher used for the analysis, e.g. to give it more threads.
+        &mut self.searcher
+    }
+    pub fn judge(&self, loss: i32) -> Option<Judgement> {
+        /// The judgement of a move that lost `loss` centipawns compared to the best move.
+        let [inaccuracy, mistake, blunder] = self.thresholds;
+        match loss {
+            _ if loss >= blunder => Some(Judgement::Blunder),
+            _ if loss >= mistake => Some(Judgement::Mistake),
+            _ if loss >= inaccuracy => Some(Judgement::Inaccuracy),
+            _ => None,
+        }
+    }
+    fn evaluate(
+        &mut self,
+        board: &ChessBoard,
+        history: &[u64],
+        only: Option<Move>,
+    ) -> (Score, Vec<Move>) {
+        let limits = SearchLimits {
+            search_moves: only.into_iter().collect(),
+            ..self.limits.clone()
+        };
+        let mut result = (Score::Centipawns(0), vec![]);
+        self.searcher.search(board, history, &limits, |info| {
+            result = (info.score, info.pv.clone());
+        });
+        result
+    }
+    pub fn annotate(&mut self, game: &PgnGame) -> Result<Vec<AnnotatedMove>, PgnError> {
+        /// Searches every position of the game, and the move played in it on its own with the same
+        /// limits, and judges the move by how much worse its score is than that of the engine's
+        /// best move. The engine's own choice is never judged.
+        let played = game.replay()?;
+        let mut history = vec![];
+        let mut moves = vec![];
+        for (board, mv) in &played {
+            let (best_score, best_line) = self.evaluate(board, &history, None);
+            let (score, loss) = match best_line.first() {
+                Some(best) if best == mv => (best_score, 0),
+                _ => {
+                    let score = self.evaluate(board, &history, Some(*mv)).0;
+                    (score, value(best_score) - value(score))
+                }
+            };
+            moves.push(AnnotatedMove {
+                mv: *mv,
+                san: board.to_san(mv),
+                best_score,
+                score,
+                judgement: self.judge(loss),
+                best_line,
+            });
+            history.push(board.zobrist_hash());
+        }
+        Ok(moves)
+    }
+    pub fn annotate_pgn(&mut self, game: &PgnGame) -> Result<String, PgnError> {
+        /// Annotates the game and writes it as PGN. Judged moves get their symbol and a comment
+        /// with the scores before and after the move and the engine's best line.
+        let moves = self.annotate(game)?;
+        let mut tags: Vec<(&str, &str)> = game
+            .tags
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        if game.tag("Annotator").is_none() {
+            tags.push(("Annotator", "chess_lib"));
+        }
+        let mut pgn = write_tags(&tags);
+
+        let mut board = game.starting_position()?;
+        let mut tokens = vec![];
+        let mut number_black = true;
+        for annotated in &moves {
+            tokens.extend(move_number(&board, number_black));
+            let symbol = annotated
+                .judgement
+                .map_or("", |judgement| judgement.symbol());
+            tokens.push(format!("{}{}", annotated.san, symbol));
+            number_black = false;
+            if let Some(judgement) = annotated.judgement {
+                tokens.push(format!("{{{}", judgement.name()));
+                tokens.push(format!("({}", format_score(annotated.best_score)));
+                tokens.push("->".to_string());
+                tokens.push(format!("{}).", format_score(annotated.score)));
+                tokens.push("Best:".to_string());
+                tokens.extend(line_tokens(&board, &annotated.best_line));
+                let last = tokens.pop().unwrap_or_default();
+                tokens.push(format!("{}}}", last));
+                number_black = true;
+            }
+            board.make_move(&annotated.mv);
+        }
+        tokens.push(
+            game.result
+                .map_or("*", |result| result.to_pgn())
+                .to_string(),
+        );
+        pgn.push_str(&wrap_movetext(&tokens));
+        Ok(pgn)
+    }
+}
+
+fn move_number(board: &ChessBoard, number_black: bool) -> Option<String> {
+    // Black's moves only get a number at the start of the game or of a line, or after a comment.
+    let number = board.turn.div_ceil(2);
+    match board.faction_decider() {
+        Color::White => Some(format!("{}.", number)),
+        Color::Black if number_black => Some(format!("{}...", number)),
+        Color::Black => None,
+    }
+}
+
+fn line_tokens(board: &ChessBoard, line: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    let mut tokens = vec![];
+    for (i, mv) in line.iter().take(MAX_LINE).enumerate() {
+        tokens.extend(move_number(&board, i == 0));
+        tokens.push(board.to_san(mv));
+        board.make_move(mv);
+    }
+    tokens
+}
+
+fn value(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp.clamp(-SCORE_CAP, SCORE_CAP),
+        Score::Mate(moves) if moves > 0 => SCORE_CAP,
+        Score::Mate(_) => -SCORE_CAP,
+    }
+}
+
+fn format_score(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("{:+.2}", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{}", moves),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::read_games;
+
+    #[test]
+    fn judge_test() {
+        let annotator = Annotator::new(SearchLimits::default());
+        assert_eq!(annotator.judge(20), None);
+        assert_eq!(annotator.judge(60), Some(Judgement::Inaccuracy));
+        assert_eq!(annotator.judge(150), Some(Judgement::Mistake));
+        assert_eq!(annotator.judge(2000), Some(Judgement::Blunder));
+        let strict = Annotator::new(SearchLimits::default()).with_thresholds(10, 20, 30);
+        assert_eq!(strict.judge(20), Some(Judgement::Mistake));
+        assert_eq!(value(Score::Mate(-3)), -SCORE_CAP);
+        assert_eq!(format_score(Score::Centipawns(-130)), "-1.30");
+    }
+
+    #[test]
+    fn annotate_test() {
+        let game = &read_games("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0")[0];
+        let mut annotator = Annotator::new(SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        });
+        let moves = annotator.annotate(game).unwrap();
+        assert_eq!(moves.len(), 7);
+        assert_eq!(moves[5].judgement, Some(Judgement::Blunder));
+        assert_eq!(moves[5].score, Score::Mate(-1));
+        assert_eq!(moves[6].judgement, None);
+
+        let pgn = annotator.annotate_pgn(game).unwrap();
+        assert!(pgn.contains("[Annotator \"chess_lib\"]"));
+        let movetext = pgn.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(movetext.contains("3. Bc4 Nf6?? {Blunder (+"));
+        assert!(movetext.contains("-> #-1). Best: 3... "));
+        assert!(movetext.ends_with("} 4. Qxf7# 1-0"));
+        // The annotated game can be read back.
+        assert_eq!(read_games(&pgn)[0].moves, game.moves);
+    }
+}
@@ -0,0 +1,336 @@
+use chess_lib::display::figurine;
+use chess_lib::search::{SearchLimits, Searcher};
+use chess_lib::{parse_square, ChessBoard, Color, Move};
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+const HELP: &str = "\
+Enter a move as SAN (Nf3, exd5, O-O), UCI (g1f3) or coordinates (g1 f3).
+Entering just a square selects the piece on it and shows where it can move.
+Other commands: moves, undo, fen, help, quit";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Player {
+    Human,
+    Engine,
+}
+
+struct Options {
+    white: Player,
+    black: Player,
+    depth: Option<u32>,
+    movetime: Duration,
+}
+
+enum Input {
+    Move(Move),
+    Select((usize, usize)),
+    Undo,
+    Moves,
+    Fen,
+    Help,
+    Quit,
+    Invalid,
+}
+
+fn parse_player(name: Option<String>) -> Result<Player, String> {
+    match name.as_deref() {
+        Some("human") => Ok(Player::Human),
+        Some("engine") => Ok(Player::Engine),
+        other => Err(format!("expected human or engine, got {:?}", other)),
+    }
+}
+
+/// Reads the command line, e.g. `chess --black engine --movetime 2000`.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        white: Player::Human,
+        black: Player::Engine,
+        depth: None,
+        movetime: Duration::from_secs(1),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--white" => options.white = parse_player(args.next())?,
+            "--black" => options.black = parse_player(args.next())?,
+            "--depth" => {
+                options.depth = Some(
+                    args.next()
+                        .and_then(|depth| depth.parse().ok())
+                        .ok_or("--depth needs a number")?,
+                )
+            }
+            "--movetime" => {
+                options.movetime = Duration::from_millis(
+                    args.next()
+                        .and_then(|ms| ms.parse().ok())
+                        .ok_or("--movetime needs a number of milliseconds")?,
+                )
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// The squares the piece on `square` can legally move to, using select_piece for the hints.
+fn move_hints(board: &ChessBoard, square: (usize, usize)) -> Vec<(usize, usize)> {
+    let legal = board.legal_moves();
+    board
+        .select_piece(square, &board.faction_decider())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|destination| {
+            legal
+                .iter()
+                .any(|mv| mv.source == square && mv.destination == *destination)
+        })
+        .collect()
+}
+
+fn parse_input(board: &ChessBoard, selected: Option<(usize, usize)>, line: &str) -> Input {
+    let line = line.trim();
+    match line {
+        "undo" => return Input::Undo,
+        "moves" => return Input::Moves,
+        "fen" => return Input::Fen,
+        "help" | "?" => return Input::Help,
+        "quit" | "exit" => return Input::Quit,
+        _ => {}
+    }
+    let find_move = |source, destination| {
+        board
+            .legal_moves()
+            .into_iter()
+            .find(|mv| mv.source == source && mv.destination == destination)
+    };
+    if let Some(square) = parse_square(line) {
+        if let Some(mv) = selected.and_then(|source| find_move(source, square)) {
+            return Input::Move(mv);
+        }
+        if board
+            .select_piece(square, &board.faction_decider())
+            .is_some()
+        {
+            return Input::Select(square);
+        }
+    }
+    if let Some(mv) = board.parse_uci_move(line).or_else(|| board.parse_san(line)) {
+        return Input::Move(mv);
+    }
+    let squares: Vec<(usize, usize)> = line
+        .split([' ', '-'])
+        .filter(|part| !part.is_empty())
+        .filter_map(parse_square)
+        .collect();
+    if let [source, destination] = squares[..] {
+        if let Some(mv) = find_move(source, destination) {
+            return Input::Move(mv);
+        }
+    }
+    Input::Invalid
+}
+
+/// Draws the board with Unicode pieces. Hinted squares are marked with "·", or brackets around a piece that can be captured.
+fn render(board: &ChessBoard, hints: &[(usize, usize)], flipped: bool) -> String {
+    let order: Vec<usize> = if flipped {
+        (0..8).rev().collect()
+    } else {
+        (0..8).collect()
+    };
+    let mut text = String::new();
+    for &y in &order {
+        text.push_str(&format!("{} ", 8 - y));
+        for &x in &order {
+            let hinted = hints.contains(&(x, y));
+            match (&board.board[y][x], hinted) {
+                (Some(piece), true) => text.push_str(&format!("[{}]", figurine(piece))),
+                (Some(piece), false) => text.push_str(&format!(" {} ", figurine(piece))),
+                (None, true) => text.push_str(" · "),
+                (None, false) => text.push_str(if (x + y) % 2 == 0 { "   " } else { " ░ " }),
+            }
+        }
+        text.push('\n');
+    }
+    text.push_str("  ");
+    for &x in &order {
+        text.push_str(&format!(" {} ", (b'a' + x as u8) as char));
+    }
+    text.push('\n');
+    text
+}
+
+/// Describes how the game ended, or None if it goes on.
+fn game_over(board: &ChessBoard, previous: &[ChessBoard]) -> Option<String> {
+    if board.is_checkmate() {
+        let winner = match board.faction_decider() {
+            Color::White => "Black",
+            Color::Black => "White",
+        };
+        return Some(format!("Checkmate, {} wins", winner));
+    }
+    if board.is_stalemate() {
+        return Some("Stalemate, the game is drawn".to_string());
+    }
+    if board.halfmove_clock >= 100 {
+        return Some("Draw by the 50 move rule".to_string());
+    }
+    let hash = board.zobrist_hash();
+    let repetitions = previous
+        .iter()
+        .filter(|earlier| earlier.zobrist_hash() == hash)
+        .count();
+    if repetitions >= 2 {
+        return Some("Draw by threefold repetition".to_string());
+    }
+    None
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("usage: chess [--white human|engine] [--black human|engine] [--depth N] [--movetime MS]");
+            std::process::exit(2);
+        }
+    };
+    let flipped = options.white == Player::Engine && options.black == Player::Human;
+    let player = |color: Color| match color {
+        Color::White => options.white,
+        Color::Black => options.black,
+    };
+
+    let mut board = ChessBoard::create();
+    let mut previous: Vec<ChessBoard> = vec![];
+    let mut searcher = Searcher::new(16);
+    let mut selected = None;
+    let mut lines = io::stdin().lock().lines();
+    println!("{}", HELP);
+
+    loop {
+        let hints = selected
+            .map(|square| move_hints(&board, square))
+            .unwrap_or_default();
+        println!("\n{}", render(&board, &hints, flipped));
+        if let Some(result) = game_over(&board, &previous) {
+            println!("{}", result);
+            return;
+        }
+        let color = board.faction_decider();
+
+        let mv = if player(color) == Player::Engine {
+            let limits = SearchLimits {
+                depth: options.depth,
+                movetime: options.depth.is_none().then_some(options.movetime),
+                ..Default::default()
+            };
+            let history: Vec<u64> = previous.iter().map(ChessBoard::zobrist_hash).collect();
+            match searcher.search(&board, &history, &limits, |_| {}) {
+                Some(mv) => {
+                    println!("{:?} plays {}", color, board.to_san(&mv));
+                    mv
+                }
+                None => return,
+            }
+        } else {
+            print!("{:?} to move> ", color);
+            io::stdout().flush().ok();
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => return,
+            };
+            match parse_input(&board, selected, &line) {
+                Input::Move(mv) => mv,
+                Input::Select(square) => {
+                    selected = Some(square);
+                    continue;
+                }
+                Input::Undo => {
+                    // Against the engine take back its reply as well, so it is the human's turn again.
+                    let mut undone = false;
+                    while let Some(earlier) = previous.pop() {
+                        board = earlier;
+                        undone = true;
+                        if player(board.faction_decider()) == Player::Human {
+                            break;
+                        }
+                    }
+                    if !undone {
+                        println!("Nothing to undo");
+                    }
+                    selected = None;
+                    continue;
+                }
+                Input::Moves => {
+                    let moves: Vec<String> = board
+                        .legal_moves()
+                        .iter()
+                        .map(|mv| board.to_san(mv))
+                        .collect();
+                    println!("{}", moves.join(" "));
+                    continue;
+                }
+                Input::Fen => {
+                    println!("{}", board.to_fen());
+                    continue;
+                }
+                Input::Help => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                Input::Quit => return,
+                Input::Invalid => {
+                    println!("Not a legal move or command, type help for the input formats");
+                    continue;
+                }
+            }
+        };
+        previous.push(board.clone());
+        board.make_move(&mv);
+        selected = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_input_test() {
+        let board = ChessBoard::create();
+        let e2e4 = board.parse_uci_move("e2e4").unwrap();
+        assert!(matches!(parse_input(&board, None, "e4"), Input::Move(mv) if mv == e2e4));
+        assert!(matches!(parse_input(&board, None, "e2e4"), Input::Move(mv) if mv == e2e4));
+        assert!(matches!(parse_input(&board, None, "e2-e4"), Input::Move(mv) if mv == e2e4));
+        assert!(matches!(
+            parse_input(&board, None, "e2"),
+            Input::Select((4, 6))
+        ));
+        assert!(
+            matches!(parse_input(&board, Some((4, 6)), "e3"), Input::Move(mv) if mv.destination == (4, 5))
+        );
+        assert!(matches!(parse_input(&board, None, "e5"), Input::Invalid));
+        assert!(matches!(parse_input(&board, None, "undo"), Input::Undo));
+    }
+
+    #[test]
+    fn move_hints_test() {
+        let board = ChessBoard::create();
+        assert_eq!(move_hints(&board, (6, 7)), vec![(5, 5), (7, 5)]);
+        let rendered = render(&board, &move_hints(&board, (6, 7)), false);
+        assert!(rendered.starts_with("8  ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ \n"));
+        assert_eq!(rendered.matches('·').count(), 2);
+        assert!(rendered.ends_with("   a  b  c  d  e  f  g  h \n"));
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["--white", "engine", "--black", "human", "--depth", "4"];
+        let options = parse_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.white, Player::Engine);
+        assert_eq!(options.black, Player::Human);
+        assert_eq!(options.depth, Some(4));
+        assert!(parse_args(["--white", "robot"].iter().map(|arg| arg.to_string())).is_err());
+    }
+}
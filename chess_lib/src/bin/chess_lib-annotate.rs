@@ -0,0 +1,106 @@
+use chess_lib::annotate::Annotator;
+use chess_lib::pgn::read_games;
+use chess_lib::search::SearchLimits;
+use std::fs;
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: chess_lib-annotate [--depth N | --movetime MS] [--threads N] PGN...
+Searches every move of the games in the PGN files and writes them to standard output with
+inaccuracies, mistakes and blunders marked (?!, ?, ??) and the engine's best line in a comment.
+The default is --depth 10.";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    limits: SearchLimits,
+    threads: usize,
+    inputs: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        limits: SearchLimits::default(),
+        threads: 1,
+        inputs: vec![],
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => {
+                options.limits.depth = Some(
+                    args.next()
+                        .and_then(|depth| depth.parse().ok())
+                        .ok_or("--depth needs a number")?,
+                )
+            }
+            "--movetime" => {
+                let millis = args
+                    .next()
+                    .and_then(|millis| millis.parse().ok())
+                    .ok_or("--movetime needs a number of milliseconds")?;
+                options.limits.movetime = Some(Duration::from_millis(millis))
+            }
+            "--threads" => {
+                options.threads = args
+                    .next()
+                    .and_then(|threads| threads.parse().ok())
+                    .ok_or("--threads needs a number")?
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => options.inputs.push(arg),
+        }
+    }
+    if options.inputs.is_empty() {
+        return Err("expected at least one PGN file".to_string());
+    }
+    if options.limits.depth.is_none() && options.limits.movetime.is_none() {
+        options.limits.depth = Some(10);
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut annotator = Annotator::new(options.limits);
+    annotator.searcher().set_threads(options.threads);
+    for input in &options.inputs {
+        let text = match fs::read_to_string(input) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("can not read {}: {}", input, error);
+                std::process::exit(1);
+            }
+        };
+        for (i, game) in read_games(&text).iter().enumerate() {
+            match annotator.annotate_pgn(game) {
+                Ok(pgn) => println!("{}", pgn),
+                Err(error) => eprintln!("{}: skipped game {}: {}", input, i + 1, error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["--movetime", "500", "--threads", "4", "a.pgn"];
+        let options = parse_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.limits.movetime, Some(Duration::from_millis(500)));
+        assert_eq!(options.limits.depth, None);
+        assert_eq!(options.threads, 4);
+        assert_eq!(options.inputs, ["a.pgn"]);
+        let options = parse_args(["a.pgn"].iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.limits.depth, Some(10));
+        assert!(parse_args(std::iter::empty()).is_err());
+        assert!(parse_args(["--depth"].iter().map(|arg| arg.to_string())).is_err());
+    }
+}
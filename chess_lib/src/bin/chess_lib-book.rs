@@ -0,0 +1,113 @@
+use chess_lib::book::BookBuilder;
+use std::fs;
+
+const USAGE: &str = "\
+usage: chess_lib-book [--plies N] [--min-games N] [--compact] OUTPUT PGN...
+Builds an opening book from the games in the PGN files. The book is written in the Polyglot
+format, or with --compact in a format that keeps the win/draw/loss counts. If OUTPUT is an
+existing compact book, the new games are added to it.";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    plies: usize,
+    min_games: u32,
+    compact: bool,
+    output: String,
+    inputs: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        plies: 16,
+        min_games: 1,
+        compact: false,
+        output: String::new(),
+        inputs: vec![],
+    };
+    let mut files = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plies" => {
+                options.plies = args
+                    .next()
+                    .and_then(|plies| plies.parse().ok())
+                    .ok_or("--plies needs a number")?
+            }
+            "--min-games" => {
+                options.min_games = args
+                    .next()
+                    .and_then(|games| games.parse().ok())
+                    .ok_or("--min-games needs a number")?
+            }
+            "--compact" => options.compact = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.len() < 2 {
+        return Err("expected an output file and at least one PGN file".to_string());
+    }
+    options.output = files.remove(0);
+    options.inputs = files;
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let existing = fs::read(&options.output)
+        .ok()
+        .and_then(|bytes| BookBuilder::from_compact(&bytes));
+    let mut builder = existing.unwrap_or_else(|| BookBuilder::new(options.plies));
+    for input in &options.inputs {
+        let text = match fs::read_to_string(input) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("can not read {}: {}", input, error);
+                std::process::exit(1);
+            }
+        };
+        let (games, errors) = builder.add_pgn(&text);
+        for error in &errors {
+            eprintln!("{}: skipped a game: {}", input, error);
+        }
+        println!("{}: {} games", input, games - errors.len());
+    }
+    let bytes = if options.compact {
+        builder.to_compact()
+    } else {
+        builder.build(options.min_games).to_bytes()
+    };
+    if let Err(error) = fs::write(&options.output, bytes) {
+        eprintln!("can not write {}: {}", options.output, error);
+        std::process::exit(1);
+    }
+    println!(
+        "{} positions written to {}",
+        builder.positions(),
+        options.output
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["--plies", "10", "--compact", "out.bin", "a.pgn", "b.pgn"];
+        let options = parse_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.plies, 10);
+        assert!(options.compact);
+        assert_eq!(options.output, "out.bin");
+        assert_eq!(options.inputs, ["a.pgn", "b.pgn"]);
+        assert!(parse_args(["out.bin"].iter().map(|arg| arg.to_string())).is_err());
+        assert!(parse_args(["--plies", "x"].iter().map(|arg| arg.to_string())).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use chess_lib::clock::TimeControl;
+use chess_lib::game::Outcome;
+use chess_lib::search::SearchLimits;
+use chess_lib::sprt::{MatchScore, Sprt, SprtResult};
+use chess_lib::tournament::{
+    play_game, read_openings, Adjudication, LibraryPlayer, Opening, Player, PlayerError, UciPlayer,
+};
+use chess_lib::{ChessBoard, Color};
+use std::fs::{self, File};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: chess_lib-match --engine SPEC... --engine SPEC... [options]
+Plays a match between two engines and reports the score, the Elo difference and, with --sprt,
+a sequential probability ratio test, which stops the match once it has an answer.
+An engine SPEC is a list of KEY=VALUE settings up to the next option:
+  cmd=PATH           a UCI engine to run; without it the engine is this library's search
+  name=NAME          the name in the results and the PGN
+  arg=ARG            a command line argument for the engine, can be repeated
+  option.NAME=VALUE  a UCI option to set
+  threads=N hash=MB  the library search's threads and hash size
+  depth=N nodes=N    limits for every search of this engine
+Options:
+  --tc TC              time control in PGN notation, e.g. 10+0.1 or 40/60 (default 10+0.1)
+  --depth N, --nodes N, --movetime MS   limits for every search instead of a clock
+  --games N            games to play, each opening twice with colours swapped (default 100)
+  --concurrency N      games played at the same time (default 1)
+  --openings FILE      a PGN file or FEN/EPD positions, one per line
+  --sprt ELO0 ELO1     test the hypotheses ELO0 and ELO1 for the first engine's strength
+  --alpha A --beta B   the error rates of the test (default 0.05 each)
+  --resign MOVES CP    adjudicate a loss after MOVES moves at or below -CP
+  --draw AFTER MOVES CP  adjudicate a draw after move AFTER once both sides were within CP of
+                       zero for MOVES moves
+  --maxmoves N         adjudicate a draw after N moves
+  --bitbases           adjudicate positions the endgame bitbases know
+  --pgnout FILE        write the games to FILE";
+
+#[derive(Debug, Clone, PartialEq)]
+struct EngineSpec {
+    name: String,
+    command: Option<String>,
+    args: Vec<String>,
+    options: Vec<(String, String)>,
+    threads: usize,
+    hash_mb: usize,
+    limits: SearchLimits,
+}
+
+impl EngineSpec {
+    fn parse(settings: &[String], number: usize) -> Result<EngineSpec, String> {
+        let mut spec = EngineSpec {
+            name: String::new(),
+            command: None,
+            args: vec![],
+            options: vec![],
+            threads: 1,
+            hash_mb: 16,
+            limits: SearchLimits::default(),
+        };
+        for setting in settings {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("engine setting '{}' is not KEY=VALUE", setting))?;
+            let number = |value: &str| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("engine setting '{}' needs a number", key))
+            };
+            match key {
+                "cmd" => spec.command = Some(value.to_string()),
+                "name" => spec.name = value.to_string(),
+                "arg" => spec.args.push(value.to_string()),
+                "threads" => spec.threads = number(value)? as usize,
+                "hash" => spec.hash_mb = number(value)? as usize,
+                "depth" => spec.limits.depth = Some(number(value)? as u32),
+                "nodes" => spec.limits.nodes = Some(number(value)?),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => spec.options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("unknown engine setting '{}'", key)),
+                },
+            }
+        }
+        if spec.name.is_empty() {
+            spec.name = match &spec.command {
+                Some(command) => command.rsplit('/').next().unwrap_or(command).to_string(),
+                None => format!("chess_lib{}", number),
+            };
+        }
+        Ok(spec)
+    }
+    fn create(&self) -> Result<Box<dyn Player>, PlayerError> {
+        match &self.command {
+            Some(command) => {
+                let player = UciPlayer::start(&self.name, command, &self.args, &self.options)?;
+                Ok(Box::new(player.with_limits(self.limits.clone())))
+            }
+            None => Ok(Box::new(LibraryPlayer::new(
+                &self.name,
+                self.hash_mb,
+                self.threads,
+                self.limits.clone(),
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    engines: Vec<EngineSpec>,
+    control: Option<TimeControl>,
+    limits: SearchLimits,
+    games: u32,
+    concurrency: usize,
+    openings: Option<String>,
+    sprt: Option<Sprt>,
+    adjudication: Adjudication,
+    pgn_out: Option<String>,
+}
+
+fn next_value(
+    args: &mut impl Iterator<Item = String>,
+    option: &str,
+    what: &str,
+) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs {}", option, what))
+}
+
+fn number(text: String) -> Result<i64, String> {
+    text.parse()
+        .map_err(|_| format!("'{}' is not a number", text))
+}
+
+fn float(text: String) -> Result<f64, String> {
+    text.parse()
+        .map_err(|_| format!("'{}' is not a number", text))
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        engines: vec![],
+        control: None,
+        limits: SearchLimits::default(),
+        games: 100,
+        concurrency: 1,
+        openings: None,
+        sprt: None,
+        adjudication: Adjudication::default(),
+        pgn_out: None,
+    };
+    let mut args = args.peekable();
+    let mut error_rates = Sprt::default();
+    while let Some(arg) = args.next() {
+        let mut value = |what: &str| next_value(&mut args, &arg, what);
+        match arg.as_str() {
+            "--engine" => {
+                let mut settings = vec![];
+                while let Some(setting) = args.next_if(|next| !next.starts_with("--")) {
+                    settings.push(setting);
+                }
+                let spec = EngineSpec::parse(&settings, options.engines.len() + 1)?;
+                options.engines.push(spec);
+            }
+            "--tc" => {
+                let text = value("a time control")?;
+                options.control = Some(TimeControl::parse(&text).map_err(|e| e.to_string())?);
+            }
+            "--depth" => options.limits.depth = Some(number(value("a depth")?)? as u32),
+            "--nodes" => options.limits.nodes = Some(number(value("a node count")?)? as u64),
+            "--movetime" => {
+                let millis = number(value("milliseconds")?)?;
+                options.limits.movetime = Some(Duration::from_millis(millis as u64));
+            }
+            "--games" => options.games = number(value("a number")?)? as u32,
+            "--concurrency" => options.concurrency = number(value("a number")?)?.max(1) as usize,
+            "--openings" => options.openings = Some(value("a file")?),
+            "--pgnout" => options.pgn_out = Some(value("a file")?),
+            "--sprt" => {
+                let elo0 = value("two Elo values")?;
+                let elo1 = value("two Elo values")?;
+                options.sprt = Some(Sprt {
+                    elo0: float(elo0)?,
+                    elo1: float(elo1)?,
+                    ..Sprt::default()
+                });
+            }
+            "--alpha" | "--beta" => {
+                let text = value("a probability")?;
+                let rate = Some(float(text.clone())?)
+                    .filter(|rate| *rate > 0.0 && *rate < 0.5)
+                    .ok_or_else(|| format!("'{}' is not an error rate", text))?;
+                match arg.as_str() {
+                    "--alpha" => error_rates.alpha = rate,
+                    _ => error_rates.beta = rate,
+                }
+            }
+            "--resign" => {
+                let moves = number(value("moves and centipawns")?)? as u32;
+                let score = number(value("moves and centipawns")?)? as i32;
+                options.adjudication.resign = Some((moves, score));
+            }
+            "--draw" => {
+                let after = number(value("a move number, moves and centipawns")?)? as u32;
+                let moves = number(value("a move number, moves and centipawns")?)? as u32;
+                let score = number(value("a move number, moves and centipawns")?)? as i32;
+                options.adjudication.draw = Some((after, moves, score));
+            }
+            "--maxmoves" => {
+                options.adjudication.max_moves = Some(number(value("a number")?)? as u32)
+            }
+            "--bitbases" => options.adjudication.bitbases = true,
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if options.engines.len() != 2 {
+        return Err("expected exactly two engines".to_string());
+    }
+    if let Some(sprt) = &mut options.sprt {
+        (sprt.alpha, sprt.beta) = (error_rates.alpha, error_rates.beta);
+    }
+    let limits = &options.limits;
+    let fixed = limits.depth.is_some() || limits.nodes.is_some() || limits.movetime.is_some();
+    if options.control.is_none() && !fixed {
+        options.control = Some(TimeControl::fischer(
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+        ));
+    }
+    Ok(options)
+}
+
+fn report(names: [&str; 2], score: &MatchScore, sprt: Option<&Sprt>) -> SprtResult {
+    // Prints the standings and returns what the test, if any, has concluded.
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        names[0],
+        names[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        score.score(),
+        score.games()
+    );
+    match score.elo() {
+        Some((elo, margin)) => println!(
+            "Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %",
+            elo,
+            margin,
+            score.los() * 100.0
+        ),
+        None => println!("Elo difference: unknown, LOS: {:.1} %", score.los() * 100.0),
+    }
+    let Some(sprt) = sprt else {
+        return SprtResult::Continue;
+    };
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "SPRT ({} Elo vs {} Elo): llr {:.2}, lbound {:.2}, ubound {:.2}",
+        sprt.elo0,
+        sprt.elo1,
+        sprt.llr(score),
+        lower,
+        upper
+    );
+    sprt.result(score)
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let openings = match &options.openings {
+        Some(path) => {
+            let openings = fs::read_to_string(path)
+                .map_err(|error| error.to_string())
+                .and_then(|text| read_openings(&text).map_err(|error| error.to_string()));
+            match openings {
+                Ok(openings) if !openings.is_empty() => openings,
+                Ok(_) => {
+                    eprintln!("{} has no openings", path);
+                    std::process::exit(1);
+                }
+                Err(error) => {
+                    eprintln!("can not read {}: {}", path, error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => vec![Opening::from_board(ChessBoard::create())],
+    };
+    let pgn_out = options
+        .pgn_out
+        .as_ref()
+        .map(|path| match File::create(path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!("can not create {}: {}", path, error);
+                std::process::exit(1);
+            }
+        });
+    if options.adjudication.bitbases {
+        chess_lib::bitbase::generate_bitbases();
+    }
+
+    let names = [
+        options.engines[0].name.as_str(),
+        options.engines[1].name.as_str(),
+    ];
+    let next_game = AtomicU32::new(0);
+    let stop = AtomicBool::new(false);
+    let state = Mutex::new((MatchScore::default(), pgn_out, SprtResult::Continue));
+    thread::scope(|scope| {
+        for _ in 0..options.concurrency {
+            scope.spawn(|| {
+                // Every thread runs its own instances of the engines.
+                let players = options.engines[0]
+                    .create()
+                    .and_then(|first| Ok((first, options.engines[1].create()?)));
+                let (mut first, mut second) = match players {
+                    Ok(players) => players,
+                    Err(error) => {
+                        eprintln!("can not start the engines: {}", error);
+                        stop.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                };
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next_game.fetch_add(1, Ordering::Relaxed);
+                    if index >= options.games {
+                        break;
+                    }
+                    let opening = &openings[(index / 2) as usize % openings.len()];
+                    let (first_color, mut game) = match index % 2 {
+                        0 => (
+                            Color::White,
+                            play_game(
+                                first.as_mut(),
+                                second.as_mut(),
+                                opening,
+                                options.control.as_ref(),
+                                &options.limits,
+                                options.adjudication,
+                            ),
+                        ),
+                        _ => (
+                            Color::Black,
+                            play_game(
+                                second.as_mut(),
+                                first.as_mut(),
+                                opening,
+                                options.control.as_ref(),
+                                &options.limits,
+                                options.adjudication,
+                            ),
+                        ),
+                    };
+                    game.set_tag("Event", "chess_lib match");
+                    game.set_tag("Round", &(index + 1).to_string());
+                    let result = game.result();
+                    let mut state = state.lock().unwrap();
+                    let (score, pgn_out, conclusion) = &mut *state;
+                    if let Some(file) = pgn_out {
+                        let _ = writeln!(file, "{}\n", game.to_pgn());
+                    }
+                    let outcome = result.map_or(Outcome::Draw, |result| result.outcome);
+                    let termination =
+                        result.map_or("unfinished".to_string(), |r| format!("{:?}", r.termination));
+                    println!(
+                        "Finished game {} ({} vs {}): {} {{{}}}",
+                        index + 1,
+                        game.white,
+                        game.black,
+                        outcome.to_pgn(),
+                        termination
+                    );
+                    score.add(outcome, first_color);
+                    *conclusion = report(names, score, options.sprt.as_ref());
+                    if *conclusion != SprtResult::Continue {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    let (score, _, conclusion) = &*state.lock().unwrap();
+    println!("Finished match after {} games", score.games());
+    match conclusion {
+        SprtResult::AcceptH0 => println!("SPRT: H0 was accepted"),
+        SprtResult::AcceptH1 => println!("SPRT: H1 was accepted"),
+        SprtResult::Continue => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_test() {
+        let args = "--engine name=dev depth=4 threads=2 --engine cmd=/usr/bin/sf option.Hash=64 \
+                    --games 10 --concurrency 2 --sprt 0 5 --alpha 0.1 --resign 3 800 --maxmoves 150";
+        let options = parse_args(args.split_whitespace().map(str::to_string)).unwrap();
+        assert_eq!(options.engines[0].name, "dev");
+        assert_eq!(options.engines[0].limits.depth, Some(4));
+        assert_eq!(options.engines[0].threads, 2);
+        assert_eq!(options.engines[1].name, "sf");
+        assert_eq!(options.engines[1].command.as_deref(), Some("/usr/bin/sf"));
+        assert_eq!(
+            options.engines[1].options,
+            [("Hash".to_string(), "64".to_string())]
+        );
+        assert_eq!((options.games, options.concurrency), (10, 2));
+        let sprt = options.sprt.unwrap();
+        assert_eq!((sprt.elo1, sprt.alpha, sprt.beta), (5.0, 0.1, 0.05));
+        assert_eq!(options.adjudication.resign, Some((3, 800)));
+        assert_eq!(options.adjudication.max_moves, Some(150));
+        assert_eq!(options.control.unwrap().to_string(), "10+0.1");
+        let depth = parse_args("--engine --engine --depth 3".split(' ').map(str::to_string));
+        assert_eq!(depth.unwrap().control, None);
+        assert!(parse_args("--engine".split(' ').map(str::to_string)).is_err());
+        assert!(parse_args("--engine cmd --engine".split(' ').map(str::to_string)).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use chess_lib::pgn::read_games;
+use chess_lib::puzzle::{to_csv, to_json, PuzzleFinder};
+use chess_lib::search::SearchLimits;
+use std::fs;
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: chess_lib-puzzles [--depth N | --movetime MS] [--threads N] [--format json|csv] PGN...
+Searches every position of the games in the PGN files for a single winning move, a forced mate or
+a decisive gain of material, and writes the positions as FEN with their solutions in UCI notation
+to standard output. The default is --depth 8 and --format json.";
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    limits: SearchLimits,
+    threads: usize,
+    format: Format,
+    inputs: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        limits: SearchLimits::default(),
+        threads: 1,
+        format: Format::Json,
+        inputs: vec![],
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => {
+                options.limits.depth = Some(
+                    args.next()
+                        .and_then(|depth| depth.parse().ok())
+                        .ok_or("--depth needs a number")?,
+                )
+            }
+            "--movetime" => {
+                let millis = args
+                    .next()
+                    .and_then(|millis| millis.parse().ok())
+                    .ok_or("--movetime needs a number of milliseconds")?;
+                options.limits.movetime = Some(Duration::from_millis(millis))
+            }
+            "--threads" => {
+                options.threads = args
+                    .next()
+                    .and_then(|threads| threads.parse().ok())
+                    .ok_or("--threads needs a number")?
+            }
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("json") => Format::Json,
+                    Some("csv") => Format::Csv,
+                    _ => return Err("--format needs json or csv".to_string()),
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => options.inputs.push(arg),
+        }
+    }
+    if options.inputs.is_empty() {
+        return Err("expected at least one PGN file".to_string());
+    }
+    if options.limits.depth.is_none() && options.limits.movetime.is_none() {
+        options.limits.depth = Some(8);
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let mut finder = PuzzleFinder::new(options.limits);
+    finder.searcher().set_threads(options.threads);
+    let mut puzzles = vec![];
+    for input in &options.inputs {
+        let text = match fs::read_to_string(input) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("can not read {}: {}", input, error);
+                std::process::exit(1);
+            }
+        };
+        for (i, game) in read_games(&text).iter().enumerate() {
+            match finder.find(game) {
+                Ok(found) => puzzles.extend(found),
+                Err(error) => eprintln!("{}: skipped game {}: {}", input, i + 1, error),
+            }
+        }
+    }
+    match options.format {
+        Format::Json => print!("{}", to_json(&puzzles)),
+        Format::Csv => print!("{}", to_csv(&puzzles)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["--depth", "6", "--format", "csv", "a.pgn", "b.pgn"];
+        let options = parse_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.limits.depth, Some(6));
+        assert_eq!(options.format, Format::Csv);
+        assert_eq!(options.inputs, ["a.pgn", "b.pgn"]);
+        let options = parse_args(["a.pgn"].iter().map(|arg| arg.to_string())).unwrap();
+        assert_eq!(options.limits.depth, Some(8));
+        assert_eq!(options.format, Format::Json);
+        assert!(parse_args(
+            ["--format", "xml", "a.pgn"]
+                .iter()
+                .map(|arg| arg.to_string())
+        )
+        .is_err());
+        assert!(parse_args(std::iter::empty()).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use chess_lib::book::Book;
+use chess_lib::engine::{Engine, DEFAULT_HASH_MB};
+use chess_lib::search::SearchLimits;
+use chess_lib::{ChessBoard, Move};
+use std::io::{self, BufRead};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
+
+/// Handles one line of input. Returns false when the engine should quit.
+fn handle(engine: &mut Engine, line: &str) -> bool {
+    let mut tokens = line.split_whitespace();
+    match tokens.next() {
+        Some("uci") => {
+            println!("id name chess_lib {}", env!("CARGO_PKG_VERSION"));
+            println!("id author chess_lib contributors");
+            println!(
+                "option name Hash type spin default {} min 1 max 4096",
+                DEFAULT_HASH_MB
+            );
+            println!(
+                "option name Threads type spin default 1 min 1 max {}",
+                MAX_THREADS
+            );
+            println!(
+                "option name MultiPV type spin default 1 min 1 max {}",
+                MAX_MULTI_PV
+            );
+            println!("option name UCI_Chess960 type check default false");
+            println!("option name OwnBook type check default false");
+            println!("option name BookFile type string default <empty>");
+            println!("uciok");
+        }
+        Some("isready") => println!("readyok"),
+        Some("ucinewgame") => engine.new_game(),
+        Some("setoption") => set_option(engine, &tokens.collect::<Vec<_>>()),
+        Some("position") => set_position(engine, &tokens.collect::<Vec<_>>()),
+        Some("go") => {
+            let limits = parse_go(engine.board(), &tokens.collect::<Vec<_>>());
+            go(engine, limits)
+        }
+        Some("stop") => engine.stop(),
+        Some("quit") => {
+            engine.stop();
+            return false;
+        }
+        _ => {}
+    }
+    true
+}
+
+fn set_option(engine: &mut Engine, tokens: &[&str]) {
+    let name_end = tokens
+        .iter()
+        .position(|token| *token == "value")
+        .unwrap_or(tokens.len());
+    let name = tokens.get(1..name_end).unwrap_or_default().join(" ");
+    let value = tokens.get(name_end + 1..).unwrap_or_default().join(" ");
+    match name.to_lowercase().as_str() {
+        "hash" => {
+            if let Ok(megabytes) = value.parse::<usize>() {
+                engine.set_hash_size(megabytes.clamp(1, 4096));
+            }
+        }
+        "threads" => {
+            if let Ok(threads) = value.parse::<usize>() {
+                engine.set_threads(threads.clamp(1, MAX_THREADS));
+            }
+        }
+        "multipv" => {
+            if let Ok(lines) = value.parse::<usize>() {
+                engine.set_multi_pv(lines.clamp(1, MAX_MULTI_PV));
+            }
+        }
+        "uci_chess960" => engine.set_chess960(value == "true"),
+        "ownbook" => engine.set_use_book(value == "true"),
+        "bookfile" => match Book::open(&value) {
+            Ok(book) => engine.set_book(Some(book)),
+            Err(error) => println!("info string can not read book {}: {}", value, error),
+        },
+        _ => println!("info string unknown option {}", name),
+    }
+}
+
+fn set_position(engine: &mut Engine, tokens: &[&str]) {
+    let moves_start = tokens
+        .iter()
+        .position(|token| *token == "moves")
+        .unwrap_or(tokens.len());
+    let board = match tokens.first() {
+        Some(&"startpos") => ChessBoard::create(),
+        Some(&"fen") => match ChessBoard::from_fen(&tokens[1..moves_start].join(" ")) {
+            Ok(board) => board,
+            Err(error) => {
+                println!("info string invalid fen: {}", error);
+                return;
+            }
+        },
+        _ => return,
+    };
+    engine.set_position(board);
+    for uci in tokens.iter().skip(moves_start + 1) {
+        if engine.play_uci(uci).is_none() {
+            println!("info string illegal move {}", uci);
+            break;
+        }
+    }
+}
+
+fn go(engine: &mut Engine, limits: SearchLimits) {
+    let stop = engine.stop_flag();
+    let infinite = limits.infinite;
+    engine.start_search(
+        limits,
+        |info| {
+            let pv: Vec<String> = info.pv.iter().map(Move::to_uci).collect();
+            println!(
+                "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+                info.depth,
+                info.multi_pv,
+                info.score.to_uci(),
+                info.nodes,
+                info.nps(),
+                info.time.as_millis(),
+                pv.join(" ")
+            );
+        },
+        move |best| {
+            // In infinite mode the GUI expects bestmove only after it sent stop.
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            match best {
+                Some(mv) => println!("bestmove {}", mv.to_uci()),
+                None => println!("bestmove 0000"),
+            }
+        },
+    );
+}
+
+/// Reads the limits of a go command, e.g. "wtime 60000 btime 60000 winc 1000 binc 1000".
+fn parse_go(board: &ChessBoard, tokens: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let millis = |value: Option<&&str>| {
+        value
+            .and_then(|value| value.parse::<i64>().ok())
+            .map(|ms| Duration::from_millis(ms.max(0) as u64))
+    };
+    let mut i = 0;
+    while i < tokens.len() {
+        let value = tokens.get(i + 1);
+        match tokens[i] {
+            "wtime" => limits.white_time = millis(value),
+            "btime" => limits.black_time = millis(value),
+            "winc" => limits.white_increment = millis(value),
+            "binc" => limits.black_increment = millis(value),
+            "movetime" => limits.movetime = millis(value),
+            "movestogo" => limits.moves_to_go = value.and_then(|v| v.parse().ok()),
+            "depth" => limits.depth = value.and_then(|v| v.parse().ok()),
+            "nodes" => limits.nodes = value.and_then(|v| v.parse().ok()),
+            "infinite" => {
+                limits.infinite = true;
+                i += 1;
+                continue;
+            }
+            "searchmoves" => {
+                // The moves run until the next keyword, which doesn't parse as a move.
+                i += 1;
+                while let Some(mv) = tokens.get(i).and_then(|uci| board.parse_uci_move(uci)) {
+                    limits.search_moves.push(mv);
+                    i += 1;
+                }
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+    limits
+}
+
+fn main() {
+    // The endgame bitbases take a moment to build, so that happens before the first search.
+    std::thread::spawn(chess_lib::bitbase::generate_bitbases);
+    let mut engine = Engine::new();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !handle(&mut engine, &line) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn parse_go_test() {
+        let board = ChessBoard::create();
+        let limits = parse_go(
+            &board,
+            &["wtime", "60000", "btime", "-5", "winc", "1000", "infinite"],
+        );
+        assert_eq!(limits.white_time, Some(Duration::from_secs(60)));
+        assert_eq!(limits.black_time, Some(Duration::ZERO));
+        assert_eq!(limits.white_increment, Some(Duration::from_secs(1)));
+        assert!(limits.infinite);
+        let limits = parse_go(&board, &["searchmoves", "e2e4", "d2d4", "depth", "7"]);
+        assert_eq!(limits.search_moves.len(), 2);
+        assert_eq!(limits.depth, Some(7));
+        let limits = parse_go(&board, &["depth", "7", "movetime", "250"]);
+        assert_eq!(limits.depth, Some(7));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn position_command_test() {
+        let mut engine = Engine::new();
+        handle(&mut engine, "position startpos moves e2e4 e7e5 g1f3");
+        assert_eq!(
+            engine.board().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        assert_eq!(engine.history().len(), 3);
+        handle(
+            &mut engine,
+            "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1g1",
+        );
+        assert_eq!(engine.board().to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn chess960_option_test() {
+        let mut engine = Engine::new();
+        handle(&mut engine, "setoption name UCI_Chess960 value true");
+        handle(
+            &mut engine,
+            "position fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1",
+        );
+        assert_eq!(engine.board().to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn commands_during_infinite_search_test() {
+        // Commands that change the engine end an infinite search instead of waiting for it.
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut engine = Engine::new();
+            handle(&mut engine, "go infinite");
+            handle(&mut engine, "ucinewgame");
+            let stopped = !engine.is_searching();
+            handle(&mut engine, "go infinite");
+            handle(&mut engine, "setoption name Hash value 1");
+            handle(&mut engine, "go infinite");
+            handle(&mut engine, "go depth 1");
+            handle(&mut engine, "stop");
+            sender.send(stopped && !engine.is_searching()).unwrap();
+        });
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(30)), Ok(true));
+    }
+}
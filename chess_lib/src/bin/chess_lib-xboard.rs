@@ -0,0 +1,378 @@
+use chess_lib::engine::Engine;
+use chess_lib::search::{Score, SearchLimits};
+use chess_lib::{ChessBoard, Color, Move};
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Everything the main loop waits for: lines from the GUI and moves from finished searches.
+enum Event {
+    Line(String),
+    EngineMove(u64, Option<Move>),
+}
+
+/// The time control set by the level command.
+struct TimeControl {
+    moves_per_session: u32,
+    base: Duration,
+    increment: Duration,
+}
+
+struct Xboard {
+    engine: Engine,
+    /// The color the engine plays, or None in force mode.
+    engine_color: Option<Color>,
+    post: bool,
+    time_control: TimeControl,
+    move_time: Option<Duration>,
+    max_depth: Option<u32>,
+    engine_time: Option<Duration>,
+    /// Searches are numbered so that moves of searches that were cancelled can be ignored.
+    search_id: u64,
+    events: Sender<Event>,
+}
+
+impl Xboard {
+    fn new(events: Sender<Event>) -> Self {
+        Self {
+            engine: Engine::new(),
+            engine_color: Some(Color::Black),
+            post: false,
+            time_control: TimeControl {
+                moves_per_session: 40,
+                base: Duration::from_secs(300),
+                increment: Duration::ZERO,
+            },
+            move_time: None,
+            max_depth: None,
+            engine_time: None,
+            search_id: 0,
+            events,
+        }
+    }
+    /// Handles one line from the GUI. Returns false when the engine should quit.
+    fn handle(&mut self, line: &str) -> bool {
+        let mut tokens = line.split_whitespace();
+        let command = match tokens.next() {
+            Some(command) => command,
+            None => return true,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+        match command {
+            "protover" => {
+                println!(
+                    "feature myname=\"chess_lib {}\" ping=1 setboard=1 usermove=1 san=0 time=1 \
+                     draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 memory=1 done=1",
+                    env!("CARGO_PKG_VERSION")
+                );
+            }
+            "new" => {
+                self.cancel_search();
+                self.engine.new_game();
+                self.engine_color = Some(Color::Black);
+                self.max_depth = None;
+                self.move_time = None;
+                self.engine_time = None;
+            }
+            "force" => {
+                self.cancel_search();
+                self.engine_color = None;
+            }
+            "go" => {
+                self.cancel_search();
+                self.engine_color = Some(self.engine.board().faction_decider());
+                self.think();
+            }
+            "playother" => {
+                self.cancel_search();
+                self.engine_color = Some(self.engine.board().faction_decider().opposite());
+            }
+            "usermove" => self.user_move(arguments.first().copied().unwrap_or_default()),
+            "undo" => {
+                self.cancel_search();
+                self.engine.undo();
+            }
+            "remove" => {
+                self.cancel_search();
+                self.engine.undo();
+                self.engine.undo();
+            }
+            "setboard" => {
+                self.cancel_search();
+                match ChessBoard::from_fen(&arguments.join(" ")) {
+                    Ok(board) => self.engine.set_position(board),
+                    Err(error) => println!("tellusererror Illegal position: {}", error),
+                }
+            }
+            "level" => {
+                if let Some(time_control) = parse_level(&arguments) {
+                    self.time_control = time_control;
+                    self.move_time = None;
+                }
+            }
+            "st" => {
+                self.move_time = arguments
+                    .first()
+                    .and_then(|seconds| seconds.parse::<f64>().ok())
+                    .map(Duration::from_secs_f64);
+            }
+            "sd" => self.max_depth = arguments.first().and_then(|depth| depth.parse().ok()),
+            "time" => {
+                self.engine_time = arguments
+                    .first()
+                    .and_then(|centiseconds| centiseconds.parse::<u64>().ok())
+                    .map(|centiseconds| Duration::from_millis(centiseconds * 10));
+            }
+            "memory" => {
+                if let Some(megabytes) = arguments.first().and_then(|mb| mb.parse::<usize>().ok()) {
+                    self.cancel_search();
+                    self.engine.set_hash_size(megabytes.clamp(1, 4096));
+                }
+            }
+            "ping" => {
+                println!("pong {}", arguments.first().copied().unwrap_or_default());
+            }
+            "result" => {
+                self.cancel_search();
+                self.engine_color = None;
+            }
+            "?" => {
+                if self.engine.is_searching() {
+                    // Unlike cancel_search the move that was found so far is still played.
+                    self.engine.stop();
+                }
+            }
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "quit" => {
+                self.cancel_search();
+                return false;
+            }
+            "xboard" | "accepted" | "rejected" | "otim" | "random" | "hard" | "easy"
+            | "computer" | "name" | "white" | "black" => {}
+            _ => {
+                // Older GUIs send moves without the usermove prefix.
+                if self.engine.board().parse_uci_move(command).is_some() {
+                    self.user_move(command);
+                } else {
+                    println!("Error (unknown command): {}", command);
+                }
+            }
+        }
+        true
+    }
+    fn user_move(&mut self, uci: &str) {
+        self.cancel_search();
+        if self.engine.play_uci(uci).is_none() {
+            println!("Illegal move: {}", uci);
+            return;
+        }
+        if self.report_result() {
+            return;
+        }
+        if self.engine_color == Some(self.engine.board().faction_decider()) {
+            self.think();
+        }
+    }
+    fn engine_move(&mut self, id: u64, best: Option<Move>) {
+        if id != self.search_id {
+            return;
+        }
+        self.engine.wait();
+        if let Some(mv) = best {
+            self.engine.play(&mv);
+            println!("move {}", mv.to_uci());
+            self.report_result();
+        }
+    }
+    /// Prints the result if the game is over and leaves the engine in force mode.
+    fn report_result(&mut self) -> bool {
+        let result = match game_result(&self.engine) {
+            Some(result) => result,
+            None => return false,
+        };
+        println!("{}", result);
+        self.engine_color = None;
+        true
+    }
+    fn cancel_search(&mut self) {
+        self.search_id += 1;
+        if self.engine.is_searching() {
+            self.engine.stop();
+        }
+    }
+    fn think(&mut self) {
+        self.search_id += 1;
+        let board = self.engine.board();
+        let mut limits = SearchLimits {
+            depth: self.max_depth,
+            movetime: self.move_time,
+            ..Default::default()
+        };
+        if self.move_time.is_none() {
+            let remaining = self.engine_time.unwrap_or(self.time_control.base);
+            let increment = Some(self.time_control.increment);
+            match board.faction_decider() {
+                Color::White => {
+                    limits.white_time = Some(remaining);
+                    limits.white_increment = increment;
+                }
+                Color::Black => {
+                    limits.black_time = Some(remaining);
+                    limits.black_increment = increment;
+                }
+            }
+            let session = self.time_control.moves_per_session;
+            if session > 0 {
+                let moves_played = (board.turn - 1) / 2;
+                limits.moves_to_go = Some(session - moves_played % session);
+            }
+        }
+
+        let post = self.post;
+        let id = self.search_id;
+        let events = self.events.clone();
+        self.engine.start_search(
+            limits,
+            move |info| {
+                if post {
+                    let pv: Vec<String> = info.pv.iter().map(Move::to_uci).collect();
+                    println!(
+                        "{} {} {} {} {}",
+                        info.depth,
+                        xboard_score(info.score),
+                        info.time.as_millis() / 10,
+                        info.nodes,
+                        pv.join(" ")
+                    );
+                }
+            },
+            move |best| {
+                let _ = events.send(Event::EngineMove(id, best));
+            },
+        );
+    }
+}
+
+/// Converts a score to centipawns, with mates written as 100000 + N the way xboard expects.
+fn xboard_score(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => 100000 + moves,
+        Score::Mate(moves) => -100000 + moves,
+    }
+}
+
+/// Reads "level MPS BASE INC", where BASE is in minutes or minutes:seconds and INC in seconds.
+fn parse_level(arguments: &[&str]) -> Option<TimeControl> {
+    let moves_per_session = arguments.first()?.parse().ok()?;
+    let base = match arguments.get(1)?.split_once(':') {
+        Some((minutes, seconds)) => {
+            minutes.parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?
+        }
+        None => arguments[1].parse::<u64>().ok()? * 60,
+    };
+    let increment = arguments.get(2)?.parse::<f64>().ok()?;
+    Some(TimeControl {
+        moves_per_session,
+        base: Duration::from_secs(base),
+        increment: Duration::from_secs_f64(increment.max(0.0)),
+    })
+}
+
+/// Returns the result line for a game that has ended by the rules, None if it is still going.
+fn game_result(engine: &Engine) -> Option<&'static str> {
+    let board = engine.board();
+    if board.legal_moves().is_empty() {
+        return Some(match (board.is_in_check(), board.faction_decider()) {
+            (true, Color::White) => "0-1 {Black mates}",
+            (true, Color::Black) => "1-0 {White mates}",
+            (false, _) => "1/2-1/2 {Stalemate}",
+        });
+    }
+    if board.halfmove_clock >= 100 {
+        return Some("1/2-1/2 {50 move rule}");
+    }
+    let hash = board.zobrist_hash();
+    if engine.history().iter().filter(|&&h| h == hash).count() >= 2 {
+        return Some("1/2-1/2 {3-fold repetition}");
+    }
+    None
+}
+
+fn main() {
+    let (sender, receiver) = mpsc::channel();
+    let lines = sender.clone();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if lines.send(Event::Line(line)).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = lines.send(Event::Line("quit".to_string()));
+    });
+
+    let mut xboard = Xboard::new(sender);
+    for event in receiver {
+        match event {
+            Event::Line(line) => {
+                if !xboard.handle(&line) {
+                    return;
+                }
+            }
+            Event::EngineMove(id, best) => xboard.engine_move(id, best),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_level_test() {
+        let level = parse_level(&["40", "5", "0"]).unwrap();
+        assert_eq!(level.moves_per_session, 40);
+        assert_eq!(level.base, Duration::from_secs(300));
+        let level = parse_level(&["0", "2:30", "1.5"]).unwrap();
+        assert_eq!(level.base, Duration::from_secs(150));
+        assert_eq!(level.increment, Duration::from_millis(1500));
+        assert!(parse_level(&["40"]).is_none());
+    }
+
+    #[test]
+    fn force_mode_and_undo_test() {
+        let (sender, _receiver) = mpsc::channel();
+        let mut xboard = Xboard::new(sender);
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("usermove e7e5");
+        assert!(!xboard.engine.is_searching());
+        xboard.handle("undo");
+        assert_eq!(
+            xboard.engine.board().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+    }
+
+    #[test]
+    fn engine_replies_test() {
+        let (sender, receiver) = mpsc::channel();
+        let mut xboard = Xboard::new(sender);
+        xboard.handle("new");
+        xboard.handle("sd 2");
+        xboard.handle("usermove e2e4");
+        match receiver.recv().unwrap() {
+            Event::EngineMove(id, best) => xboard.engine_move(id, best),
+            Event::Line(_) => unreachable!(),
+        }
+        assert_eq!(xboard.engine.board().faction_decider(), Color::White);
+        assert_eq!(xboard.engine.history().len(), 2);
+    }
+}
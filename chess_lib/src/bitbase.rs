@@ -0,0 +1,384 @@
+use crate::game::Outcome;
+use crate::{ChessBoard, ChessPiece, Color, Variant};
+use std::sync::OnceLock;
+
+/// Positions per table: side to move, then the squares of the stronger king, the lone king and
+/// the extra piece.
+const SIZE: usize = 2 * 64 * 64 * 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    Queen,
+    Rook,
+    Pawn,
+}
+
+/// One bit per position: set when the side with the extra piece wins with perfect play.
+struct Bitbase {
+    bits: Vec<u64>,
+}
+
+impl Bitbase {
+    fn get(&self, index: usize) -> bool {
+        self.bits[index / 64] >> (index % 64) & 1 == 1
+    }
+    fn set(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+}
+
+fn index(strong_to_move: bool, strong_king: usize, weak_king: usize, piece: usize) -> usize {
+    ((strong_to_move as usize * 64 + strong_king) * 64 + weak_king) * 64 + piece
+}
+
+fn adjacent(a: usize, b: usize) -> bool {
+    (a % 8).abs_diff(b % 8) <= 1 && (a / 8).abs_diff(b / 8) <= 1
+}
+
+fn offset(square: usize, dx: i32, dy: i32) -> Option<usize> {
+    let x = (square % 8) as i32 + dx;
+    let y = (square / 8) as i32 + dy;
+    ((0..8).contains(&x) && (0..8).contains(&y)).then(|| (y * 8 + x) as usize)
+}
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+fn directions(kind: Kind) -> &'static [(i32, i32)] {
+    match kind {
+        Kind::Queen => &KING_STEPS,
+        Kind::Rook => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+        Kind::Pawn => &[],
+    }
+}
+
+fn attacks(kind: Kind, piece: usize, target: usize, blocker: usize) -> bool {
+    /// Whether the piece attacks target, with the stronger king on blocker possibly in the way.
+    /// Pawns belong to the stronger side, which moves up the board (towards row 0).
+    if kind == Kind::Pawn {
+        return offset(piece, -1, -1) == Some(target) || offset(piece, 1, -1) == Some(target);
+    }
+    directions(kind).iter().any(|(dx, dy)| {
+        let mut square = piece;
+        while let Some(next) = offset(square, *dx, *dy) {
+            if next == target {
+                return true;
+            }
+            if next == blocker {
+                return false;
+            }
+            square = next;
+        }
+        false
+    })
+}
+
+fn any_piece_move(
+    kind: Kind,
+    piece: usize,
+    kings: [usize; 2],
+    mut f: impl FnMut(usize) -> bool,
+) -> bool {
+    /// Whether f holds for any square the piece can move to.
+    if kind == Kind::Pawn {
+        let one = match offset(piece, 0, -1).filter(|s| !kings.contains(s)) {
+            Some(one) => one,
+            None => return false,
+        };
+        let two = offset(one, 0, -1).filter(|s| piece / 8 == 6 && !kings.contains(s));
+        return f(one) || two.is_some_and(f);
+    }
+    directions(kind).iter().any(|(dx, dy)| {
+        let mut square = piece;
+        while let Some(next) = offset(square, *dx, *dy).filter(|s| !kings.contains(s)) {
+            if f(next) {
+                return true;
+            }
+            square = next;
+        }
+        false
+    })
+}
+
+fn transform(square: usize, symmetry: usize) -> usize {
+    /// One of the eight symmetries of the board: bit 0 mirrors the files, bit 1 the rows and
+    /// bit 2 swaps files with rows.
+    let (mut x, mut y) = (square % 8, square / 8);
+    if symmetry & 4 != 0 {
+        std::mem::swap(&mut x, &mut y);
+    }
+    if symmetry & 1 != 0 {
+        x = 7 - x;
+    }
+    if symmetry & 2 != 0 {
+        y = 7 - y;
+    }
+    y * 8 + x
+}
+
+fn symmetries(kind: Kind) -> usize {
+    /// Pawns only allow mirroring the files.
+    match kind {
+        Kind::Pawn => 2,
+        _ => 8,
+    }
+}
+
+fn canonical(kind: Kind, weak_king: usize, piece: usize) -> bool {
+    /// Whether the position is the one of its symmetric copies that the sweeps look at: the
+    /// pawn on files a to d, or the lone king in the triangle a8-d8-d5.
+    let (x, y) = (weak_king % 8, weak_king / 8);
+    match kind {
+        Kind::Pawn => piece % 8 < 4,
+        _ => x < 4 && y < 4 && y <= x,
+    }
+}
+
+fn legal(
+    strong_to_move: bool,
+    kind: Kind,
+    strong_king: usize,
+    weak_king: usize,
+    piece: usize,
+) -> bool {
+    let distinct = strong_king != weak_king && piece != strong_king && piece != weak_king;
+    let pawn_row = kind != Kind::Pawn || (1..7).contains(&(piece / 8));
+    // The lone king can't be in check when it is not its turn.
+    let checked = strong_to_move && attacks(kind, piece, weak_king, strong_king);
+    distinct && pawn_row && !adjacent(strong_king, weak_king) && !checked
+}
+
+fn generate(kind: Kind) -> Bitbase {
+    /// Retrograde analysis by repeated sweeps: a position is won once the stronger side has a
+    /// move into a won position, or every move of the lone king leads into one. Sweeps continue
+    /// until nothing changes, and what is left is drawn. Pawns promote into the queen and rook
+    /// tables.
+    let mut table = Bitbase {
+        bits: vec![0; SIZE / 64],
+    };
+    let mut pending: Vec<usize> = (0..SIZE)
+        .filter(|here| canonical(kind, here / 64 % 64, here % 64))
+        .filter(|here| {
+            let (strong_to_move, strong_king) = (here / (64 * 64 * 64) == 1, here / (64 * 64) % 64);
+            legal(strong_to_move, kind, strong_king, here / 64 % 64, here % 64)
+        })
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        pending.retain(|&here| {
+            let (strong_king, weak_king, piece) =
+                (here / (64 * 64) % 64, here / 64 % 64, here % 64);
+            let won = if here / (64 * 64 * 64) == 1 {
+                strong_wins(&table, kind, strong_king, weak_king, piece)
+            } else {
+                weak_loses(&table, kind, strong_king, weak_king, piece)
+            };
+            if won {
+                for symmetry in 0..symmetries(kind) {
+                    let (strong_king, weak_king, piece) = (
+                        transform(strong_king, symmetry),
+                        transform(weak_king, symmetry),
+                        transform(piece, symmetry),
+                    );
+                    table.set(index(here >= 64 * 64 * 64, strong_king, weak_king, piece));
+                }
+                changed = true;
+            }
+            !won
+        });
+    }
+    table
+}
+
+fn strong_wins(table: &Bitbase, kind: Kind, king: usize, weak_king: usize, piece: usize) -> bool {
+    let king_wins = KING_STEPS.iter().any(|(dx, dy)| {
+        offset(king, *dx, *dy).is_some_and(|to| {
+            to != piece && !adjacent(to, weak_king) && table.get(index(false, to, weak_king, piece))
+        })
+    });
+    king_wins
+        || any_piece_move(kind, piece, [king, weak_king], |to| {
+            if kind == Kind::Pawn && to / 8 == 0 {
+                let promoted = index(false, king, weak_king, to);
+                return tables().queen.get(promoted) || tables().rook.get(promoted);
+            }
+            table.get(index(false, king, weak_king, to))
+        })
+}
+
+fn weak_loses(table: &Bitbase, kind: Kind, strong_king: usize, king: usize, piece: usize) -> bool {
+    let mut can_move = false;
+    for (dx, dy) in KING_STEPS {
+        let to = match offset(king, dx, dy) {
+            Some(to) if !adjacent(to, strong_king) => to,
+            _ => continue,
+        };
+        if to == piece {
+            // Taking the undefended piece draws.
+            return false;
+        }
+        if attacks(kind, piece, to, strong_king) {
+            continue;
+        }
+        can_move = true;
+        if !table.get(index(true, strong_king, to, piece)) {
+            return false;
+        }
+    }
+    can_move || attacks(kind, piece, king, strong_king)
+}
+
+struct Tables {
+    queen: Bitbase,
+    rook: Bitbase,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| Tables {
+        queen: generate(Kind::Queen),
+        rook: generate(Kind::Rook),
+    })
+}
+
+fn pawn_table() -> &'static Bitbase {
+    static PAWN: OnceLock<Bitbase> = OnceLock::new();
+    PAWN.get_or_init(|| generate(Kind::Pawn))
+}
+
+pub fn generate_bitbases() {
+    /// Builds the KQK, KRK and KPK tables now instead of on the first probe. This takes a moment,
+    /// so programs that probe during timed play should call it at startup.
+    pawn_table();
+}
+
+pub fn probe_simple_endgame(board: &ChessBoard) -> Option<Outcome> {
+    /// The result with perfect play of a KQK, KRK or KPK position, or of two bare kings, with
+    /// either side having the extra piece. The 50 move rule is ignored. None for other material,
+    /// other variants and illegal positions.
+    if board.variant != Variant::Standard {
+        return None;
+    }
+    let mut kings = [None, None];
+    let mut extra = None;
+    for (y, row) in board.board.iter().enumerate() {
+        for (x, square) in row.iter().enumerate() {
+            let piece = match square {
+                Some(piece) => *piece,
+                None => continue,
+            };
+            let kind = match piece {
+                ChessPiece::King(color) => {
+                    kings[(color == Color::Black) as usize] = Some((x, y));
+                    continue;
+                }
+                ChessPiece::Queen(_) => Kind::Queen,
+                ChessPiece::Rook(_) => Kind::Rook,
+                ChessPiece::Pawn(_) => Kind::Pawn,
+                _ => return None,
+            };
+            if extra.is_some() {
+                return None;
+            }
+            extra = Some((kind, piece.color(), (x, y)));
+        }
+    }
+    let (white_king, black_king) = (kings[0]?, kings[1]?);
+    let (kind, strong, piece) = match extra {
+        Some(extra) => extra,
+        None => return Some(Outcome::Draw),
+    };
+    // Tables are built for the stronger side playing up the board, as White does.
+    let square = |(x, y): (usize, usize)| match strong {
+        Color::White => y * 8 + x,
+        Color::Black => (7 - y) * 8 + x,
+    };
+    let (strong_king, weak_king) = match strong {
+        Color::White => (white_king, black_king),
+        Color::Black => (black_king, white_king),
+    };
+    let strong_to_move = board.faction_decider() == strong;
+    let (strong_king, weak_king, piece) = (square(strong_king), square(weak_king), square(piece));
+    if !legal(strong_to_move, kind, strong_king, weak_king, piece) {
+        return None;
+    }
+    let here = index(strong_to_move, strong_king, weak_king, piece);
+    let won = match kind {
+        Kind::Queen => tables().queen.get(here),
+        Kind::Rook => tables().rook.get(here),
+        Kind::Pawn => pawn_table().get(here),
+    };
+    let outcome = if won {
+        Outcome::Win(strong)
+    } else {
+        Outcome::Draw
+    };
+    Some(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(fen: &str) -> Option<Outcome> {
+        probe_simple_endgame(&ChessBoard::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn heavy_piece_test() {
+        // With the stronger side to move every legal KQK and KRK position is won.
+        for (table, kind) in [(&tables().queen, Kind::Queen), (&tables().rook, Kind::Rook)] {
+            for strong_king in 0..64 {
+                for weak_king in 0..64 {
+                    for piece in 0..64 {
+                        if legal(true, kind, strong_king, weak_king, piece) {
+                            assert!(table.get(index(true, strong_king, weak_king, piece)));
+                        }
+                    }
+                }
+            }
+        }
+        let white = Some(Outcome::Win(Color::White));
+        assert_eq!(probe("8/8/8/8/8/8/1Q6/k1K5 b - - 0 1"), white);
+        assert_eq!(probe("8/8/8/8/8/8/1Q6/k6K b - - 0 1"), Some(Outcome::Draw));
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(Outcome::Draw));
+        assert_eq!(probe("8/8/8/3k4/8/8/8/R3K3 b - - 0 1"), white);
+        assert_eq!(
+            probe("8/8/8/3K4/8/8/8/r3k3 w - - 0 1"),
+            Some(Outcome::Win(Color::Black))
+        );
+        assert_eq!(probe("8/8/8/3k4/8/8/8/4K3 w - - 0 1"), Some(Outcome::Draw));
+        assert_eq!(probe("8/8/8/3k4/8/8/8/2B1K3 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn pawn_test() {
+        let white = Some(Outcome::Win(Color::White));
+        let draw = Some(Outcome::Draw);
+        // Opposition: the side to move decides.
+        assert_eq!(probe("8/8/4k3/8/4K3/4P3/8/8 w - - 0 1"), draw);
+        assert_eq!(probe("8/8/4k3/8/4K3/4P3/8/8 b - - 0 1"), white);
+        // A king on the sixth in front of its pawn wins, but stalemate saves the lone king.
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), white);
+        assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), draw);
+        // The rule of the square.
+        assert_eq!(probe("8/8/8/6k1/8/8/P7/7K w - - 0 1"), white);
+        assert_eq!(probe("8/8/8/6k1/8/8/P7/7K b - - 0 1"), draw);
+        // A king in front of a rook pawn holds.
+        assert_eq!(probe("k7/8/8/8/8/8/P7/7K w - - 0 1"), draw);
+        // Black's pawn runs down the board.
+        assert_eq!(
+            probe("7K/p7/8/8/8/8/8/k7 b - - 0 1"),
+            Some(Outcome::Win(Color::Black))
+        );
+    }
+}
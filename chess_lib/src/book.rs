@@ -0,0 +1,419 @@
+use crate::game::Outcome;
+use crate::pgn::{PgnError, PgnGame};
+use crate::{ChessBoard, ChessPiece, Color, Move};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// One 16 byte record of a Polyglot book: the position key, the move, how often it should be
+/// played relative to the other moves in the position, and a field some tools use for learning.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    pub fn from_bytes(bytes: &[u8; 16]) -> BookEntry {
+        /// Reads a record as stored in the file, with all fields big-endian.
+        let key = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+        BookEntry {
+            key,
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+pub fn encode_move(board: &ChessBoard, mv: &Move) -> u16 {
+    /// The Polyglot encoding of a move: destination file and rank in the low six bits, then the
+    /// source square and the promotion piece. Castling is written as the king taking its own rook,
+    /// so e1g1 becomes e1h1 even outside Chess960.
+    let destination = board.castling_rook(mv).unwrap_or(mv.destination);
+    let promotion = match mv.promotion {
+        Some(ChessPiece::Knight(_)) => 1,
+        Some(ChessPiece::Bishop(_)) => 2,
+        Some(ChessPiece::Rook(_)) => 3,
+        Some(ChessPiece::Queen(_)) => 4,
+        _ => 0,
+    };
+    let square = |(x, y): (usize, usize)| ((7 - y) * 8 + x) as u16;
+    square(destination) | square(mv.source) << 6 | promotion << 12
+}
+
+/// A Polyglot opening book, read from a `.bin` file. Entries are kept sorted by key, as in the
+/// file, so the moves of a position are found with a binary search.
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Book> {
+        let bytes = std::fs::read(path)?;
+        Ok(Book::from_bytes(&bytes))
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Book {
+        /// Reads the records of a book file. A truncated record at the end is ignored.
+        let mut entries: Vec<BookEntry> = bytes
+            .chunks_exact(16)
+            .map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap()))
+            .collect();
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+    pub fn from_entries(mut entries: Vec<BookEntry>) -> Book {
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+    pub fn entries(&self) -> &[BookEntry] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        /// The book in the Polyglot file format.
+        self.entries.iter().flat_map(BookEntry::to_bytes).collect()
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+    pub fn lookup(&self, key: u64) -> &[BookEntry] {
+        /// The entries stored for a position key.
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+    pub fn probe(&self, board: &ChessBoard) -> Vec<(Move, u16)> {
+        /// The legal book moves of the position with their weights, most played first. Entries
+        /// whose move is not legal in the position (a key collision) are skipped.
+        let entries = self.lookup(board.polyglot_hash());
+        if entries.is_empty() {
+            return vec![];
+        }
+        let legal = board.legal_moves();
+        let mut moves: Vec<(Move, u16)> = entries
+            .iter()
+            .filter_map(|entry| {
+                let mv = legal
+                    .iter()
+                    .find(|mv| encode_move(board, mv) == entry.raw_move)?;
+                Some((*mv, entry.weight))
+            })
+            .collect();
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        moves
+    }
+    pub fn best_move(&self, board: &ChessBoard) -> Option<Move> {
+        /// The book move with the highest weight.
+        self.probe(board).first().map(|(mv, _)| *mv)
+    }
+    pub fn weighted_move(&self, board: &ChessBoard, random: u64) -> Option<Move> {
+        /// Picks a book move with a probability proportional to its weight, using random as the
+        /// source of chance. Moves with weight 0 are never picked.
+        let moves = self.probe(board);
+        let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = random % total;
+        for (mv, weight) in moves {
+            if pick < weight as u64 {
+                return Some(mv);
+            }
+            pick -= weight as u64;
+        }
+        None
+    }
+}
+
+/// How the games went after a move was played in a position.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveStats {
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.white_wins + self.draws + self.black_wins
+    }
+    pub fn score(&self, color: Color) -> u32 {
+        /// Half points scored by color: two for a win and one for a draw. This is the weight
+        /// Polyglot's own book maker gives a move.
+        let wins = match color {
+            Color::White => self.white_wins,
+            Color::Black => self.black_wins,
+        };
+        2 * wins + self.draws
+    }
+}
+
+const COMPACT_MAGIC: &[u8; 4] = b"CLBK";
+const COMPACT_RECORD: usize = 23;
+
+/// Collects the moves played in a set of games, up to a number of plies, with how each of them
+/// turned out. The result is written as a Polyglot book with build, or in a compact format that
+/// keeps the win, draw and loss counts so more games can be added later.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BookBuilder {
+    max_plies: usize,
+    /// The side to move and the statistics of each move, by Polyglot key.
+    positions: HashMap<u64, (Color, HashMap<u16, MoveStats>)>,
+}
+
+impl BookBuilder {
+    pub fn new(max_plies: usize) -> BookBuilder {
+        BookBuilder {
+            max_plies,
+            positions: HashMap::new(),
+        }
+    }
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<(), PgnError> {
+        /// Adds the first max_plies moves of a game. Unfinished games count for nothing and are
+        /// skipped, as are games from other variants, which Polyglot keys can not tell apart.
+        let result = match game.result {
+            Some(result) => result,
+            None => return Ok(()),
+        };
+        let board = game.starting_position()?;
+        if board.variant != crate::Variant::Standard {
+            return Ok(());
+        }
+        let played = game.replay()?;
+        for (board, mv) in played.iter().take(self.max_plies) {
+            let key = board.polyglot_hash();
+            let raw_move = encode_move(board, mv);
+            let stats = self
+                .positions
+                .entry(key)
+                .or_insert_with(|| (board.faction_decider(), HashMap::new()))
+                .1
+                .entry(raw_move)
+                .or_default();
+            match result {
+                Outcome::Win(Color::White) => stats.white_wins += 1,
+                Outcome::Win(Color::Black) => stats.black_wins += 1,
+                Outcome::Draw => stats.draws += 1,
+            }
+        }
+        Ok(())
+    }
+    pub fn add_pgn(&mut self, text: &str) -> (usize, Vec<PgnError>) {
+        /// Adds every game of a PGN file and returns how many were read, along with the errors of
+        /// the games that could not be replayed.
+        let games = crate::pgn::read_games(text);
+        let errors = games
+            .iter()
+            .filter_map(|game| self.add_game(game).err())
+            .collect();
+        (games.len(), errors)
+    }
+    pub fn positions(&self) -> usize {
+        self.positions.len()
+    }
+    pub fn stats(&self, board: &ChessBoard) -> Vec<(Move, MoveStats)> {
+        /// The moves collected for the position with their statistics, most played first.
+        let moves = match self.positions.get(&board.polyglot_hash()) {
+            Some((_, moves)) => moves,
+            None => return vec![],
+        };
+        let mut stats: Vec<(Move, MoveStats)> = board
+            .legal_moves()
+            .into_iter()
+            .filter_map(|mv| Some((mv, *moves.get(&encode_move(board, &mv))?)))
+            .collect();
+        stats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.games()));
+        stats
+    }
+    pub fn build(&self, min_games: u32) -> Book {
+        /// A Polyglot book of the moves played in at least min_games games, weighted by the score
+        /// of the side that played them. Weights of a position are scaled down together if they
+        /// do not fit the 16 bit field.
+        let mut entries = Vec::new();
+        for (key, (mover, moves)) in &self.positions {
+            let kept: Vec<(&u16, &MoveStats)> = moves
+                .iter()
+                .filter(|(_, stats)| stats.games() >= min_games)
+                .collect();
+            let scores: Vec<u32> = kept.iter().map(|(_, stats)| stats.score(*mover)).collect();
+            let max = scores.iter().copied().max().unwrap_or(0);
+            for ((raw_move, _), score) in kept.into_iter().zip(scores) {
+                let weight = if max > u16::MAX as u32 {
+                    (score as u64 * u16::MAX as u64 / max as u64) as u16
+                } else {
+                    score as u16
+                };
+                entries.push(BookEntry {
+                    key: *key,
+                    raw_move: *raw_move,
+                    weight,
+                    learn: 0,
+                });
+            }
+        }
+        entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight), entry.raw_move));
+        Book { entries }
+    }
+    pub fn to_compact(&self) -> Vec<u8> {
+        /// Writes the statistics in a compact format: "CLBK", the number of plies, then one
+        /// record per move with the key, the move in Polyglot encoding, the side to move and the
+        /// white wins, draws and black wins, all big-endian.
+        let mut bytes = COMPACT_MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.max_plies as u32).to_be_bytes());
+        let mut keys: Vec<&u64> = self.positions.keys().collect();
+        keys.sort();
+        for key in keys {
+            let (mover, moves) = &self.positions[key];
+            let mut raw_moves: Vec<&u16> = moves.keys().collect();
+            raw_moves.sort();
+            for raw_move in raw_moves {
+                let stats = moves[raw_move];
+                bytes.extend_from_slice(&key.to_be_bytes());
+                bytes.extend_from_slice(&raw_move.to_be_bytes());
+                bytes.push((*mover == Color::Black) as u8);
+                for count in [stats.white_wins, stats.draws, stats.black_wins] {
+                    bytes.extend_from_slice(&count.to_be_bytes());
+                }
+            }
+        }
+        bytes
+    }
+    pub fn from_compact(bytes: &[u8]) -> Option<BookBuilder> {
+        /// Reads statistics written by to_compact, so that more games can be added to them.
+        /// Returns None if the data is not in that format.
+        let records = bytes.strip_prefix(COMPACT_MAGIC)?;
+        let (plies, records) = records.split_first_chunk::<4>()?;
+        if records.len() % COMPACT_RECORD != 0 {
+            return None;
+        }
+        let mut builder = BookBuilder::new(u32::from_be_bytes(*plies) as usize);
+        for record in records.chunks_exact(COMPACT_RECORD) {
+            let number = |at: usize| u32::from_be_bytes(record[at..at + 4].try_into().unwrap());
+            let key = u64::from_be_bytes(record[0..8].try_into().unwrap());
+            let raw_move = u16::from_be_bytes([record[8], record[9]]);
+            let mover = match record[10] {
+                0 => Color::White,
+                1 => Color::Black,
+                _ => return None,
+            };
+            let stats = MoveStats {
+                white_wins: number(11),
+                draws: number(15),
+                black_wins: number(19),
+            };
+            let moves = &mut builder
+                .positions
+                .entry(key)
+                .or_insert_with(|| (mover, HashMap::new()))
+                .1;
+            moves.insert(raw_move, stats);
+        }
+        Some(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(board: &ChessBoard, uci: &str, weight: u16) -> BookEntry {
+        let mv = board.parse_uci_move(uci).unwrap();
+        BookEntry {
+            key: board.polyglot_hash(),
+            raw_move: encode_move(board, &mv),
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn book_probe_test() {
+        let board = ChessBoard::create();
+        let book = Book::from_entries(vec![
+            entry(&board, "d2d4", 10),
+            entry(&board, "e2e4", 30),
+            entry(&board, "a2a3", 0),
+        ]);
+        let book = Book::from_bytes(&book.to_bytes());
+        assert_eq!(book.len(), 3);
+        let moves = book.probe(&board);
+        let names: Vec<String> = moves.iter().map(|(mv, _)| mv.to_uci()).collect();
+        assert_eq!(names, ["e2e4", "d2d4", "a2a3"]);
+        assert_eq!(book.best_move(&board).unwrap().to_uci(), "e2e4");
+        assert_eq!(book.weighted_move(&board, 29).unwrap().to_uci(), "e2e4");
+        assert_eq!(book.weighted_move(&board, 30).unwrap().to_uci(), "d2d4");
+        assert_eq!(book.weighted_move(&board, 40).unwrap().to_uci(), "e2e4");
+        let mut after = board.clone();
+        after.make_move(&board.parse_uci_move("e2e4").unwrap());
+        assert!(book.probe(&after).is_empty());
+    }
+
+    #[test]
+    fn book_castling_test() {
+        let board =
+            ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
+        let castle = board.parse_uci_move("e1g1").unwrap();
+        // e1h1: from square 4, to square 7.
+        assert_eq!(encode_move(&board, &castle), 4 << 6 | 7);
+        let book = Book::from_entries(vec![entry(&board, "e1c1", 1)]);
+        assert_eq!(book.best_move(&board).unwrap().to_uci(), "e1c1");
+        assert_eq!(book.entries()[0].raw_move, 4 << 6);
+    }
+
+    #[test]
+    fn book_builder_test() {
+        let pgn = "1. e4 e5 2. Nf3 1-0\n\n1. e4 c5 0-1\n\n1. d4 d5 1/2-1/2\n\n1. e4 e5 *\n";
+        let mut builder = BookBuilder::new(2);
+        let (games, errors) = builder.add_pgn(pgn);
+        assert_eq!((games, errors.len()), (4, 0));
+        let board = ChessBoard::create();
+        let stats = builder.stats(&board);
+        assert_eq!(stats[0].0.to_uci(), "e2e4");
+        assert_eq!(
+            stats[0].1,
+            MoveStats {
+                white_wins: 1,
+                draws: 0,
+                black_wins: 1
+            }
+        );
+        // Nf3 is past the ply limit.
+        assert_eq!(builder.positions(), 3);
+
+        let book = builder.build(1);
+        let moves = book.probe(&board);
+        assert_eq!(moves.len(), 2);
+        assert!(moves.contains(&(board.parse_uci_move("e2e4").unwrap(), 2)));
+        assert!(moves.contains(&(board.parse_uci_move("d2d4").unwrap(), 1)));
+        assert_eq!(builder.build(2).probe(&board).len(), 1);
+        let mut after = board.clone();
+        after.make_move(&board.parse_uci_move("e2e4").unwrap());
+        let replies = book.probe(&after);
+        assert_eq!(replies[0].0.to_uci(), "c7c5");
+        assert_eq!(replies[0].1, 2);
+
+        let restored = BookBuilder::from_compact(&builder.to_compact()).unwrap();
+        assert_eq!(restored, builder);
+        assert!(BookBuilder::from_compact(b"nonsense").is_none());
+    }
+}
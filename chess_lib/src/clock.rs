@@ -0,0 +1,461 @@
+use crate::{ChessBoard, Color};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where a clock gets the current time from. Only differences between two readings are used,
+/// so the starting point does not matter.
+pub trait TimeSource {
+    fn now(&self) -> Duration;
+}
+
+/// Real time, measured from when the WallClock was created.
+#[derive(Clone, Copy, Debug)]
+pub struct WallClock {
+    start: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for WallClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Time that only moves when advanced by hand. Clones share the same time, so a test can keep
+/// one and give another to the clock.
+#[derive(Clone, Default, Debug)]
+pub struct ManualTime {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn advance(&self, time: Duration) {
+        *self.now.lock().unwrap() += time;
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// One period of a time control. `moves` is the number of moves that have to be made in it, or
+/// None for the rest of the game. Its time is added to the clock when the period begins.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stage {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    /// Fischer increment, added after every move.
+    pub increment: Duration,
+    /// Bronstein delay: after every move the time used for it is given back, up to this much.
+    pub delay: Duration,
+}
+
+/// The periods of a game's time control. There is always at least one.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "SerdeTimeControl"))]
+pub struct TimeControl {
+    stages: Vec<Stage>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerdeTimeControl {
+    stages: Vec<Stage>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeTimeControl> for TimeControl {
+    type Error = TimeControlError;
+    fn try_from(control: SerdeTimeControl) -> Result<Self, Self::Error> {
+        TimeControl::from_stages(control.stages).ok_or(TimeControlError(String::new()))
+    }
+}
+
+/// A TimeControl string that could not be parsed.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TimeControlError(pub String);
+
+impl fmt::Display for TimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid time control '{}'", self.0)
+    }
+}
+
+impl std::error::Error for TimeControlError {}
+
+impl TimeControl {
+    pub fn from_stages(stages: Vec<Stage>) -> Option<Self> {
+        /// A time control with the given periods, or None if there are none.
+        if stages.is_empty() {
+            return None;
+        }
+        Some(Self { stages })
+    }
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+    pub fn sudden_death(time: Duration) -> Self {
+        Self::fischer(time, Duration::ZERO)
+    }
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        Self {
+            stages: vec![Stage {
+                moves: None,
+                time,
+                increment,
+                delay: Duration::ZERO,
+            }],
+        }
+    }
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        Self {
+            stages: vec![Stage {
+                moves: None,
+                time,
+                increment: Duration::ZERO,
+                delay,
+            }],
+        }
+    }
+    pub fn parse(text: &str) -> Result<Self, TimeControlError> {
+        /// Parses the format of the PGN TimeControl tag: periods separated by ":", each written as
+        /// "seconds", "seconds+increment" or "moves/seconds+increment". A "d" in front of the
+        /// increment makes it a Bronstein delay. For example 40 moves in 90 minutes followed by
+        /// 30 minutes for the rest, with 30 seconds added per move, is "40/5400+30:1800+30".
+        let error = || TimeControlError(text.to_string());
+        let seconds = |value: &str| -> Result<Duration, TimeControlError> {
+            let seconds = value.parse::<f64>().map_err(|_| error())?;
+            if seconds < 0.0 || !seconds.is_finite() {
+                return Err(error());
+            }
+            Ok(Duration::from_secs_f64(seconds))
+        };
+        let mut stages = vec![];
+        for period in text.split(':') {
+            let (moves, rest) = match period.split_once('/') {
+                Some((moves, rest)) => (Some(moves.parse::<u32>().map_err(|_| error())?), rest),
+                None => (None, period),
+            };
+            let (time, bonus) = rest.split_once('+').unwrap_or((rest, "0"));
+            let (increment, delay) = match bonus.strip_prefix('d') {
+                Some(delay) => (Duration::ZERO, seconds(delay)?),
+                None => (seconds(bonus)?, Duration::ZERO),
+            };
+            stages.push(Stage {
+                moves: moves.filter(|moves| *moves > 0),
+                time: seconds(time)?,
+                increment,
+                delay,
+            });
+        }
+        Ok(Self { stages })
+    }
+    fn stage(&self, index: usize) -> Stage {
+        // The last period is repeated if a game outlasts all of them.
+        self.stages[index.min(self.stages.len() - 1)]
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = stage.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", stage.time.as_secs_f64())?;
+            if !stage.delay.is_zero() {
+                write!(f, "+d{}", stage.delay.as_secs_f64())?;
+            } else if !stage.increment.is_zero() {
+                write!(f, "+{}", stage.increment.as_secs_f64())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How a game ended on time.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeOutcome {
+    /// The given side won because the other one ran out of time.
+    Win(Color),
+    /// A side ran out of time but the opponent could not have checkmated anyway.
+    Draw,
+}
+
+fn index(color: &Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// A chess clock for both players.
+pub struct Clock<T: TimeSource = WallClock> {
+    control: TimeControl,
+    source: T,
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u32; 2],
+    /// The side whose clock is running and when it was started.
+    running: Option<(Color, Duration)>,
+    flagged: Option<Color>,
+}
+
+impl Clock<WallClock> {
+    pub fn with_wall_clock(control: TimeControl) -> Self {
+        Self::new(control, WallClock::new())
+    }
+}
+
+impl<T: TimeSource> Clock<T> {
+    pub fn new(control: TimeControl, source: T) -> Self {
+        /// Creates a stopped clock with both sides given the time of the first period.
+        let time = control.stage(0).time;
+        Self {
+            control,
+            source,
+            remaining: [time; 2],
+            stage: [0; 2],
+            moves_in_stage: [0; 2],
+            running: None,
+            flagged: None,
+        }
+    }
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+    pub fn start(&mut self, color: Color) {
+        /// Starts the clock of the given side, stopping the other one without counting it as a move.
+        self.pause();
+        if self.flagged.is_none() {
+            self.running = Some((color, self.source.now()));
+        }
+    }
+    pub fn pause(&mut self) {
+        /// Stops the running clock, keeping the time used so far.
+        if let Some((color, _)) = self.running {
+            let used = self.used_time(&color);
+            self.running = None;
+            self.charge(&color, used);
+        }
+    }
+    pub fn press(&mut self) -> Option<Color> {
+        /// Ends the move of the side whose clock is running and starts the opponent's clock.
+        /// The increment or delay of the current period is applied, and the next period's time is
+        /// added once its move count is reached. Returns the side that ran out of time, if any,
+        /// in which case both clocks stay stopped.
+        let (color, _) = self.running?;
+        let used = self.used_time(&color);
+        self.running = None;
+        let stage = self.control.stage(self.stage[index(&color)]);
+        self.charge(&color, used);
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+
+        let side = index(&color);
+        self.remaining[side] += stage.increment + used.min(stage.delay);
+        self.moves_in_stage[side] += 1;
+        if stage.moves == Some(self.moves_in_stage[side]) {
+            self.stage[side] += 1;
+            self.moves_in_stage[side] = 0;
+            self.remaining[side] += self.control.stage(self.stage[side]).time;
+        }
+        self.running = Some((color.opposite(), self.source.now()));
+        None
+    }
+    pub fn remaining(&self, color: &Color) -> Duration {
+        /// The time the side has left right now, counting a running move.
+        self.remaining[index(color)].saturating_sub(self.used_time(color))
+    }
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+    pub fn stage(&self, color: &Color) -> Stage {
+        /// The period the side is playing in, for its increment or delay.
+        self.control.stage(self.stage[index(color)])
+    }
+    pub fn moves_to_go(&self, color: &Color) -> Option<u32> {
+        /// The moves the side still has to make before its next period begins, if the current
+        /// one has a move count.
+        self.stage(color)
+            .moves
+            .map(|moves| moves - self.moves_in_stage[index(color)])
+    }
+    pub fn flagged(&self) -> Option<Color> {
+        /// The side that ran out of time, also noticing it while that side's clock is still running.
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+        match self.running {
+            Some((color, _)) if self.remaining(&color).is_zero() => Some(color),
+            _ => None,
+        }
+    }
+    pub fn outcome(&self, board: &ChessBoard) -> Option<TimeOutcome> {
+        /// The result of the game if a side ran out of time: a loss, unless the opponent does not
+        /// have enough material left on `board` to checkmate.
+        let flagged = self.flagged()?;
+        let opponent = flagged.opposite();
+        if board.has_insufficient_material(&opponent) {
+            Some(TimeOutcome::Draw)
+        } else {
+            Some(TimeOutcome::Win(opponent))
+        }
+    }
+    fn used_time(&self, color: &Color) -> Duration {
+        match self.running {
+            Some((running, started)) if running == *color => {
+                self.source.now().saturating_sub(started)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+    fn charge(&mut self, color: &Color, used: Duration) {
+        let side = index(color);
+        if used >= self.remaining[side] {
+            self.remaining[side] = Duration::ZERO;
+            self.flagged = Some(*color);
+        } else {
+            self.remaining[side] -= used;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn sudden_death_test() {
+        let time = ManualTime::new();
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(60)), time.clone());
+        clock.start(Color::White);
+        time.advance(secs(10));
+        assert_eq!(clock.remaining(&Color::White), secs(50));
+        assert_eq!(clock.press(), None);
+        assert_eq!(clock.running(), Some(Color::Black));
+        time.advance(secs(61));
+        assert_eq!(clock.flagged(), Some(Color::Black));
+        assert_eq!(clock.press(), Some(Color::Black));
+        assert_eq!(clock.remaining(&Color::Black), Duration::ZERO);
+        assert_eq!(clock.remaining(&Color::White), secs(50));
+    }
+
+    #[test]
+    fn fischer_increment_test() {
+        let time = ManualTime::new();
+        let mut clock = Clock::new(TimeControl::fischer(secs(60), secs(5)), time.clone());
+        clock.start(Color::White);
+        time.advance(secs(2));
+        clock.press();
+        assert_eq!(clock.remaining(&Color::White), secs(63));
+    }
+
+    #[test]
+    fn bronstein_delay_test() {
+        let time = ManualTime::new();
+        let mut clock = Clock::new(TimeControl::bronstein(secs(60), secs(5)), time.clone());
+        clock.start(Color::White);
+        time.advance(secs(3));
+        assert_eq!(clock.remaining(&Color::White), secs(57));
+        clock.press();
+        assert_eq!(clock.remaining(&Color::White), secs(60));
+        time.advance(secs(8));
+        assert_eq!(clock.remaining(&Color::Black), secs(52));
+        clock.press();
+        assert_eq!(clock.remaining(&Color::Black), secs(57));
+    }
+
+    #[test]
+    fn multi_stage_test() {
+        let control = TimeControl::parse("2/100+10:50").unwrap();
+        assert_eq!(control.stages().len(), 2);
+        assert_eq!(control.to_string(), "2/100+10:50");
+        let time = ManualTime::new();
+        let mut clock = Clock::new(control, time.clone());
+        assert_eq!(clock.moves_to_go(&Color::White), Some(2));
+        for _ in 0..2 {
+            clock.start(Color::White);
+            time.advance(secs(20));
+            clock.press();
+        }
+        assert_eq!(clock.moves_to_go(&Color::White), None);
+        assert_eq!(clock.stage(&Color::Black).increment, secs(10));
+        // 100 - 40 + 2 * 10 for the first period, then 50 more for the rest of the game.
+        assert_eq!(clock.remaining(&Color::White), secs(130));
+        clock.start(Color::White);
+        time.advance(secs(30));
+        clock.press();
+        assert_eq!(clock.remaining(&Color::White), secs(100));
+        assert_eq!(clock.remaining(&Color::Black), secs(100));
+    }
+
+    #[test]
+    fn parse_test() {
+        let control = TimeControl::parse("40/5400+30:1800+30").unwrap();
+        assert_eq!(control.stages()[0].moves, Some(40));
+        assert_eq!(control.stages()[0].time, secs(5400));
+        assert_eq!(control.stages()[1].increment, secs(30));
+        let control = TimeControl::parse("300+d5").unwrap();
+        assert_eq!(control.stages()[0].delay, secs(5));
+        assert!(TimeControl::parse("forty/90").is_err());
+        assert!(TimeControl::parse("-5").is_err());
+        assert_eq!(TimeControl::from_stages(vec![]), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        let control = TimeControl::parse("40/5400+30:1800+30").unwrap();
+        let json = serde_json::to_string(&control).unwrap();
+        assert!(json.starts_with("{\"stages\":[{"));
+        assert_eq!(serde_json::from_str::<TimeControl>(&json).unwrap(), control);
+        assert!(serde_json::from_str::<TimeControl>("{\"stages\":[]}").is_err());
+    }
+
+    #[test]
+    fn outcome_test() {
+        let time = ManualTime::new();
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(1)), time.clone());
+        clock.start(Color::White);
+        time.advance(secs(2));
+        let winning = ChessBoard::from_fen("4k3/8/8/8/8/8/8/q3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            clock.outcome(&winning),
+            Some(TimeOutcome::Win(Color::Black))
+        );
+        let drawn = ChessBoard::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        assert_eq!(clock.outcome(&drawn), Some(TimeOutcome::Draw));
+        assert!(clock.outcome(&ChessBoard::create()).is_some());
+    }
+}
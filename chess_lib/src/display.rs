@@ -0,0 +1,162 @@
+use crate::fen::piece_to_char;
+use crate::{ChessBoard, ChessPiece, Color};
+use std::fmt;
+
+/// How pieces are drawn when a board is displayed as text.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PieceStyle {
+    /// FEN letters, uppercase for White: "K", "q", "n".
+    Ascii,
+    /// Chess figurines: "♔", "♛", "♞".
+    Unicode,
+}
+
+/// Options for ChessBoard::display.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayOptions {
+    pub style: PieceStyle,
+    pub coordinates: bool,
+    /// The side shown at the bottom of the board.
+    pub perspective: Color,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            style: PieceStyle::Ascii,
+            coordinates: true,
+            perspective: Color::White,
+        }
+    }
+}
+
+/// A board together with the options to display it with, returned by ChessBoard::display.
+pub struct BoardDisplay<'a> {
+    board: &'a ChessBoard,
+    options: DisplayOptions,
+}
+
+pub fn figurine(piece: &ChessPiece) -> char {
+    /// The Unicode chess symbol for the piece.
+    match piece {
+        ChessPiece::King(Color::White) => '♔',
+        ChessPiece::Queen(Color::White) => '♕',
+        ChessPiece::Rook(Color::White) => '♖',
+        ChessPiece::Bishop(Color::White) => '♗',
+        ChessPiece::Knight(Color::White) => '♘',
+        ChessPiece::Pawn(Color::White) => '♙',
+        ChessPiece::King(Color::Black) => '♚',
+        ChessPiece::Queen(Color::Black) => '♛',
+        ChessPiece::Rook(Color::Black) => '♜',
+        ChessPiece::Bishop(Color::Black) => '♝',
+        ChessPiece::Knight(Color::Black) => '♞',
+        ChessPiece::Pawn(Color::Black) => '♟',
+    }
+}
+
+impl ChessBoard {
+    pub fn display(&self, options: DisplayOptions) -> BoardDisplay<'_> {
+        /// Returns something that can be printed with "{}" using the given style, coordinates and perspective.
+        /// Printing the board itself uses the default options, or Unicode pieces with "{:#}".
+        BoardDisplay {
+            board: self,
+            options,
+        }
+    }
+}
+
+impl fmt::Display for BoardDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order: Vec<usize> = match self.options.perspective {
+            Color::White => (0..8).collect(),
+            Color::Black => (0..8).rev().collect(),
+        };
+        for &y in &order {
+            if self.options.coordinates {
+                write!(f, "{} ", 8 - y)?;
+            }
+            for (i, &x) in order.iter().enumerate() {
+                let symbol = match (&self.board.board[y][x], self.options.style) {
+                    (Some(piece), PieceStyle::Ascii) => piece_to_char(piece),
+                    (Some(piece), PieceStyle::Unicode) => figurine(piece),
+                    (None, PieceStyle::Ascii) => '.',
+                    (None, PieceStyle::Unicode) => '·',
+                };
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", symbol)?;
+            }
+            writeln!(f)?;
+        }
+        if self.options.coordinates {
+            write!(f, " ")?;
+            for &x in &order {
+                write!(f, " {}", (b'a' + x as u8) as char)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChessBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let style = if f.alternate() {
+            PieceStyle::Unicode
+        } else {
+            PieceStyle::Ascii
+        };
+        let options = DisplayOptions {
+            style,
+            ..Default::default()
+        };
+        write!(f, "{}", self.display(options))
+    }
+}
+
+/// Writes the FEN letter of the piece, or its figurine with "{:#}".
+impl fmt::Display for ChessPiece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", figurine(self))
+        } else {
+            write!(f, "{}", piece_to_char(self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_display_test() {
+        let board = ChessBoard::create();
+        let text = board.to_string();
+        assert!(text.starts_with("8 r n b q k b n r\n7 p p p p p p p p\n6 . . . . . . . .\n"));
+        assert!(text.ends_with("1 R N B Q K B N R\n  a b c d e f g h\n"));
+
+        let options = DisplayOptions {
+            coordinates: false,
+            perspective: Color::Black,
+            ..Default::default()
+        };
+        let text = board.display(options).to_string();
+        assert_eq!(text.lines().next(), Some("R N B K Q B N R"));
+        assert_eq!(text.lines().count(), 8);
+    }
+
+    #[test]
+    fn unicode_display_test() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let text = format!("{:#}", board);
+        assert!(text.starts_with("8 · · · · ♚ · · ·\n"));
+        assert!(text.contains("1 · · · · ♔ · · ♖\n"));
+        assert_eq!(format!("{}", ChessPiece::Knight(Color::Black)), "n");
+        assert_eq!(format!("{:#}", ChessPiece::Knight(Color::White)), "♘");
+        assert_eq!(ChessPiece::Knight(Color::White).name(), "Knight");
+    }
+}
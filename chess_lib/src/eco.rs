@@ -0,0 +1,496 @@
+use crate::pgn::PgnGame;
+use crate::{ChessBoard, Move};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A named opening line of the Encyclopaedia of Chess Openings.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+    /// The moves from the starting position, in SAN separated by spaces.
+    pub moves: &'static str,
+}
+
+const fn opening(eco: &'static str, name: &'static str, moves: &'static str) -> Opening {
+    Opening { eco, name, moves }
+}
+
+/// The embedded table: the main lines of the common openings. Family names come first and
+/// variations are separated by a colon, so "Sicilian Defense: Najdorf Variation" is a line of
+/// the "Sicilian Defense".
+pub const OPENINGS: &[Opening] = &[
+    opening("A00", "Polish Opening", "b4"),
+    opening("A00", "Grob Opening", "g4"),
+    opening("A01", "Nimzo-Larsen Attack", "b3"),
+    opening("A02", "Bird Opening", "f4"),
+    opening("A04", "Zukertort Opening", "Nf3"),
+    opening(
+        "A06",
+        "Zukertort Opening: Queen's Gambit Invitation",
+        "Nf3 d5",
+    ),
+    opening("A07", "King's Indian Attack", "Nf3 d5 g3"),
+    opening("A09", "Réti Opening", "Nf3 d5 c4"),
+    opening("A10", "English Opening", "c4"),
+    opening("A13", "English Opening: Agincourt Defense", "c4 e6"),
+    opening("A15", "English Opening: Anglo-Indian Defense", "c4 Nf6"),
+    opening("A20", "English Opening: King's English Variation", "c4 e5"),
+    opening("A30", "English Opening: Symmetrical Variation", "c4 c5"),
+    opening("A40", "Queen's Pawn Game", "d4"),
+    opening("A43", "Benoni Defense: Old Benoni", "d4 c5"),
+    opening("A45", "Indian Defense", "d4 Nf6"),
+    opening("A45", "Trompowsky Attack", "d4 Nf6 Bg5"),
+    opening("A46", "Indian Defense: Knights Variation", "d4 Nf6 Nf3"),
+    opening("A51", "Indian Defense: Budapest Defense", "d4 Nf6 c4 e5"),
+    opening("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    opening("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    opening(
+        "A60",
+        "Benoni Defense: Modern Variation",
+        "d4 Nf6 c4 c5 d5 e6",
+    ),
+    opening("A80", "Dutch Defense", "d4 f5"),
+    opening("A82", "Dutch Defense: Staunton Gambit", "d4 f5 e4"),
+    opening("B00", "King's Pawn Game", "e4"),
+    opening("B00", "Nimzowitsch Defense", "e4 Nc6"),
+    opening("B01", "Scandinavian Defense", "e4 d5"),
+    opening(
+        "B01",
+        "Scandinavian Defense: Modern Variation",
+        "e4 d5 exd5 Nf6",
+    ),
+    opening(
+        "B01",
+        "Scandinavian Defense: Main Line",
+        "e4 d5 exd5 Qxd5 Nc3 Qa5",
+    ),
+    opening("B02", "Alekhine Defense", "e4 Nf6"),
+    opening(
+        "B03",
+        "Alekhine Defense: Four Pawns Attack",
+        "e4 Nf6 e5 Nd5 d4 d6 c4 Nb6 f4",
+    ),
+    opening(
+        "B04",
+        "Alekhine Defense: Modern Variation",
+        "e4 Nf6 e5 Nd5 d4 d6 Nf3",
+    ),
+    opening("B06", "Modern Defense", "e4 g6"),
+    opening("B07", "Pirc Defense", "e4 d6 d4 Nf6 Nc3 g6"),
+    opening(
+        "B09",
+        "Pirc Defense: Austrian Attack",
+        "e4 d6 d4 Nf6 Nc3 g6 f4",
+    ),
+    opening("B10", "Caro-Kann Defense", "e4 c6"),
+    opening(
+        "B12",
+        "Caro-Kann Defense: Advance Variation",
+        "e4 c6 d4 d5 e5",
+    ),
+    opening(
+        "B13",
+        "Caro-Kann Defense: Exchange Variation",
+        "e4 c6 d4 d5 exd5 cxd5",
+    ),
+    opening(
+        "B13",
+        "Caro-Kann Defense: Panov Attack",
+        "e4 c6 d4 d5 exd5 cxd5 c4",
+    ),
+    opening("B15", "Caro-Kann Defense", "e4 c6 d4 d5 Nc3"),
+    opening(
+        "B17",
+        "Caro-Kann Defense: Karpov Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Nd7",
+    ),
+    opening(
+        "B18",
+        "Caro-Kann Defense: Classical Variation",
+        "e4 c6 d4 d5 Nc3 dxe4 Nxe4 Bf5",
+    ),
+    opening("B20", "Sicilian Defense", "e4 c5"),
+    opening(
+        "B21",
+        "Sicilian Defense: Smith-Morra Gambit",
+        "e4 c5 d4 cxd4 c3",
+    ),
+    opening("B22", "Sicilian Defense: Alapin Variation", "e4 c5 c3"),
+    opening("B23", "Sicilian Defense: Closed", "e4 c5 Nc3"),
+    opening("B27", "Sicilian Defense", "e4 c5 Nf3"),
+    opening("B30", "Sicilian Defense: Old Sicilian", "e4 c5 Nf3 Nc6"),
+    opening(
+        "B30",
+        "Sicilian Defense: Rossolimo Variation",
+        "e4 c5 Nf3 Nc6 Bb5",
+    ),
+    opening(
+        "B32",
+        "Sicilian Defense: Open",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4",
+    ),
+    opening(
+        "B33",
+        "Sicilian Defense: Sveshnikov Variation",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5",
+    ),
+    opening(
+        "B34",
+        "Sicilian Defense: Accelerated Dragon",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 g6",
+    ),
+    opening("B40", "Sicilian Defense: French Variation", "e4 c5 Nf3 e6"),
+    opening(
+        "B41",
+        "Sicilian Defense: Kan Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 a6",
+    ),
+    opening(
+        "B44",
+        "Sicilian Defense: Taimanov Variation",
+        "e4 c5 Nf3 e6 d4 cxd4 Nxd4 Nc6",
+    ),
+    opening("B50", "Sicilian Defense: Modern Variations", "e4 c5 Nf3 d6"),
+    opening(
+        "B51",
+        "Sicilian Defense: Moscow Variation",
+        "e4 c5 Nf3 d6 Bb5+",
+    ),
+    opening("B54", "Sicilian Defense: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    opening(
+        "B56",
+        "Sicilian Defense: Classical Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 Nc6",
+    ),
+    opening(
+        "B70",
+        "Sicilian Defense: Dragon Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    opening(
+        "B80",
+        "Sicilian Defense: Scheveningen Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 e6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    opening(
+        "B90",
+        "Sicilian Defense: Najdorf Variation, English Attack",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3",
+    ),
+    opening(
+        "B92",
+        "Sicilian Defense: Najdorf Variation, Opocensky Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be2",
+    ),
+    opening(
+        "B94",
+        "Sicilian Defense: Najdorf Variation",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Bg5",
+    ),
+    opening("C00", "French Defense", "e4 e6"),
+    opening(
+        "C01",
+        "French Defense: Exchange Variation",
+        "e4 e6 d4 d5 exd5",
+    ),
+    opening("C02", "French Defense: Advance Variation", "e4 e6 d4 d5 e5"),
+    opening(
+        "C03",
+        "French Defense: Tarrasch Variation",
+        "e4 e6 d4 d5 Nd2",
+    ),
+    opening(
+        "C10",
+        "French Defense: Rubinstein Variation",
+        "e4 e6 d4 d5 Nc3 dxe4",
+    ),
+    opening(
+        "C11",
+        "French Defense: Classical Variation",
+        "e4 e6 d4 d5 Nc3 Nf6",
+    ),
+    opening(
+        "C15",
+        "French Defense: Winawer Variation",
+        "e4 e6 d4 d5 Nc3 Bb4",
+    ),
+    opening("C20", "King's Pawn Game", "e4 e5"),
+    opening("C21", "Danish Gambit", "e4 e5 d4 exd4 c3"),
+    opening("C22", "Center Game", "e4 e5 d4 exd4 Qxd4"),
+    opening("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    opening("C25", "Vienna Game", "e4 e5 Nc3"),
+    opening("C30", "King's Gambit", "e4 e5 f4"),
+    opening("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    opening("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    opening("C40", "Latvian Gambit", "e4 e5 Nf3 f5"),
+    opening("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    opening("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    opening(
+        "C44",
+        "King's Knight Opening: Normal Variation",
+        "e4 e5 Nf3 Nc6",
+    ),
+    opening("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    opening("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    opening(
+        "C45",
+        "Scotch Game: Classical Variation",
+        "e4 e5 Nf3 Nc6 d4 exd4 Nxd4 Bc5",
+    ),
+    opening("C46", "Three Knights Opening", "e4 e5 Nf3 Nc6 Nc3"),
+    opening("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    opening("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    opening("C50", "Italian Game: Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    opening(
+        "C51",
+        "Italian Game: Evans Gambit",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 b4",
+    ),
+    opening(
+        "C53",
+        "Italian Game: Classical Variation",
+        "e4 e5 Nf3 Nc6 Bc4 Bc5 c3",
+    ),
+    opening(
+        "C55",
+        "Italian Game: Two Knights Defense",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6",
+    ),
+    opening(
+        "C57",
+        "Italian Game: Two Knights Defense, Fried Liver Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5 d5 exd5 Nxd5 Nxf7",
+    ),
+    opening("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    opening("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    opening(
+        "C68",
+        "Ruy Lopez: Exchange Variation",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6",
+    ),
+    opening(
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4",
+    ),
+    opening(
+        "C78",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O",
+    ),
+    opening(
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    opening(
+        "C88",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3",
+    ),
+    opening(
+        "C89",
+        "Ruy Lopez: Marshall Attack",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7 Re1 b5 Bb3 O-O c3 d5",
+    ),
+    opening("D00", "Queen's Pawn Game", "d4 d5"),
+    opening(
+        "D00",
+        "Queen's Pawn Game: Accelerated London System",
+        "d4 d5 Bf4",
+    ),
+    opening("D00", "Blackmar-Diemer Gambit", "d4 d5 e4"),
+    opening(
+        "D02",
+        "Queen's Pawn Game: London System",
+        "d4 d5 Nf3 Nf6 Bf4",
+    ),
+    opening("D06", "Queen's Gambit", "d4 d5 c4"),
+    opening(
+        "D07",
+        "Queen's Gambit Declined: Chigorin Defense",
+        "d4 d5 c4 Nc6",
+    ),
+    opening(
+        "D08",
+        "Queen's Gambit Declined: Albin Countergambit",
+        "d4 d5 c4 e5",
+    ),
+    opening("D10", "Slav Defense", "d4 d5 c4 c6"),
+    opening("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    opening("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    opening("D31", "Queen's Gambit Declined", "d4 d5 c4 e6 Nc3"),
+    opening(
+        "D35",
+        "Queen's Gambit Declined: Exchange Variation",
+        "d4 d5 c4 e6 Nc3 Nf6 cxd5",
+    ),
+    opening("D43", "Semi-Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6"),
+    opening("D80", "Grünfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    opening(
+        "D85",
+        "Grünfeld Defense: Exchange Variation",
+        "d4 Nf6 c4 g6 Nc3 d5 cxd5 Nxd5",
+    ),
+    opening("E00", "Indian Defense", "d4 Nf6 c4 e6"),
+    opening("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    opening("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4+"),
+    opening("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    opening("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    opening(
+        "E32",
+        "Nimzo-Indian Defense: Classical Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 Qc2",
+    ),
+    opening(
+        "E40",
+        "Nimzo-Indian Defense: Rubinstein Variation",
+        "d4 Nf6 c4 e6 Nc3 Bb4 e3",
+    ),
+    opening("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    opening("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+    opening(
+        "E70",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6",
+    ),
+    opening(
+        "E80",
+        "King's Indian Defense: Sämisch Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 f3",
+    ),
+    opening(
+        "E90",
+        "King's Indian Defense: Normal Variation",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3",
+    ),
+    opening(
+        "E97",
+        "King's Indian Defense: Orthodox Variation, Aronin-Taimanov Defense",
+        "d4 Nf6 c4 g6 Nc3 Bg7 e4 d6 Nf3 O-O Be2 e5 O-O Nc6",
+    ),
+];
+
+fn index() -> &'static HashMap<u64, &'static Opening> {
+    /// The openings by the Polyglot key of the position their moves lead to. When two lines
+    /// reach the same position the first one in the table is kept.
+    static INDEX: OnceLock<HashMap<u64, &'static Opening>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index = HashMap::new();
+        for opening in OPENINGS {
+            let mut board = ChessBoard::create();
+            for san in opening.moves.split_whitespace() {
+                let mv = board
+                    .parse_san(san)
+                    .unwrap_or_else(|| panic!("bad move {} in {}", san, opening.name));
+                board.make_move(&mv);
+            }
+            index.entry(board.polyglot_hash()).or_insert(opening);
+        }
+        index
+    })
+}
+
+pub fn classify_position(board: &ChessBoard) -> Option<&'static Opening> {
+    /// The opening whose line leads to exactly this position.
+    index().get(&board.polyglot_hash()).copied()
+}
+
+pub fn classify(start: &ChessBoard, moves: &[Move]) -> Option<&'static Opening> {
+    /// The deepest opening reached while playing the moves from start, the one with the most
+    /// moves. Every position along the way is looked up, so move orders that transpose into a
+    /// known line are recognised too. Of two lines of the same length the later one wins.
+    let depth = |opening: &Opening| opening.moves.split_whitespace().count();
+    let mut board = start.clone();
+    let mut found = classify_position(&board);
+    for mv in moves {
+        board.make_move(mv);
+        if let Some(opening) = classify_position(&board) {
+            if found.is_none_or(|best| depth(opening) >= depth(best)) {
+                found = Some(opening);
+            }
+        }
+    }
+    found
+}
+
+pub fn classify_game(game: &PgnGame) -> Option<&'static Opening> {
+    /// Classifies a game read from PGN, up to the first move that can not be replayed.
+    let start = game.starting_position().ok()?;
+    let mut board = start.clone();
+    let mut moves = Vec::new();
+    for san in &game.moves {
+        let mv = match board.parse_san(san) {
+            Some(mv) => mv,
+            None => break,
+        };
+        board.make_move(&mv);
+        moves.push(mv);
+    }
+    classify(&start, &moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(sans: &str) -> Vec<Move> {
+        let mut board = ChessBoard::create();
+        let mut moves = Vec::new();
+        for san in sans.split_whitespace() {
+            let mv = board.parse_san(san).unwrap();
+            board.make_move(&mv);
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn table_test() {
+        // Building the index replays every line and panics on a bad one.
+        assert!(index().len() > 100);
+        for opening in OPENINGS {
+            assert_eq!(opening.eco.len(), 3, "{}", opening.name);
+        }
+    }
+
+    #[test]
+    fn classify_test() {
+        let start = ChessBoard::create();
+        let najdorf = play("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 f4 e5 Nf3");
+        let opening = classify(&start, &najdorf).unwrap();
+        assert_eq!(opening.eco, "B90");
+        assert_eq!(opening.name, "Sicilian Defense: Najdorf Variation");
+        let english_attack = play("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 Be3 e5");
+        assert_eq!(
+            classify(&start, &english_attack).unwrap().name,
+            "Sicilian Defense: Najdorf Variation, English Attack"
+        );
+        assert_eq!(classify(&start, &play("a3")), None);
+        assert_eq!(
+            classify(&start, &play("e4 a6")).unwrap().name,
+            "King's Pawn Game"
+        );
+        // Retreating both knights reaches the King's Pawn Game position again.
+        let retreat = play("e4 e5 Nf3 Nc6 Ng1 Nb8");
+        assert_eq!(
+            classify(&start, &retreat).unwrap().name,
+            "King's Knight Opening: Normal Variation"
+        );
+    }
+
+    #[test]
+    fn transposition_test() {
+        let start = ChessBoard::create();
+        let nimzo = play("c4 e6 d4 Nf6 Nc3 Bb4");
+        assert_eq!(classify(&start, &nimzo).unwrap().eco, "E20");
+        let game = &crate::pgn::read_games("1. c4 Nf6 2. Nc3 e6 3. d4 Bb4 *")[0];
+        assert_eq!(classify_game(game).unwrap().name, "Nimzo-Indian Defense");
+    }
+}
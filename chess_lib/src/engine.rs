@@ -0,0 +1,245 @@
+use crate::book::Book;
+use crate::search::{SearchInfo, SearchLimits, Searcher};
+use crate::zobrist::splitmix64;
+use crate::{ChessBoard, Move, Variant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// The protocol independent part of an engine: the game being played and a searcher that runs on
+/// a background thread, so that the front-end can keep reading commands while it thinks.
+pub struct Engine {
+    board: ChessBoard,
+    previous: Vec<ChessBoard>,
+    searcher: Option<Searcher>,
+    search_thread: Option<JoinHandle<Searcher>>,
+    stop: Arc<AtomicBool>,
+    chess960: bool,
+    book: Option<Book>,
+    use_book: bool,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let searcher = Searcher::new(DEFAULT_HASH_MB);
+        Self {
+            board: ChessBoard::create(),
+            previous: vec![],
+            stop: searcher.stop_flag(),
+            searcher: Some(searcher),
+            search_thread: None,
+            chess960: false,
+            book: None,
+            use_book: false,
+        }
+    }
+    pub fn board(&self) -> &ChessBoard {
+        &self.board
+    }
+    pub fn history(&self) -> Vec<u64> {
+        /// The hashes of the positions before the current one, oldest first.
+        self.previous.iter().map(ChessBoard::zobrist_hash).collect()
+    }
+    pub fn new_game(&mut self) {
+        /// Resets the board to the starting position and forgets what earlier searches learned.
+        self.searcher().clear();
+        self.set_position(ChessBoard::create());
+    }
+    pub fn set_position(&mut self, mut board: ChessBoard) {
+        // Replaces the game with a new position. In Chess960 mode castling is always written as
+        // the king taking its own rook, even in positions that look like standard chess.
+        board.chess960 |= self.chess960;
+        self.board = board;
+        self.previous.clear();
+    }
+    pub fn play(&mut self, mv: &Move) {
+        self.previous.push(self.board.clone());
+        self.board.make_move(mv);
+    }
+    pub fn play_uci(&mut self, uci: &str) -> Option<Move> {
+        /// Plays a move given in UCI notation if it is legal and returns it.
+        let mv = self.board.parse_uci_move(uci)?;
+        self.play(&mv);
+        Some(mv)
+    }
+    pub fn undo(&mut self) -> bool {
+        /// Takes back the last move. Returns false if there was nothing to take back.
+        match self.previous.pop() {
+            Some(board) => {
+                self.board = board;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+        self.board.chess960 = chess960;
+    }
+    pub fn set_book(&mut self, book: Option<Book>) {
+        /// Sets the opening book searches take their first moves from. Setting a book turns its
+        /// use on; set_use_book(false) keeps it loaded without playing from it.
+        let use_book = book.is_some();
+        self.book = book;
+        self.use_book = use_book;
+    }
+    pub fn set_use_book(&mut self, use_book: bool) {
+        self.use_book = use_book;
+    }
+    pub fn book_move(&self) -> Option<Move> {
+        /// A move for the current position picked from the book by weight, if the book is in use
+        /// and knows the position. Books only cover standard chess.
+        let book = self.book.as_ref().filter(|_| self.use_book)?;
+        if self.board.variant != Variant::Standard {
+            return None;
+        }
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos() as u64)
+            .unwrap_or_default();
+        book.weighted_move(&self.board, splitmix64(nanos ^ self.board.zobrist_hash()))
+    }
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.searcher().set_hash_size(megabytes);
+    }
+    pub fn set_threads(&mut self, threads: usize) {
+        /// Sets how many threads each search uses. One thread, the default, gives the same result
+        /// for the same search every time.
+        self.searcher().set_threads(threads);
+    }
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        /// Sets how many lines searches report, each led by a different move.
+        self.searcher().set_multi_pv(lines);
+    }
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+    pub fn is_searching(&self) -> bool {
+        self.search_thread.is_some()
+    }
+    pub fn start_search(
+        &mut self,
+        limits: SearchLimits,
+        mut on_info: impl FnMut(&SearchInfo) + Send + 'static,
+        on_done: impl FnOnce(Option<Move>) + Send + 'static,
+    ) {
+        /// Starts searching the current position on a background thread. `on_info` is called after
+        /// every completed depth and `on_done` with the best move once the search ends. When the
+        /// book has a move for the position it is played right away without searching, except in
+        /// infinite analysis. A search that is still running is stopped first.
+        self.searcher();
+        let book_move = self.book_move().filter(|_| !limits.infinite);
+        if book_move.is_some() {
+            on_done(book_move);
+            return;
+        }
+        let mut searcher = self.searcher.take().unwrap();
+        self.stop.store(false, Ordering::Relaxed);
+        let board = self.board.clone();
+        let history = self.history();
+        self.search_thread = Some(thread::spawn(move || {
+            let best = searcher.search(&board, &history, &limits, |info| on_info(info));
+            on_done(best);
+            searcher
+        }));
+    }
+    pub fn stop(&mut self) {
+        /// Asks a running search to finish and waits until it has reported its move.
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait();
+    }
+    pub fn wait(&mut self) {
+        /// Waits until a running search has finished on its own.
+        if self.search_thread.is_some() {
+            let searcher = self.join();
+            self.searcher = Some(searcher);
+        }
+    }
+    fn searcher(&mut self) -> &mut Searcher {
+        // Changing the searcher, or starting another search, ends the running search first:
+        // an infinite search would otherwise never return.
+        if self.search_thread.is_some() {
+            self.stop();
+        }
+        self.searcher.as_mut().unwrap()
+    }
+    fn join(&mut self) -> Searcher {
+        self.search_thread
+            .take()
+            .expect("no search is running")
+            .join()
+            .expect("search thread panicked")
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if self.search_thread.is_some() {
+            self.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn play_and_undo_test() {
+        let mut engine = Engine::new();
+        assert!(engine.play_uci("e2e4").is_some());
+        assert!(engine.play_uci("e2e4").is_none());
+        assert_eq!(engine.history(), vec![ChessBoard::create().zobrist_hash()]);
+        assert!(engine.undo());
+        assert!(!engine.undo());
+        assert_eq!(engine.board().to_fen(), ChessBoard::create().to_fen());
+    }
+
+    #[test]
+    fn background_search_test() {
+        let mut engine = Engine::new();
+        engine.set_position(ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap());
+        let (sender, receiver) = mpsc::channel();
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        engine.start_search(limits, |_| {}, move |best| sender.send(best).unwrap());
+        engine.wait();
+        assert_eq!(receiver.recv().unwrap().unwrap().to_uci(), "a1a8");
+        assert!(!engine.is_searching());
+    }
+
+    #[test]
+    fn book_move_test() {
+        let mut engine = Engine::new();
+        let board = ChessBoard::create();
+        let mv = board.parse_uci_move("c2c4").unwrap();
+        let entry = crate::book::BookEntry {
+            key: board.polyglot_hash(),
+            raw_move: crate::book::encode_move(&board, &mv),
+            weight: 1,
+            learn: 0,
+        };
+        engine.set_book(Some(Book::from_entries(vec![entry])));
+        let (sender, receiver) = mpsc::channel();
+        engine.start_search(
+            SearchLimits::default(),
+            |_| {},
+            move |best| sender.send(best).unwrap(),
+        );
+        assert_eq!(receiver.recv().unwrap(), Some(mv));
+        engine.set_use_book(false);
+        assert_eq!(engine.book_move(), None);
+    }
+}
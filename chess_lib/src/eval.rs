@@ -0,0 +1,186 @@
+use crate::{ChessBoard, ChessPiece, Color, Variant};
+
+// Piece-square tables from White's point of view, laid out like the board: the first row is the 8th rank.
+const PAWN_TABLE: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+const KNIGHT_TABLE: [[i32; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, 0, 0, 0, 0, -20, -40],
+    [-30, 0, 10, 15, 15, 10, 0, -30],
+    [-30, 5, 15, 20, 20, 15, 5, -30],
+    [-30, 0, 15, 20, 20, 15, 0, -30],
+    [-30, 5, 10, 15, 15, 10, 5, -30],
+    [-40, -20, 0, 5, 5, 0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+const BISHOP_TABLE: [[i32; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 5, 5, 10, 10, 5, 5, -10],
+    [-10, 0, 10, 10, 10, 10, 0, -10],
+    [-10, 10, 10, 10, 10, 10, 10, -10],
+    [-10, 5, 0, 0, 0, 0, 5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+const ROOK_TABLE: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, 10, 10, 10, 10, 5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [0, 0, 0, 5, 5, 0, 0, 0],
+];
+const QUEEN_TABLE: [[i32; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-5, 0, 5, 5, 5, 5, 0, -5],
+    [0, 0, 5, 5, 5, 5, 0, -5],
+    [-10, 5, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 5, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+];
+const KING_MIDDLEGAME_TABLE: [[i32; 8]; 8] = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [20, 30, 10, 0, 0, 10, 30, 20],
+];
+const KING_ENDGAME_TABLE: [[i32; 8]; 8] = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10, 0, 0, -10, -20, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -30, 0, 0, 0, 0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+pub fn piece_value(piece: &ChessPiece) -> i32 {
+    /// The material value of a piece in centipawns.
+    match piece {
+        ChessPiece::Pawn(_) => 100,
+        ChessPiece::Knight(_) => 320,
+        ChessPiece::Bishop(_) => 330,
+        ChessPiece::Rook(_) => 500,
+        ChessPiece::Queen(_) => 900,
+        ChessPiece::King(_) => 20000,
+    }
+}
+
+pub fn evaluate(board: &ChessBoard) -> i32 {
+    /// Scores the position in centipawns from the point of view of the side whose turn it is,
+    /// using material and piece-square tables.
+    let mut non_pawn_material = 0;
+    for piece in board.board.iter().flatten().flatten() {
+        if !matches!(piece, ChessPiece::Pawn(_) | ChessPiece::King(_)) {
+            non_pawn_material += piece_value(piece);
+        }
+    }
+    let endgame = non_pawn_material <= 1300;
+
+    let mut score = 0;
+    for (y, row) in board.board.iter().enumerate() {
+        for (x, square) in row.iter().enumerate() {
+            let piece = match square {
+                Some(piece) => piece,
+                None => continue,
+            };
+            let table_row = match piece.color() {
+                Color::White => y,
+                Color::Black => 7 - y,
+            };
+            let table = match piece {
+                ChessPiece::Pawn(_) => &PAWN_TABLE,
+                ChessPiece::Knight(_) => &KNIGHT_TABLE,
+                ChessPiece::Bishop(_) => &BISHOP_TABLE,
+                ChessPiece::Rook(_) => &ROOK_TABLE,
+                ChessPiece::Queen(_) => &QUEEN_TABLE,
+                ChessPiece::King(_) if endgame => &KING_ENDGAME_TABLE,
+                ChessPiece::King(_) => &KING_MIDDLEGAME_TABLE,
+            };
+            let value = piece_value(piece) + table[table_row][x];
+            match piece.color() {
+                Color::White => score += value,
+                Color::Black => score -= value,
+            }
+        }
+    }
+    score += variant_bonus(board, &Color::White) - variant_bonus(board, &Color::Black);
+    match board.faction_decider() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+fn variant_bonus(board: &ChessBoard, color: &Color) -> i32 {
+    /// Rough terms for the goals of the variants: checks given in three-check, a king close to the
+    /// center in King of the Hill, a king close to the 8th rank in Racing Kings, the pieces in
+    /// hand in crazyhouse and having few pieces left in antichess.
+    let king = match color {
+        Color::White => board.white_king_pos,
+        Color::Black => board.black_king_pos,
+    };
+    match board.variant {
+        Variant::Standard => 0,
+        Variant::ThreeCheck => {
+            let checks = match color {
+                Color::White => board.white_checks,
+                Color::Black => board.black_checks,
+            };
+            checks as i32 * 250
+        }
+        Variant::KingOfTheHill => {
+            let distance = king.0.abs_diff(3).min(king.0.abs_diff(4))
+                + king.1.abs_diff(3).min(king.1.abs_diff(4));
+            (6 - distance as i32) * 40
+        }
+        Variant::RacingKings => (7 - king.1 as i32) * 100,
+        // Pieces in hand are worth about as much as pieces on the board.
+        Variant::Crazyhouse => board.pocket(color).iter().map(piece_value).sum(),
+        Variant::Atomic => 0,
+        // Cancel out the material counted above and reward having few pieces instead.
+        Variant::Antichess => board
+            .board
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|piece| piece.color() == *color)
+            .map(|piece| -piece_value(piece) - 100)
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_evaluation_test() {
+        assert_eq!(evaluate(&ChessBoard::create()), 0);
+        let board =
+            ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1")
+                .unwrap();
+        assert!(evaluate(&board) < -800);
+        let board =
+            ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR b KQkq - 0 1")
+                .unwrap();
+        assert!(evaluate(&board) > 800);
+    }
+}
@@ -0,0 +1,823 @@
+use crate::fen::FenError;
+use crate::{CastlingRights, Color};
+
+/// How a piece moves, as a list of leaps and rides. Every step is taken in all directions it
+/// has by symmetry, so the knight is the single leap (1, 2) and the rook the single ride (1, 0).
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Movement {
+    /// Steps taken exactly once, jumping over anything in between.
+    pub leaps: Vec<(i32, i32)>,
+    /// Steps repeated until the edge of the board or the first occupied square.
+    pub rides: Vec<(i32, i32)>,
+}
+
+fn symmetries(dx: i32, dy: i32) -> Vec<(i32, i32)> {
+    let mut steps = Vec::new();
+    for (a, b) in [(dx, dy), (dy, dx)] {
+        for (sx, sy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let step = (a * sx, b * sy);
+            if !steps.contains(&step) {
+                steps.push(step);
+            }
+        }
+    }
+    steps
+}
+
+fn betza_atom(letter: char) -> Option<(i32, i32)> {
+    let step = match letter {
+        'W' => (1, 0),
+        'F' => (1, 1),
+        'D' => (2, 0),
+        'N' => (1, 2),
+        'A' => (2, 2),
+        'H' => (3, 0),
+        'C' => (1, 3),
+        'Z' => (2, 3),
+        'G' => (3, 3),
+        _ => return None,
+    };
+    Some(step)
+}
+
+impl Movement {
+    pub fn leaper(dx: i32, dy: i32) -> Movement {
+        /// A piece jumping (dx, dy) in every direction, like the knight for (1, 2).
+        Movement {
+            leaps: symmetries(dx, dy),
+            rides: Vec::new(),
+        }
+    }
+    pub fn rider(dx: i32, dy: i32) -> Movement {
+        /// A piece sliding along (dx, dy) in every direction, like the bishop for (1, 1).
+        Movement {
+            leaps: Vec::new(),
+            rides: symmetries(dx, dy),
+        }
+    }
+    pub fn combine(mut self, other: Movement) -> Movement {
+        /// The movement of a compound piece that moves like either of the two.
+        let Movement { leaps, rides } = other;
+        for step in leaps {
+            if !self.leaps.contains(&step) {
+                self.leaps.push(step);
+            }
+        }
+        for step in rides {
+            if !self.rides.contains(&step) {
+                self.rides.push(step);
+            }
+        }
+        self
+    }
+    pub fn from_betza(notation: &str) -> Option<Movement> {
+        /// Reads the plain part of Betza's funny notation: the atoms W F D N A H C Z G, a doubled
+        /// atom for a rider (NN is the nightrider) and the shorthands K, R, B and Q. Modifiers
+        /// such as move-only or capture-only are not supported and give None.
+        let chars: Vec<char> = notation.chars().collect();
+        let mut movement = Movement::default();
+        let mut i = 0;
+        while i < chars.len() {
+            let letter = chars[i];
+            let part = match letter {
+                'K' => Movement::leaper(1, 0).combine(Movement::leaper(1, 1)),
+                'R' => Movement::rider(1, 0),
+                'B' => Movement::rider(1, 1),
+                'Q' => Movement::rider(1, 0).combine(Movement::rider(1, 1)),
+                _ => {
+                    let (dx, dy) = betza_atom(letter)?;
+                    if chars.get(i + 1) == Some(&letter) {
+                        i += 1;
+                        Movement::rider(dx, dy)
+                    } else {
+                        Movement::leaper(dx, dy)
+                    }
+                }
+            };
+            movement = movement.combine(part);
+            i += 1;
+        }
+        if movement.leaps.is_empty() && movement.rides.is_empty() {
+            return None;
+        }
+        Some(movement)
+    }
+}
+
+/// A kind of piece in a fairy rule set. The letter is written uppercase for White and
+/// lowercase for Black in FEN. Pawns ignore the movement and move like chess pawns, and the
+/// side whose royal piece can not escape capture is checkmated.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PieceKind {
+    pub name: String,
+    pub letter: char,
+    pub movement: Movement,
+    pub royal: bool,
+    pub pawn: bool,
+}
+
+impl PieceKind {
+    pub fn from_betza(name: &str, letter: char, notation: &str) -> Option<PieceKind> {
+        /// A non-royal piece moving as described by the Betza notation.
+        let movement = Movement::from_betza(notation)?;
+        Some(PieceKind {
+            name: name.to_string(),
+            letter: letter.to_ascii_uppercase(),
+            movement,
+            royal: false,
+            pawn: false,
+        })
+    }
+    pub fn pawn() -> PieceKind {
+        PieceKind {
+            name: "Pawn".to_string(),
+            letter: 'P',
+            movement: Movement::default(),
+            royal: false,
+            pawn: true,
+        }
+    }
+    pub fn king() -> PieceKind {
+        /// The standard royal piece.
+        let mut king = PieceKind::from_betza("King", 'K', "K").unwrap();
+        king.royal = true;
+        king
+    }
+    pub fn knight() -> PieceKind {
+        PieceKind::from_betza("Knight", 'N', "N").unwrap()
+    }
+    pub fn bishop() -> PieceKind {
+        PieceKind::from_betza("Bishop", 'B', "B").unwrap()
+    }
+    pub fn rook() -> PieceKind {
+        PieceKind::from_betza("Rook", 'R', "R").unwrap()
+    }
+    pub fn queen() -> PieceKind {
+        PieceKind::from_betza("Queen", 'Q', "Q").unwrap()
+    }
+    pub fn archbishop() -> PieceKind {
+        /// Moves like a bishop or a knight.
+        PieceKind::from_betza("Archbishop", 'A', "BN").unwrap()
+    }
+    pub fn chancellor() -> PieceKind {
+        /// Moves like a rook or a knight.
+        PieceKind::from_betza("Chancellor", 'C', "RN").unwrap()
+    }
+    pub fn amazon() -> PieceKind {
+        /// Moves like a queen or a knight.
+        PieceKind::from_betza("Amazon", 'M', "QN").unwrap()
+    }
+    pub fn standard_set() -> Vec<PieceKind> {
+        /// The six pieces of standard chess.
+        vec![
+            PieceKind::pawn(),
+            PieceKind::knight(),
+            PieceKind::bishop(),
+            PieceKind::rook(),
+            PieceKind::queen(),
+            PieceKind::king(),
+        ]
+    }
+}
+
+/// A piece on a FairyBoard: an index into the board's kinds and the side it belongs to.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FairyPiece {
+    pub kind: usize,
+    pub color: Color,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FairyMove {
+    pub source: (usize, usize),
+    pub destination: (usize, usize),
+    /// The kind a pawn promotes to on the last row.
+    pub promotion: Option<usize>,
+}
+
+/// A board of any size played with any set of PieceKinds, for teaching positions and small
+/// variants such as 5x5 Minichess or 10x8 Capablanca chess. Coordinates are (x, y) as on
+/// ChessBoard, with y = 0 the row at the top (Black's side). Pawns step two squares from their
+/// starting row only when `double_step` is set, and promote to any non-royal piece of the set.
+/// Castling is done with the rooks in the corners: the royal piece moves to the third file or
+/// the second to last one, and the rook lands next to it on the inside, as in Capablanca chess.
+///
+/// FairyBoard has rules of its own and isn't a ChessBoard of another size. It knows no en
+/// passant, SAN, fifty move rule or repetitions, and the engine, PGN, FEN and variant modules
+/// only work with ChessBoard.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FairyBoard {
+    pub width: usize,
+    pub height: usize,
+    pub kinds: Vec<PieceKind>,
+    pub squares: Vec<Option<FairyPiece>>,
+    pub turn: u32,
+    pub double_step: bool,
+    /// The rook files are always the first and the last one.
+    pub castling_rights: CastlingRights,
+}
+
+impl FairyBoard {
+    pub fn empty(width: usize, height: usize, kinds: Vec<PieceKind>) -> Option<FairyBoard> {
+        /// An empty board of the given size. Files are named with letters, so the width is at
+        /// most 26; None is returned for sizes that can't be played.
+        if !(1..=26).contains(&width) || height == 0 {
+            return None;
+        }
+        Some(FairyBoard {
+            width,
+            height,
+            kinds,
+            squares: vec![None; width * height],
+            turn: 1,
+            double_step: false,
+            castling_rights: CastlingRights::none(),
+        })
+    }
+    pub fn from_fen(
+        fen: &str,
+        width: usize,
+        height: usize,
+        kinds: Vec<PieceKind>,
+    ) -> Result<FairyBoard, FenError> {
+        /// Reads the placement, side to move and castling rights of a FEN for the given size.
+        /// Empty runs may have more than one digit ("10"), and the letters are those of the kinds.
+        /// The en passant field is ignored; the move number is read when present.
+        let mut board = FairyBoard::empty(width, height, kinds)
+            .ok_or(FenError::InvalidBoardSize(width, height))?;
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingField("placement"))?;
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != height {
+            return Err(FenError::InvalidRowCount(rows.len()));
+        }
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = 0;
+            let mut run = 0;
+            for c in row.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    run = run * 10 + digit as usize;
+                    continue;
+                }
+                x += run;
+                run = 0;
+                let kind = board.kind_by_letter(c).ok_or(FenError::InvalidPiece(c))?;
+                if x >= width {
+                    return Err(FenError::InvalidRowLength(y));
+                }
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                board.set((x, y), Some(FairyPiece { kind, color }));
+                x += 1;
+            }
+            if x + run != width {
+                return Err(FenError::InvalidRowLength(y));
+            }
+        }
+        let side = fields.next().unwrap_or("w");
+        let black = match side {
+            "w" => false,
+            "b" => true,
+            _ => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+        let castling = fields.next().unwrap_or("-");
+        for c in castling.chars().filter(|_| castling != "-") {
+            let (color, kingside) = match c {
+                'K' => (Color::White, true),
+                'Q' => (Color::White, false),
+                'k' => (Color::Black, true),
+                'q' => (Color::Black, false),
+                _ => return Err(FenError::InvalidCastling(castling.to_string())),
+            };
+            let file = if kingside { width - 1 } else { 0 };
+            board.castling_rights.grant(&color, kingside, file);
+        }
+        let number = fields.nth(2).unwrap_or("1");
+        let number: u32 = number
+            .parse()
+            .ok()
+            .filter(|n| *n >= 1)
+            .ok_or_else(|| FenError::InvalidNumber(number.to_string()))?;
+        board.turn = (number - 1) * 2 + 1 + black as u32;
+        Ok(board)
+    }
+    pub fn gardner_minichess() -> FairyBoard {
+        /// Gardner's 5x5 Minichess: the standard pieces without double steps.
+        FairyBoard::from_fen(
+            "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1",
+            5,
+            5,
+            PieceKind::standard_set(),
+        )
+        .unwrap()
+    }
+    pub fn capablanca() -> FairyBoard {
+        /// Capablanca chess on 10x8, with an archbishop and a chancellor added to the standard
+        /// pieces. The king castles three squares to the c or the i file.
+        let mut kinds = PieceKind::standard_set();
+        kinds.push(PieceKind::archbishop());
+        kinds.push(PieceKind::chancellor());
+        let fen = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+        let mut board = FairyBoard::from_fen(fen, 10, 8, kinds).unwrap();
+        board.double_step = true;
+        board
+    }
+    pub fn to_fen(&self) -> String {
+        let mut rows = Vec::new();
+        for y in 0..self.height {
+            let mut row = String::new();
+            let mut run = 0;
+            for x in 0..self.width {
+                match self.get((x, y)) {
+                    Some(piece) => {
+                        if run > 0 {
+                            row.push_str(&run.to_string());
+                            run = 0;
+                        }
+                        row.push(self.piece_letter(&piece));
+                    }
+                    None => run += 1,
+                }
+            }
+            if run > 0 {
+                row.push_str(&run.to_string());
+            }
+            rows.push(row);
+        }
+        let side = match self.faction_decider() {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+        let mut castling = String::new();
+        for (letter, color, kingside) in [
+            ('K', Color::White, true),
+            ('Q', Color::White, false),
+            ('k', Color::Black, true),
+            ('q', Color::Black, false),
+        ] {
+            if self.castling_rights.available(&color, kingside) {
+                castling.push(letter);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        format!(
+            "{} {} {} - 0 {}",
+            rows.join("/"),
+            side,
+            castling,
+            self.turn.div_ceil(2)
+        )
+    }
+    fn kind_by_letter(&self, c: char) -> Option<usize> {
+        let letter = c.to_ascii_uppercase();
+        self.kinds.iter().position(|kind| kind.letter == letter)
+    }
+    fn piece_letter(&self, piece: &FairyPiece) -> char {
+        let letter = self.kinds[piece.kind].letter;
+        match piece.color {
+            Color::White => letter,
+            Color::Black => letter.to_ascii_lowercase(),
+        }
+    }
+    pub fn get(&self, square: (usize, usize)) -> Option<FairyPiece> {
+        self.squares[square.1 * self.width + square.0]
+    }
+    pub fn set(&mut self, square: (usize, usize), piece: Option<FairyPiece>) {
+        let index = square.1 * self.width + square.0;
+        self.squares[index] = piece;
+    }
+    pub fn faction_decider(&self) -> Color {
+        if self.turn % 2 == 1 {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+    pub fn square_name(&self, square: (usize, usize)) -> String {
+        /// Files are letters from a and ranks count up from White's side, so the top left of a
+        /// 10x8 board is a8.
+        let file = (b'a' + square.0 as u8) as char;
+        format!("{}{}", file, self.height - square.1)
+    }
+    pub fn parse_square(&self, name: &str) -> Option<(usize, usize)> {
+        let mut chars = name.chars();
+        let x = (chars.next()? as usize).checked_sub('a' as usize)?;
+        let rank: usize = chars.as_str().parse().ok()?;
+        if x >= self.width || rank == 0 || rank > self.height {
+            return None;
+        }
+        Some((x, self.height - rank))
+    }
+    pub fn move_name(&self, mv: &FairyMove) -> String {
+        /// The move in coordinate notation, with the promotion as the lowercase letter.
+        let mut name = self.square_name(mv.source) + &self.square_name(mv.destination);
+        if let Some(kind) = mv.promotion {
+            name.push(self.kinds[kind].letter.to_ascii_lowercase());
+        }
+        name
+    }
+    pub fn parse_move(&self, text: &str) -> Option<FairyMove> {
+        /// Finds the legal move written as by move_name.
+        self.legal_moves()
+            .into_iter()
+            .find(|mv| self.move_name(mv) == text)
+    }
+    fn offset(&self, square: (usize, usize), step: (i32, i32)) -> Option<(usize, usize)> {
+        let x = square.0 as i32 + step.0;
+        let y = square.1 as i32 + step.1;
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+    fn forward(color: Color) -> i32 {
+        match color {
+            Color::White => -1,
+            Color::Black => 1,
+        }
+    }
+    fn targets(
+        &self,
+        square: (usize, usize),
+        piece: FairyPiece,
+        captures_only: bool,
+    ) -> Vec<(usize, usize)> {
+        /// The squares the piece on square can move to, ignoring whether the own royal piece
+        /// stays safe. With captures_only, pawns only give their diagonal squares, which is what
+        /// attack detection needs.
+        let mut targets = Vec::new();
+        let kind = &self.kinds[piece.kind];
+        let free = |target: (usize, usize)| match self.get(target) {
+            Some(other) => other.color != piece.color,
+            None => true,
+        };
+        if kind.pawn {
+            let dy = FairyBoard::forward(piece.color);
+            for dx in [-1, 1] {
+                if let Some(target) = self.offset(square, (dx, dy)) {
+                    let enemy = self.get(target).is_some_and(|p| p.color != piece.color);
+                    if enemy || captures_only {
+                        targets.push(target);
+                    }
+                }
+            }
+            if captures_only {
+                return targets;
+            }
+            if let Some(one) = self
+                .offset(square, (0, dy))
+                .filter(|t| self.get(*t).is_none())
+            {
+                targets.push(one);
+                let start = match piece.color {
+                    Color::White => self.height - 2,
+                    Color::Black => 1,
+                };
+                if self.double_step && square.1 == start {
+                    if let Some(two) = self.offset(one, (0, dy)).filter(|t| self.get(*t).is_none())
+                    {
+                        targets.push(two);
+                    }
+                }
+            }
+            return targets;
+        }
+        for step in &kind.movement.leaps {
+            if let Some(target) = self.offset(square, *step).filter(|t| free(*t)) {
+                targets.push(target);
+            }
+        }
+        for step in &kind.movement.rides {
+            let mut current = square;
+            while let Some(target) = self.offset(current, *step) {
+                if free(target) {
+                    targets.push(target);
+                }
+                if self.get(target).is_some() {
+                    break;
+                }
+                current = target;
+            }
+        }
+        targets
+    }
+    pub fn select_piece(&self, square: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        /// The legal destinations of the piece on square if it belongs to the side to move.
+        let piece = self.get(square)?;
+        if piece.color != self.faction_decider() {
+            return None;
+        }
+        let mut destinations: Vec<(usize, usize)> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.source == square)
+            .map(|mv| mv.destination)
+            .collect();
+        destinations.dedup();
+        Some(destinations)
+    }
+    pub fn is_attacked(&self, square: (usize, usize), by: Color) -> bool {
+        let squares = self.occupied(by);
+        squares
+            .into_iter()
+            .any(|(from, piece)| self.targets(from, piece, true).contains(&square))
+    }
+    fn occupied(&self, color: Color) -> Vec<((usize, usize), FairyPiece)> {
+        let mut pieces = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(piece) = self.get((x, y)).filter(|p| p.color == color) {
+                    pieces.push(((x, y), piece));
+                }
+            }
+        }
+        pieces
+    }
+    pub fn is_in_check(&self, color: Color) -> bool {
+        /// Whether any royal piece of color is attacked. A side without royal pieces is never
+        /// in check.
+        self.occupied(color)
+            .into_iter()
+            .filter(|(_, piece)| self.kinds[piece.kind].royal)
+            .any(|(square, _)| self.is_attacked(square, color.opposite()))
+    }
+    pub fn legal_moves(&self) -> Vec<FairyMove> {
+        let color = self.faction_decider();
+        let promotions: Vec<usize> = (0..self.kinds.len())
+            .filter(|k| !self.kinds[*k].pawn && !self.kinds[*k].royal)
+            .collect();
+        let last_row = match color {
+            Color::White => 0,
+            Color::Black => self.height - 1,
+        };
+        let mut moves = Vec::new();
+        for (source, piece) in self.occupied(color) {
+            let pawn = self.kinds[piece.kind].pawn;
+            for destination in self.targets(source, piece, false) {
+                if pawn && destination.1 == last_row {
+                    for kind in &promotions {
+                        moves.push(FairyMove {
+                            source,
+                            destination,
+                            promotion: Some(*kind),
+                        });
+                    }
+                } else {
+                    moves.push(FairyMove {
+                        source,
+                        destination,
+                        promotion: None,
+                    });
+                }
+            }
+        }
+        moves.extend(self.castling_moves(color));
+        moves.retain(|mv| {
+            let mut after = self.clone();
+            after.apply_move(mv);
+            !after.is_in_check(color)
+        });
+        moves
+    }
+    fn home_row(&self, color: Color) -> usize {
+        match color {
+            Color::White => self.height - 1,
+            Color::Black => 0,
+        }
+    }
+    fn castling_squares(&self, kingside: bool) -> Option<(usize, usize)> {
+        // The files the royal piece and the rook end up on. Boards narrower than 5 files have no
+        // room for castling.
+        match kingside {
+            _ if self.width < 5 => None,
+            true => Some((self.width - 2, self.width - 3)),
+            false => Some((2, 3)),
+        }
+    }
+    fn castling_moves(&self, color: Color) -> Vec<FairyMove> {
+        // The king may not castle out of, through or into check, and every square between the
+        // king, the rook and where they land has to be empty. The rook is the piece written R.
+        let row = self.home_row(color);
+        let king = (0..self.width).find(|x| {
+            self.get((*x, row))
+                .is_some_and(|p| p.color == color && self.kinds[p.kind].royal)
+        });
+        let Some(king) = king else {
+            return Vec::new();
+        };
+        let mut moves = Vec::new();
+        for kingside in [true, false] {
+            let rook = self.castling_rights.rook_file(&color, kingside);
+            let Some((king_to, rook_to)) = self.castling_squares(kingside) else {
+                continue;
+            };
+            let available = self.castling_rights.available(&color, kingside)
+                && rook < self.width
+                && self
+                    .get((rook, row))
+                    .is_some_and(|p| p.color == color && self.kinds[p.kind].letter == 'R');
+            if !available || king.abs_diff(king_to) < 2 {
+                continue;
+            }
+            let files = [king, rook, king_to, rook_to];
+            let (low, high) = (*files.iter().min().unwrap(), *files.iter().max().unwrap());
+            let blocked =
+                (low..=high).any(|x| x != king && x != rook && self.get((x, row)).is_some());
+            let passed = king.min(king_to)..=king.max(king_to);
+            let attacked = passed
+                .into_iter()
+                .any(|x| self.is_attacked((x, row), color.opposite()));
+            if !blocked && !attacked {
+                moves.push(FairyMove {
+                    source: (king, row),
+                    destination: (king_to, row),
+                    promotion: None,
+                });
+            }
+        }
+        moves
+    }
+    fn apply_move(&mut self, mv: &FairyMove) {
+        let mut piece = self.get(mv.source);
+        if let (Some(piece), Some(kind)) = (piece.as_mut(), mv.promotion) {
+            piece.kind = kind;
+        }
+        let mut castling = None;
+        if let Some(moved) = piece.filter(|p| self.kinds[p.kind].royal) {
+            // A royal piece that may still castle and moves at least two files along its home row
+            // to where castling puts it is castling.
+            let row = self.home_row(moved.color);
+            for kingside in [true, false] {
+                let Some((king_to, rook_to)) = self.castling_squares(kingside) else {
+                    continue;
+                };
+                if self.castling_rights.available(&moved.color, kingside)
+                    && mv.source.1 == row
+                    && mv.destination == (king_to, row)
+                    && mv.source.0.abs_diff(king_to) >= 2
+                {
+                    let rook = self.castling_rights.rook_file(&moved.color, kingside);
+                    castling = Some(((rook, row), (rook_to, row)));
+                }
+            }
+            self.castling_rights.revoke(&moved.color, true);
+            self.castling_rights.revoke(&moved.color, false);
+        }
+        for square in [mv.source, mv.destination] {
+            for color in [Color::White, Color::Black] {
+                for kingside in [true, false] {
+                    let rook = (
+                        self.castling_rights.rook_file(&color, kingside),
+                        self.home_row(color),
+                    );
+                    if square == rook {
+                        self.castling_rights.revoke(&color, kingside);
+                    }
+                }
+            }
+        }
+        self.set(mv.source, None);
+        self.set(mv.destination, piece);
+        if let Some((rook, rook_to)) = castling {
+            let rook_piece = self.get(rook);
+            self.set(rook, None);
+            self.set(rook_to, rook_piece);
+        }
+        self.turn += 1;
+    }
+    pub fn make_move(&mut self, mv: &FairyMove) -> bool {
+        /// Plays the move if it is legal and reports whether it was.
+        let legal = self.legal_moves().contains(mv);
+        if legal {
+            self.apply_move(mv);
+        }
+        legal
+    }
+    pub fn is_checkmate(&self) -> bool {
+        self.is_in_check(self.faction_decider()) && self.legal_moves().is_empty()
+    }
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_in_check(self.faction_decider()) && self.legal_moves().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(board: &FairyBoard, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for mv in board.legal_moves() {
+            let mut after = board.clone();
+            after.apply_move(&mv);
+            nodes += perft(&after, depth - 1);
+        }
+        nodes
+    }
+
+    #[test]
+    fn betza_test() {
+        let archbishop = Movement::from_betza("BN").unwrap();
+        assert_eq!(archbishop.leaps.len(), 8);
+        assert_eq!(archbishop.rides.len(), 4);
+        let nightrider = Movement::from_betza("NN").unwrap();
+        assert_eq!((nightrider.leaps.len(), nightrider.rides.len()), (0, 8));
+        assert_eq!(Movement::from_betza("K").unwrap().leaps.len(), 8);
+        assert!(Movement::from_betza("fmW").is_none());
+        assert!(Movement::from_betza("").is_none());
+    }
+
+    #[test]
+    fn minichess_test() {
+        let board = FairyBoard::gardner_minichess();
+        assert_eq!(board.to_fen(), "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1");
+        assert_eq!(perft(&board, 1), 7);
+        assert_eq!(perft(&board, 2), 53);
+    }
+
+    #[test]
+    fn capablanca_test() {
+        let board = FairyBoard::capablanca();
+        assert_eq!(
+            board.to_fen(),
+            "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1"
+        );
+        assert_eq!(perft(&board, 1), 28);
+        assert_eq!(perft(&board, 2), 784);
+        let archbishop = board.parse_square("c1").unwrap();
+        let destinations = board.select_piece(archbishop).unwrap();
+        assert_eq!(destinations.len(), 2);
+    }
+
+    #[test]
+    fn capablanca_castling_test() {
+        let kinds = FairyBoard::capablanca().kinds;
+        let fen = "r4k3r/10/10/10/10/10/10/R4K3R w KQkq - 0 1";
+        let board = FairyBoard::from_fen(fen, 10, 8, kinds.clone()).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        let mut kingside = board.clone();
+        assert!(kingside.make_move(&board.parse_move("f1i1").unwrap()));
+        assert_eq!(
+            kingside.to_fen(),
+            "r4k3r/10/10/10/10/10/10/R6RK1 b kq - 0 1"
+        );
+        let mut queenside = board.clone();
+        assert!(queenside.make_move(&board.parse_move("f1c1").unwrap()));
+        assert_eq!(
+            queenside.to_fen(),
+            "r4k3r/10/10/10/10/10/10/2KR5R b kq - 0 1"
+        );
+        // The king may not pass the attacked g1, and a moved rook gives up its side.
+        let attacked = "r4kr3/10/10/10/10/10/10/R4K3R w KQkq - 0 1";
+        let board = FairyBoard::from_fen(attacked, 10, 8, kinds.clone()).unwrap();
+        assert!(board.parse_move("f1i1").is_none());
+        assert!(board.parse_move("f1c1").is_some());
+        let mut board = FairyBoard::from_fen(fen, 10, 8, kinds.clone()).unwrap();
+        for mv in ["a1a2", "a8a7", "a2a1", "a7a8"] {
+            assert!(board.make_move(&board.parse_move(mv).unwrap()));
+        }
+        assert!(board.parse_move("f1c1").is_none());
+        assert_eq!(board.to_fen(), "r4k3r/10/10/10/10/10/10/R4K3R w Kk - 0 3");
+        // Only a rook castles, even if the right was never taken away.
+        let bishop = "r4k3r/10/10/10/10/10/10/R4K3B w KQkq - 0 1";
+        let board = FairyBoard::from_fen(bishop, 10, 8, kinds).unwrap();
+        assert!(board.parse_move("f1i1").is_none());
+        assert!(board.parse_move("f1c1").is_some());
+        assert_eq!(
+            FairyBoard::gardner_minichess().castling_rights,
+            CastlingRights::none()
+        );
+    }
+
+    #[test]
+    fn fairy_checkmate_test() {
+        let mut kinds = PieceKind::standard_set();
+        kinds.push(PieceKind::amazon());
+        let mut board = FairyBoard::from_fen("k5/6/1K4/6/6/2M3 w - - 0 1", 6, 6, kinds).unwrap();
+        assert!(!board.is_in_check(Color::Black));
+        let mv = board.parse_move("c1c5").unwrap();
+        assert!(board.make_move(&mv));
+        assert!(board.is_checkmate());
+        assert_eq!(board.to_fen(), "k5/2M3/1K4/6/6/6 b - - 0 1");
+        assert!(
+            FairyBoard::from_fen("k5/6/6/6/6/X5 w - - 0 1", 6, 6, PieceKind::standard_set())
+                .is_err()
+        );
+        let wide = FairyBoard::from_fen("27", 27, 1, PieceKind::standard_set());
+        assert_eq!(wide, Err(FenError::InvalidBoardSize(27, 1)));
+        assert!(FairyBoard::empty(8, 0, PieceKind::standard_set()).is_none());
+    }
+}
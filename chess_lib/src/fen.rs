@@ -0,0 +1,470 @@
+use crate::{parse_square, square_name, CastlingRights, ChessBoard, ChessPiece, Color, Variant};
+use std::fmt;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Reasons a FEN string could not be turned into a ChessBoard.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPiece(char),
+    InvalidRowLength(usize),
+    InvalidRowCount(usize),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidNumber(String),
+    InvalidChecks(String),
+    InvalidPocket(String),
+    /// A FairyBoard of this width and height can't be made.
+    InvalidBoardSize(usize, usize),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing {} field", field),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::InvalidRowLength(row) => {
+                write!(f, "row {} has the wrong number of squares", row)
+            }
+            FenError::InvalidRowCount(count) => write!(f, "wrong number of rows ({})", count),
+            FenError::InvalidSideToMove(side) => write!(f, "invalid side to move '{}'", side),
+            FenError::InvalidCastling(castling) => {
+                write!(f, "invalid castling field '{}'", castling)
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "invalid en passant square '{}'", square)
+            }
+            FenError::InvalidNumber(number) => write!(f, "invalid move number '{}'", number),
+            FenError::InvalidChecks(checks) => write!(f, "invalid remaining checks '{}'", checks),
+            FenError::InvalidPocket(pocket) => write!(f, "invalid pocket '{}'", pocket),
+            FenError::InvalidBoardSize(width, height) => {
+                write!(f, "unsupported board size {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+pub(crate) fn piece_from_char(c: char) -> Option<ChessPiece> {
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    match c.to_ascii_lowercase() {
+        'p' => Some(ChessPiece::Pawn(color)),
+        'r' => Some(ChessPiece::Rook(color)),
+        'n' => Some(ChessPiece::Knight(color)),
+        'b' => Some(ChessPiece::Bishop(color)),
+        'q' => Some(ChessPiece::Queen(color)),
+        'k' => Some(ChessPiece::King(color)),
+        _ => None,
+    }
+}
+
+pub(crate) fn piece_to_char(piece: &ChessPiece) -> char {
+    let c = match piece {
+        ChessPiece::Pawn(_) => 'p',
+        ChessPiece::Rook(_) => 'r',
+        ChessPiece::Knight(_) => 'n',
+        ChessPiece::Bishop(_) => 'b',
+        ChessPiece::Queen(_) => 'q',
+        ChessPiece::King(_) => 'k',
+    };
+    match piece.color() {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
+
+impl ChessBoard {
+    pub fn from_fen(fen: &str) -> Result<ChessBoard, FenError> {
+        /// Parses a position in Forsyth-Edwards Notation.
+        /// The castling, en passant and move number fields may be left out, as in EPD records.
+        /// A three-check FEN with the remaining checks after the en passant field ("3+3") sets up a
+        /// ThreeCheck board.
+        let mut fields = fen.split_whitespace().peekable();
+        let placement = fields.next().ok_or(FenError::MissingField("placement"))?;
+        let mut board = ChessBoard::empty();
+
+        // Crazyhouse pockets come after the placement in brackets, or as a 9th row.
+        let (placement, pocket) = match placement.split_once('[') {
+            Some((placement, pocket)) => match pocket.strip_suffix(']') {
+                Some(pocket) => (placement, Some(pocket)),
+                None => return Err(FenError::InvalidPocket(pocket.to_string())),
+            },
+            None => (placement, None),
+        };
+        let mut rows: Vec<&str> = placement.split('/').collect();
+        let pocket = match rows.len() {
+            9 if pocket.is_none() => rows.pop(),
+            _ => pocket,
+        };
+        if rows.len() != 8 {
+            return Err(FenError::InvalidRowCount(rows.len()));
+        }
+        for (y, row) in rows.iter().enumerate() {
+            let mut x = 0;
+            for c in row.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    x += skip as usize;
+                    continue;
+                }
+                if c == '~' {
+                    // Marks the piece before it as a promoted pawn.
+                    if x == 0 || x > 8 || board.board[y][x - 1].is_none() {
+                        return Err(FenError::InvalidPiece(c));
+                    }
+                    board.promoted[y][x - 1] = true;
+                    continue;
+                }
+                let piece = piece_from_char(c).ok_or(FenError::InvalidPiece(c))?;
+                if x >= 8 {
+                    return Err(FenError::InvalidRowLength(y));
+                }
+                match piece {
+                    ChessPiece::King(Color::White) => board.white_king_pos = (x, y),
+                    ChessPiece::King(Color::Black) => board.black_king_pos = (x, y),
+                    _ => {}
+                }
+                board.board[y][x] = Some(piece);
+                x += 1;
+            }
+            if x != 8 {
+                return Err(FenError::InvalidRowLength(y));
+            }
+        }
+
+        if let Some(pocket) = pocket {
+            board.variant = Variant::Crazyhouse;
+            for c in pocket.chars() {
+                let piece = piece_from_char(c)
+                    .filter(|piece| !matches!(piece, ChessPiece::King(_)))
+                    .ok_or_else(|| FenError::InvalidPocket(pocket.to_string()))?;
+                // A piece in White's pocket was captured from Black, so it is kept in removed_black.
+                match piece.color() {
+                    Color::White => board.removed_black.push(piece.with_color(Color::Black)),
+                    Color::Black => board.removed_white.push(piece.with_color(Color::White)),
+                }
+            }
+        }
+
+        let side = fields
+            .next()
+            .ok_or(FenError::MissingField("side to move"))?;
+        let black_to_move = match side {
+            "w" => false,
+            "b" => true,
+            _ => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+
+        let castling = fields.next().unwrap_or("-");
+        if castling != "-" {
+            for c in castling.chars() {
+                let (color, row) = if c.is_ascii_uppercase() {
+                    (Color::White, 7)
+                } else {
+                    (Color::Black, 0)
+                };
+                let king_file = (0..8)
+                    .find(|file| board.board[row][*file] == Some(ChessPiece::King(color)))
+                    .unwrap_or(4);
+                let is_rook =
+                    |file: &usize| board.board[row][*file] == Some(ChessPiece::Rook(color));
+                // K and Q stand for the outermost rook on that side, as in X-FEN. Shredder-FEN names
+                // the file of the rook instead.
+                let (kingside, file) = match c.to_ascii_uppercase() {
+                    'K' => (true, (king_file..8).rev().find(is_rook).unwrap_or(7)),
+                    'Q' => (false, (0..king_file).find(is_rook).unwrap_or(0)),
+                    'A'..='H' => {
+                        board.chess960 = true;
+                        let file = (c.to_ascii_uppercase() as u8 - b'A') as usize;
+                        (file > king_file, file)
+                    }
+                    _ => return Err(FenError::InvalidCastling(castling.to_string())),
+                };
+                board.castling_rights.grant(&color, kingside, file);
+                if king_file != 4 || file != if kingside { 7 } else { 0 } {
+                    board.chess960 = true;
+                }
+            }
+        }
+
+        let en_passant = fields.next().unwrap_or("-");
+        if en_passant != "-" {
+            board.en_passant = Some(
+                parse_square(en_passant)
+                    .ok_or_else(|| FenError::InvalidEnPassant(en_passant.to_string()))?,
+            );
+        }
+
+        let parse_number = |field: Option<&str>, default: u32| match field {
+            Some(number) => number
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidNumber(number.to_string())),
+            None => Ok(default),
+        };
+        if let Some(checks) = fields.next_if(|field| field.contains('+')) {
+            let invalid = || FenError::InvalidChecks(checks.to_string());
+            let (white, black) = checks.split_once('+').ok_or_else(invalid)?;
+            let remaining = |count: &str| count.parse::<u32>().ok().filter(|count| *count <= 3);
+            board.white_checks = 3 - remaining(white).ok_or_else(invalid)?;
+            board.black_checks = 3 - remaining(black).ok_or_else(invalid)?;
+            board.variant = Variant::ThreeCheck;
+        }
+        board.halfmove_clock = parse_number(fields.next(), 0)?;
+        let fullmove = parse_number(fields.next(), 1)?.max(1);
+        board.turn = 2 * (fullmove - 1) + if black_to_move { 2 } else { 1 };
+        Ok(board)
+    }
+    pub fn to_fen(&self) -> String {
+        /// Writes the position in Forsyth-Edwards Notation. Chess960 positions get X-FEN castling
+        /// rights, which only name the rook file when it is not the outermost rook on its side.
+        self.write_fen(false)
+    }
+    pub fn to_shredder_fen(&self) -> String {
+        /// Writes the position like to_fen, but with the castling rights given as rook files in
+        /// the Shredder-FEN style, e.g. "HAha" for the standard starting position.
+        self.write_fen(true)
+    }
+    fn castling_field(&self, shredder: bool) -> String {
+        let rights = &self.castling_rights;
+        let mut castling = String::new();
+        for (color, row, kingside) in [
+            (Color::White, 7, true),
+            (Color::White, 7, false),
+            (Color::Black, 0, true),
+            (Color::Black, 0, false),
+        ] {
+            if !rights.available(&color, kingside) {
+                continue;
+            }
+            let file = rights.rook_file(&color, kingside);
+            let rook = self.board[row][file];
+            let outermost = if kingside {
+                (file + 1..8).all(|other| self.board[row][other] != rook)
+            } else {
+                (0..file).all(|other| self.board[row][other] != rook)
+            };
+            let c = if shredder || (self.chess960 && !outermost) {
+                (b'A' + file as u8) as char
+            } else if kingside {
+                'K'
+            } else {
+                'Q'
+            };
+            castling.push(if row == 7 { c } else { c.to_ascii_lowercase() });
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        castling
+    }
+    fn write_fen(&self, shredder: bool) -> String {
+        let mut fen = String::new();
+        for (y, row) in self.board.iter().enumerate() {
+            let mut empty = 0;
+            for (x, square) in row.iter().enumerate() {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(piece));
+                        if self.promoted[y][x] {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < 7 {
+                fen.push('/');
+            }
+        }
+        if self.variant == Variant::Crazyhouse {
+            fen.push('[');
+            for color in [Color::White, Color::Black] {
+                fen.extend(self.pocket(&color).iter().map(piece_to_char));
+            }
+            fen.push(']');
+        }
+
+        fen.push_str(match self.faction_decider() {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        fen.push_str(&self.castling_field(shredder));
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {}", square_name(square))),
+            None => fen.push_str(" -"),
+        }
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(
+                " {}+{}",
+                3u32.saturating_sub(self.white_checks),
+                3u32.saturating_sub(self.black_checks)
+            ));
+        }
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock,
+            self.turn.div_ceil(2)
+        ));
+        fen
+    }
+}
+
+/// Serializes a ChessBoard as its FEN string instead of field by field, for a compact representation:
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct SavedGame {
+///     #[serde(with = "chess_lib::fen::serde_fen")]
+///     board: ChessBoard,
+/// }
+/// ```
+///
+/// Crazyhouse pockets are part of the FEN and come back in the removed_* vectors. Other captured
+/// pieces have no place in FEN, so outside crazyhouse those vectors come back empty.
+#[cfg(feature = "serde")]
+pub mod serde_fen {
+    use crate::ChessBoard;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(board: &ChessBoard, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&board.to_fen())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ChessBoard, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        ChessBoard::from_fen(&fen).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_position_round_trip_test() {
+        let board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+        assert_eq!(board.to_fen(), STARTING_FEN);
+        assert_eq!(ChessBoard::create().to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn fen_fields_test() {
+        let fen = "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w Kq c6 0 2";
+        let board = ChessBoard::from_fen(fen).unwrap();
+        assert_eq!(board.faction_decider(), Color::White);
+        assert_eq!(board.turn, 3);
+        assert_eq!(board.en_passant, Some((2, 2)));
+        assert!(board.castling_rights.white_kingside);
+        assert!(!board.castling_rights.white_queenside);
+        assert_eq!(board.to_fen(), fen);
+
+        let board = ChessBoard::from_fen("8/8/8/8/8/8/8/k6K b - -").unwrap();
+        assert_eq!(board.faction_decider(), Color::Black);
+        assert_eq!(board.white_king_pos, (7, 7));
+        assert_eq!(board.black_king_pos, (0, 7));
+    }
+
+    #[test]
+    fn chess960_castling_fields_test() {
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let board = ChessBoard::from_fen(fen).unwrap();
+        assert!(board.chess960);
+        assert_eq!(board.castling_rights.rook_file(&Color::White, true), 7);
+        assert_eq!(board.castling_rights.rook_file(&Color::Black, false), 5);
+        assert_eq!(board.to_shredder_fen(), fen);
+        // Both rooks are the outermost ones on their side of the king, so X-FEN can use KQkq.
+        assert_eq!(
+            board.to_fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/RR2K2R w KB - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/RR2K2R w KB - 0 1");
+        assert_eq!(ChessBoard::from_fen(&board.to_fen()).unwrap(), board);
+
+        let board =
+            ChessBoard::from_fen("r1k1r2q/p1ppp1pp/8/8/8/8/P1PPP1PP/R1K1R2Q w KQkq - 0 1").unwrap();
+        assert!(board.chess960);
+        assert_eq!(board.castling_rights.rook_file(&Color::Black, true), 4);
+        assert_eq!(board.to_shredder_fen().split(' ').nth(2), Some("EAea"));
+        // The sides castle with rooks on different files.
+        let fen = "1r2k1r1/8/8/8/8/8/8/R3K2R b HAgb - 0 1";
+        let mut board = ChessBoard::from_fen(fen).unwrap();
+        assert_eq!(board.castling_rights.rook_file(&Color::White, true), 7);
+        assert_eq!(board.castling_rights.rook_file(&Color::Black, true), 6);
+        assert_eq!(board.castling_rights.rook_file(&Color::Black, false), 1);
+        assert_eq!(board.to_shredder_fen(), fen);
+        assert_eq!(board.to_fen(), "1r2k1r1/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert_eq!(ChessBoard::from_fen(&board.to_fen()).unwrap(), board);
+        let castle = board.parse_uci_move("e8g8").unwrap();
+        assert!(board.is_castling(&castle));
+        board.make_move(&castle);
+        assert_eq!(
+            board.to_shredder_fen(),
+            "1r3rk1/8/8/8/8/8/8/R3K2R w HA - 1 2"
+        );
+        assert!(!ChessBoard::create().chess960);
+        assert_eq!(
+            ChessBoard::create().to_shredder_fen().split(' ').nth(2),
+            Some("HAha")
+        );
+    }
+
+    #[test]
+    fn invalid_fen_test() {
+        assert_eq!(
+            ChessBoard::from_fen("8/8/8/8/8/8/8 w - - 0 1").err(),
+            Some(FenError::InvalidRowCount(7))
+        );
+        assert_eq!(
+            ChessBoard::from_fen("8/8/8/8/8/8/8/7X w - - 0 1").err(),
+            Some(FenError::InvalidPiece('X'))
+        );
+        assert_eq!(
+            ChessBoard::from_fen("8/8/8/8/8/8/8/9 w - - 0 1").err(),
+            Some(FenError::InvalidRowLength(7))
+        );
+        assert!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 x - - 0 1").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_test() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Saved {
+            #[serde(with = "serde_fen")]
+            board: ChessBoard,
+        }
+        let mut board = ChessBoard::create();
+        board.set_piece((4, 6), (4, 4));
+        board.set_piece((3, 1), (3, 3));
+        board.set_piece((4, 4), (3, 3));
+
+        let json = serde_json::to_string(&board).unwrap();
+        let restored: ChessBoard = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, board);
+        assert_eq!(restored.removed_black, vec![ChessPiece::Pawn(Color::Black)]);
+
+        let saved = Saved {
+            board: board.clone(),
+        };
+        let json = serde_json::to_string(&saved).unwrap();
+        assert_eq!(json, format!("{{\"board\":\"{}\"}}", board.to_fen()));
+        let restored: Saved = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.board.to_fen(), board.to_fen());
+        assert!(serde_json::from_str::<Saved>("{\"board\":\"8/8 w\"}").is_err());
+    }
+}
@@ -0,0 +1,473 @@
+use crate::clock::{Clock, TimeOutcome, TimeSource, WallClock};
+use crate::fen::STARTING_FEN;
+use crate::pgn::{wrap_movetext, write_tags};
+use crate::{ChessBoard, Color, Move, Variant};
+use std::fmt;
+
+/// Who won a finished game.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    Win(Color),
+    Draw,
+}
+
+impl Outcome {
+    pub fn to_pgn(&self) -> &'static str {
+        /// The result as written in PGN, "1-0", "0-1" or "1/2-1/2".
+        match self {
+            Outcome::Win(Color::White) => "1-0",
+            Outcome::Win(Color::Black) => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// Why a game ended.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    Timeout,
+    DrawAgreement,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    /// Decided by an arbiter or a program rather than by the rules.
+    Adjudication,
+    /// Decided by a rule of the variant being played, like a third check.
+    VariantRule,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GameResult {
+    pub outcome: Outcome,
+    pub termination: Termination,
+}
+
+/// Reasons an action was refused by a Game.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameError {
+    GameOver,
+    IllegalMove(String),
+    NoDrawOffer,
+    /// A player tried to answer their own draw offer.
+    OwnDrawOffer,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::GameOver => write!(f, "the game is already over"),
+            GameError::IllegalMove(mv) => write!(f, "illegal move '{}'", mv),
+            GameError::NoDrawOffer => write!(f, "no draw has been offered"),
+            GameError::OwnDrawOffer => write!(f, "a draw offer must be answered by the opponent"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// A game of chess: the position together with the moves that led to it, the players, PGN style
+/// tags, an optional clock and the result once the game is over. Moves are checked for legality
+/// and the game ends on its own by checkmate, stalemate, repetition, the 50 move rule,
+/// insufficient material or a flag fall.
+pub struct Game<T: TimeSource = WallClock> {
+    start: ChessBoard,
+    board: ChessBoard,
+    moves: Vec<Move>,
+    /// Hashes of the positions before each move, for spotting repetitions.
+    hashes: Vec<u64>,
+    pub white: String,
+    pub black: String,
+    tags: Vec<(String, String)>,
+    clock: Option<Clock<T>>,
+    draw_offer: Option<Color>,
+    result: Option<GameResult>,
+}
+
+impl Game<WallClock> {
+    pub fn new(white: &str, black: &str) -> Self {
+        Self::from_position(white, black, ChessBoard::create())
+    }
+}
+
+impl<T: TimeSource> Game<T> {
+    pub fn from_position(white: &str, black: &str, board: ChessBoard) -> Self {
+        Self {
+            start: board.clone(),
+            board,
+            moves: vec![],
+            hashes: vec![],
+            white: white.to_string(),
+            black: black.to_string(),
+            tags: vec![],
+            clock: None,
+            draw_offer: None,
+            result: None,
+        }
+    }
+    pub fn with_clock(mut self, clock: Clock<T>) -> Self {
+        // Adds a clock. It is started for the side to move by the first move played, or by calling
+        // start on it directly.
+        self.clock = Some(clock);
+        self
+    }
+    pub fn board(&self) -> &ChessBoard {
+        &self.board
+    }
+    pub fn starting_position(&self) -> &ChessBoard {
+        &self.start
+    }
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+    pub fn clock(&self) -> Option<&Clock<T>> {
+        self.clock.as_ref()
+    }
+    pub fn clock_mut(&mut self) -> Option<&mut Clock<T>> {
+        self.clock.as_mut()
+    }
+    pub fn player(&self, color: &Color) -> &str {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        /// Sets an event tag such as "Event", "Site" or "Date", replacing an earlier value.
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+    pub fn draw_offer(&self) -> Option<Color> {
+        /// The side whose draw offer is waiting for an answer.
+        self.draw_offer
+    }
+    pub fn play(&mut self, mv: &Move) -> Result<(), GameError> {
+        /// Plays a move for the side to move. Pressing the clock may end the game on time
+        /// instead, in which case the move is not played.
+        if self.check_time().is_some() {
+            return Err(GameError::GameOver);
+        }
+        if !self.board.legal_moves().contains(mv) {
+            return Err(GameError::IllegalMove(mv.to_uci()));
+        }
+        let color = self.board.faction_decider();
+        if let Some(clock) = &mut self.clock {
+            if clock.running().is_none() {
+                clock.start(color);
+            }
+            if clock.press().is_some() {
+                self.check_time();
+                return Err(GameError::GameOver);
+            }
+        }
+        self.hashes.push(self.board.zobrist_hash());
+        self.board.make_move(mv);
+        self.moves.push(*mv);
+        // Making a move declines the opponent's offer, while the mover's own offer stands.
+        if self.draw_offer != Some(color) {
+            self.draw_offer = None;
+        }
+        self.result = self.rules_result();
+        if self.result.is_some() {
+            if let Some(clock) = &mut self.clock {
+                clock.pause();
+            }
+        }
+        Ok(())
+    }
+    pub fn play_san(&mut self, san: &str) -> Result<Move, GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        let mv = self
+            .board
+            .parse_san(san)
+            .ok_or_else(|| GameError::IllegalMove(san.to_string()))?;
+        self.play(&mv)?;
+        Ok(mv)
+    }
+    pub fn play_uci(&mut self, uci: &str) -> Result<Move, GameError> {
+        if self.is_over() {
+            return Err(GameError::GameOver);
+        }
+        let mv = self
+            .board
+            .parse_uci_move(uci)
+            .ok_or_else(|| GameError::IllegalMove(uci.to_string()))?;
+        self.play(&mv)?;
+        Ok(mv)
+    }
+    pub fn resign(&mut self, color: Color) -> Result<(), GameError> {
+        self.finish(Outcome::Win(color.opposite()), Termination::Resignation)
+    }
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), GameError> {
+        /// Offers a draw to the opponent, who can accept or decline it. Also accepts a pending
+        /// offer from the opponent, since both sides then want a draw.
+        if self.result.is_some() {
+            return Err(GameError::GameOver);
+        }
+        match self.draw_offer {
+            Some(offered) if offered != color => self.accept_draw(color),
+            _ => {
+                self.draw_offer = Some(color);
+                Ok(())
+            }
+        }
+    }
+    pub fn accept_draw(&mut self, color: Color) -> Result<(), GameError> {
+        match self.draw_offer {
+            None => Err(GameError::NoDrawOffer),
+            Some(offered) if offered == color => Err(GameError::OwnDrawOffer),
+            Some(_) => self.finish(Outcome::Draw, Termination::DrawAgreement),
+        }
+    }
+    pub fn decline_draw(&mut self, color: Color) -> Result<(), GameError> {
+        match self.draw_offer {
+            None => Err(GameError::NoDrawOffer),
+            Some(offered) if offered == color => Err(GameError::OwnDrawOffer),
+            Some(_) => {
+                self.draw_offer = None;
+                Ok(())
+            }
+        }
+    }
+    pub fn adjudicate(&mut self, outcome: Outcome) -> Result<(), GameError> {
+        /// Ends the game with the given outcome, e.g. when an engine match stops a game that is
+        /// clearly decided.
+        self.finish(outcome, Termination::Adjudication)
+    }
+    pub fn check_time(&mut self) -> Option<GameResult> {
+        /// Ends the game if the side to move has run out of time. Apps should call this
+        /// regularly while waiting for a move.
+        if self.result.is_some() {
+            return self.result;
+        }
+        let outcome = self.clock.as_mut().and_then(|clock| {
+            let outcome = clock.outcome(&self.board)?;
+            clock.pause();
+            Some(outcome)
+        })?;
+        let outcome = match outcome {
+            TimeOutcome::Win(color) => Outcome::Win(color),
+            TimeOutcome::Draw => Outcome::Draw,
+        };
+        self.result = Some(GameResult {
+            outcome,
+            termination: Termination::Timeout,
+        });
+        self.result
+    }
+    pub fn to_pgn(&self) -> String {
+        /// Writes the game in PGN with the seven tag roster first, followed by the other tags.
+        let result = self.result.map_or("*", |result| result.outcome.to_pgn());
+        let mut tags: Vec<(&str, &str)> = vec![];
+        for (name, default) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+        ] {
+            tags.push((name, self.tag(name).unwrap_or(default)));
+        }
+        tags.push(("White", &self.white));
+        tags.push(("Black", &self.black));
+        tags.push(("Result", result));
+        let start_fen = self.start.to_fen();
+        if self.start.variant != Variant::Standard {
+            tags.push(("Variant", self.start.variant.name()));
+        } else if self.start.chess960 {
+            tags.push(("Variant", "Chess960"));
+        }
+        if start_fen != STARTING_FEN {
+            tags.push(("SetUp", "1"));
+            tags.push(("FEN", &start_fen));
+        }
+        for (name, value) in &self.tags {
+            if !tags.iter().any(|(tag, _)| tag == name) {
+                tags.push((name, value));
+            }
+        }
+
+        let mut pgn = write_tags(&tags);
+        let mut board = self.start.clone();
+        let mut tokens = vec![];
+        for (i, mv) in self.moves.iter().enumerate() {
+            let number = board.turn.div_ceil(2);
+            match board.faction_decider() {
+                Color::White => tokens.push(format!("{}.", number)),
+                Color::Black if i == 0 => tokens.push(format!("{}...", number)),
+                Color::Black => {}
+            }
+            tokens.push(board.to_san(mv));
+            board.make_move(mv);
+        }
+        tokens.push(result.to_string());
+        pgn.push_str(&wrap_movetext(&tokens));
+        pgn
+    }
+    fn finish(&mut self, outcome: Outcome, termination: Termination) -> Result<(), GameError> {
+        if self.result.is_some() {
+            return Err(GameError::GameOver);
+        }
+        self.result = Some(GameResult {
+            outcome,
+            termination,
+        });
+        self.draw_offer = None;
+        if let Some(clock) = &mut self.clock {
+            clock.pause();
+        }
+        Ok(())
+    }
+    fn rules_result(&self) -> Option<GameResult> {
+        let board = &self.board;
+        let (outcome, termination) = if let Some(outcome) = board.variant_outcome() {
+            (outcome, Termination::VariantRule)
+        } else if board.is_checkmate() {
+            (
+                Outcome::Win(board.faction_decider().opposite()),
+                Termination::Checkmate,
+            )
+        } else if board.is_stalemate() {
+            (Outcome::Draw, Termination::Stalemate)
+        } else if board.is_insufficient_material() {
+            (Outcome::Draw, Termination::InsufficientMaterial)
+        } else if board.halfmove_clock >= 100 {
+            (Outcome::Draw, Termination::FiftyMoves)
+        } else {
+            let hash = board.zobrist_hash();
+            let repetitions = self.hashes.iter().filter(|&&h| h == hash).count();
+            if repetitions < 2 {
+                return None;
+            }
+            (Outcome::Draw, Termination::Repetition)
+        };
+        Some(GameResult {
+            outcome,
+            termination,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{ManualTime, TimeControl};
+    use std::time::Duration;
+
+    #[test]
+    fn checkmate_and_pgn_test() {
+        let mut game = Game::new("Alice", "Bob");
+        game.set_tag("Event", "Fool's mate");
+        for san in ["f3", "e5", "g4"] {
+            game.play_san(san).unwrap();
+        }
+        assert_eq!(
+            game.play_san("Nd7"),
+            Err(GameError::IllegalMove("Nd7".to_string()))
+        );
+        game.play_san("Qh4#").unwrap();
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                outcome: Outcome::Win(Color::Black),
+                termination: Termination::Checkmate,
+            })
+        );
+        assert_eq!(game.play_san("a3"), Err(GameError::GameOver));
+        assert_eq!(
+            game.to_pgn(),
+            "[Event \"Fool's mate\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"Alice\"]\n[Black \"Bob\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n"
+        );
+    }
+
+    #[test]
+    fn draw_offer_test() {
+        let mut game = Game::new("Alice", "Bob");
+        assert_eq!(game.accept_draw(Color::Black), Err(GameError::NoDrawOffer));
+        game.offer_draw(Color::White).unwrap();
+        assert_eq!(game.accept_draw(Color::White), Err(GameError::OwnDrawOffer));
+        game.decline_draw(Color::Black).unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        game.play_san("e4").unwrap();
+        game.offer_draw(Color::Black).unwrap();
+        game.play_san("e5").unwrap();
+        assert_eq!(game.draw_offer(), Some(Color::Black));
+        game.play_san("Nf3").unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        game.offer_draw(Color::Black).unwrap();
+        game.accept_draw(Color::White).unwrap();
+        assert_eq!(
+            game.result().map(|result| result.termination),
+            Some(Termination::DrawAgreement)
+        );
+        assert_eq!(game.resign(Color::White), Err(GameError::GameOver));
+    }
+
+    #[test]
+    fn repetition_and_resignation_test() {
+        let mut game = Game::new("Alice", "Bob");
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1"] {
+            game.play_san(san).unwrap();
+        }
+        assert!(!game.is_over());
+        game.play_san("Ng8").unwrap();
+        assert_eq!(
+            game.result().map(|result| result.termination),
+            Some(Termination::Repetition)
+        );
+
+        let mut game = Game::new("Alice", "Bob");
+        game.resign(Color::White).unwrap();
+        assert_eq!(
+            game.result().map(|result| result.outcome),
+            Some(Outcome::Win(Color::Black))
+        );
+    }
+
+    #[test]
+    fn timeout_test() {
+        let time = ManualTime::new();
+        let clock = Clock::new(
+            TimeControl::sudden_death(Duration::from_secs(10)),
+            time.clone(),
+        );
+        let mut game = Game::from_position("Alice", "Bob", ChessBoard::create()).with_clock(clock);
+        game.play_san("e4").unwrap();
+        time.advance(Duration::from_secs(11));
+        assert_eq!(game.play_san("e5"), Err(GameError::GameOver));
+        assert_eq!(
+            game.result(),
+            Some(GameResult {
+                outcome: Outcome::Win(Color::White),
+                termination: Termination::Timeout,
+            })
+        );
+        assert_eq!(game.moves().len(), 1);
+    }
+}
@@ -1,11 +1,50 @@
 #![allow(unused)]
 
-#[derive(PartialEq, Clone, Copy)]
+pub mod annotate;
+pub mod bitbase;
+pub mod book;
+pub mod clock;
+pub mod display;
+pub mod eco;
+pub mod engine;
+pub mod eval;
+pub mod fairy;
+pub mod fen;
+pub mod game;
+pub mod mate;
+pub mod pgn;
+pub mod puzzle;
+pub mod rating;
+pub mod san;
+pub mod search;
+pub mod sprt;
+pub mod svg;
+pub mod timeman;
+pub mod tournament;
+pub mod variant;
+pub mod zobrist;
+
+pub use display::{DisplayOptions, PieceStyle};
+pub use fen::FenError;
+pub use variant::Variant;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     White,
     Black,
 }
-#[derive(Clone, Copy)]
+
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChessPiece {
     Pawn(Color),
     Rook(Color),
@@ -16,7 +55,8 @@ pub enum ChessPiece {
 }
 
 impl ChessPiece {
-    fn to_string(&self) -> &str {
+    pub fn name(&self) -> &'static str {
+        /// The English name of the piece, e.g. "Knight".
         match self {
             ChessPiece::Bishop(_) => "Bishop",
             ChessPiece::Pawn(_) => "Pawn",
@@ -36,8 +76,198 @@ impl ChessPiece {
             ChessPiece::King(x) => x,
         }
     }
+    pub fn with_color(&self, color: Color) -> ChessPiece {
+        /// The same kind of piece in the given color.
+        match self {
+            ChessPiece::Pawn(_) => ChessPiece::Pawn(color),
+            ChessPiece::Rook(_) => ChessPiece::Rook(color),
+            ChessPiece::Knight(_) => ChessPiece::Knight(color),
+            ChessPiece::Bishop(_) => ChessPiece::Bishop(color),
+            ChessPiece::Queen(_) => ChessPiece::Queen(color),
+            ChessPiece::King(_) => ChessPiece::King(color),
+        }
+    }
+    pub fn color(&self) -> Color {
+        *self.get_color()
+    }
+}
+
+/// Which castling moves are still available to each side.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+    /// The files of the rooks castling is done with, 7 and 0 in standard chess. Chess960 games
+    /// start with the same files for both sides, but set up positions may differ.
+    pub white_kingside_file: usize,
+    pub white_queenside_file: usize,
+    pub black_kingside_file: usize,
+    pub black_queenside_file: usize,
+}
+
+impl CastlingRights {
+    pub fn all() -> Self {
+        Self {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+            white_kingside_file: 7,
+            white_queenside_file: 0,
+            black_kingside_file: 7,
+            black_queenside_file: 0,
+        }
+    }
+    pub fn none() -> Self {
+        Self {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+            white_kingside_file: 7,
+            white_queenside_file: 0,
+            black_kingside_file: 7,
+            black_queenside_file: 0,
+        }
+    }
+    pub fn available(&self, color: &Color, kingside: bool) -> bool {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside,
+            (Color::White, false) => self.white_queenside,
+            (Color::Black, true) => self.black_kingside,
+            (Color::Black, false) => self.black_queenside,
+        }
+    }
+    pub fn rook_file(&self, color: &Color, kingside: bool) -> usize {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside_file,
+            (Color::White, false) => self.white_queenside_file,
+            (Color::Black, true) => self.black_kingside_file,
+            (Color::Black, false) => self.black_queenside_file,
+        }
+    }
+    pub fn grant(&mut self, color: &Color, kingside: bool, rook_file: usize) {
+        /// Allows castling on one side with the rook on the given file.
+        let (available, file) = match (color, kingside) {
+            (Color::White, true) => (&mut self.white_kingside, &mut self.white_kingside_file),
+            (Color::White, false) => (&mut self.white_queenside, &mut self.white_queenside_file),
+            (Color::Black, true) => (&mut self.black_kingside, &mut self.black_kingside_file),
+            (Color::Black, false) => (&mut self.black_queenside, &mut self.black_queenside_file),
+        };
+        *available = true;
+        *file = rook_file;
+    }
+    pub fn revoke(&mut self, color: &Color, kingside: bool) {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside = false,
+            (Color::White, false) => self.white_queenside = false,
+            (Color::Black, true) => self.black_kingside = false,
+            (Color::Black, false) => self.black_queenside = false,
+        }
+    }
+    pub(crate) fn remove_rook(&mut self, square: (usize, usize)) {
+        // A piece left or was taken on the square, so a castling rook that stood there is gone.
+        for (color, row) in [(Color::White, 7), (Color::Black, 0)] {
+            for kingside in [true, false] {
+                if square == (self.rook_file(&color, kingside), row) {
+                    self.revoke(&color, kingside);
+                }
+            }
+        }
+    }
+}
+
+/// A move from one square to another, with the piece a pawn promotes to if it reaches the last rank.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move {
+    pub source: (usize, usize),
+    pub destination: (usize, usize),
+    pub promotion: Option<ChessPiece>,
+    /// The piece put on the destination square from the pocket in crazyhouse. The source is then
+    /// the destination square as well.
+    pub dropped: Option<ChessPiece>,
+}
+
+impl Move {
+    pub fn new(source: (usize, usize), destination: (usize, usize)) -> Self {
+        Self {
+            source,
+            destination,
+            promotion: None,
+            dropped: None,
+        }
+    }
+    pub fn with_promotion(
+        source: (usize, usize),
+        destination: (usize, usize),
+        piece: ChessPiece,
+    ) -> Self {
+        Self {
+            source,
+            destination,
+            promotion: Some(piece),
+            dropped: None,
+        }
+    }
+    pub fn drop_piece(piece: ChessPiece, destination: (usize, usize)) -> Self {
+        Self {
+            source: destination,
+            destination,
+            promotion: None,
+            dropped: Some(piece),
+        }
+    }
+    pub fn to_uci(&self) -> String {
+        /// Formats the move in the long algebraic notation used by UCI, e.g. "e2e4" or "e7e8q".
+        /// Drops are written as the piece letter, "@" and the square, e.g. "N@f3".
+        if let Some(piece) = self.dropped {
+            let letter = crate::fen::piece_to_char(&piece).to_ascii_uppercase();
+            return format!("{}@{}", letter, square_name(self.destination));
+        }
+        let mut uci = format!(
+            "{}{}",
+            square_name(self.source),
+            square_name(self.destination)
+        );
+        if let Some(piece) = self.promotion {
+            uci.push(match piece {
+                ChessPiece::Queen(_) => 'q',
+                ChessPiece::Rook(_) => 'r',
+                ChessPiece::Bishop(_) => 'b',
+                ChessPiece::Knight(_) => 'n',
+                ChessPiece::Pawn(_) => 'p',
+                ChessPiece::King(_) => 'k',
+            });
+        }
+        uci
+    }
 }
-#[derive(Clone)]
+
+pub fn square_name(square: (usize, usize)) -> String {
+    /// Converts board coordinates to a square name, (0, 7) being "a1" and (7, 0) being "h8".
+    let (x, y) = square;
+    format!("{}{}", (b'a' + x as u8) as char, 8 - y)
+}
+
+pub fn parse_square(name: &str) -> Option<(usize, usize)> {
+    /// Converts a square name like "e4" to board coordinates. Returns None if the name is not a square.
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let (file, rank) = (bytes[0], bytes[1]);
+    if !(b'a'..=b'h').contains(&file) || !(b'1'..=b'8').contains(&rank) {
+        return None;
+    }
+    Some(((file - b'a') as usize, (b'8' - rank) as usize))
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChessBoard {
     pub turn: u32,
     pub board: [[Option<ChessPiece>; 8]; 8],
@@ -45,6 +275,123 @@ pub struct ChessBoard {
     pub white_king_pos: (usize, usize),
     pub removed_black: Vec<ChessPiece>,
     pub removed_white: Vec<ChessPiece>,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    /// Chess960 rules: castling moves are written as the king moving onto its own rook.
+    pub chess960: bool,
+    pub variant: Variant,
+    /// The number of checks each side has given, counted in three-check.
+    pub white_checks: u32,
+    pub black_checks: u32,
+    /// Pieces that were promoted from pawns, which go back into the pocket as pawns when captured
+    /// in crazyhouse.
+    pub promoted: [[bool; 8]; 8],
+}
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (1, -1),
+    (0, -1),
+    (-1, 1),
+    (1, 1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+];
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (-1, -2),
+    (1, -2),
+    (-1, 2),
+    (1, 2),
+    (-2, -1),
+    (-2, 1),
+    (2, -1),
+    (2, 1),
+];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+fn on_board(x: i32, y: i32) -> bool {
+    (0..8).contains(&x) && (0..8).contains(&y)
+}
+
+fn square_attacked(
+    squares: &[[Option<ChessPiece>; 8]; 8],
+    square: (usize, usize),
+    by: &Color,
+) -> bool {
+    /// Checks if any piece of the color `by` attacks the given square.
+    let (x, y) = (square.0 as i32, square.1 as i32);
+    let piece_at = |x: i32, y: i32| -> Option<ChessPiece> {
+        if on_board(x, y) {
+            squares[y as usize][x as usize]
+        } else {
+            None
+        }
+    };
+    for (dx, dy) in KNIGHT_STEPS {
+        if piece_at(x + dx, y + dy) == Some(ChessPiece::Knight(*by)) {
+            return true;
+        }
+    }
+    for (dx, dy) in KING_STEPS {
+        if piece_at(x + dx, y + dy) == Some(ChessPiece::King(*by)) {
+            return true;
+        }
+    }
+    // Pawns attack towards the opposite side, so look one row back from their point of view.
+    let pawn_row = if *by == Color::White { y + 1 } else { y - 1 };
+    for dx in [-1, 1] {
+        if piece_at(x + dx, pawn_row) == Some(ChessPiece::Pawn(*by)) {
+            return true;
+        }
+    }
+    for (directions, is_slider) in [
+        (
+            ROOK_DIRECTIONS,
+            (|piece| matches!(piece, ChessPiece::Rook(_) | ChessPiece::Queen(_)))
+                as fn(ChessPiece) -> bool,
+        ),
+        (BISHOP_DIRECTIONS, |piece| {
+            matches!(piece, ChessPiece::Bishop(_) | ChessPiece::Queen(_))
+        }),
+    ] {
+        for (dx, dy) in directions {
+            let (mut cx, mut cy) = (x + dx, y + dy);
+            while on_board(cx, cy) {
+                if let Some(piece) = squares[cy as usize][cx as usize] {
+                    if piece.get_color() == by && is_slider(piece) {
+                        return true;
+                    }
+                    break;
+                }
+                cx += dx;
+                cy += dy;
+            }
+        }
+    }
+    false
+}
+
+fn home_row(color: &Color) -> usize {
+    match color {
+        Color::White => 7,
+        Color::Black => 0,
+    }
+}
+
+pub(crate) fn find_king(
+    squares: &[[Option<ChessPiece>; 8]; 8],
+    hint: (usize, usize),
+    color: &Color,
+) -> Option<(usize, usize)> {
+    if squares[hint.1][hint.0] == Some(ChessPiece::King(*color)) {
+        return Some(hint);
+    }
+    (0..8)
+        .flat_map(|y| (0..8).map(move |x| (x, y)))
+        .find(|&(x, y)| squares[y][x] == Some(ChessPiece::King(*color)))
 }
 
 impl ChessBoard {
@@ -102,7 +449,96 @@ impl ChessBoard {
             black_king_pos: (4, 0),
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::all(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
+        }
+    }
+    pub fn empty() -> Self {
+        /// Creates a board without any pieces, white to move and no castling rights.
+        /// Useful for setting up positions piece by piece.
+        Self {
+            turn: 1,
+            board: [[None; 8]; 8],
+            white_king_pos: (4, 7),
+            black_king_pos: (4, 0),
+            removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
+            removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
+        }
+    }
+    pub fn create_chess960(index: u16) -> Self {
+        /// Creates the Chess960 starting position with the given Scharnagl number, from 0 to 959.
+        /// Number 518 is the standard starting position. Panics if the number is 960 or more.
+        assert!(index < 960, "Chess960 positions are numbered 0 to 959");
+        type Constructor = fn(Color) -> ChessPiece;
+        let mut back_row: [Option<Constructor>; 8] = [None; 8];
+        let mut n = index as usize;
+        back_row[n % 4 * 2 + 1] = Some(ChessPiece::Bishop);
+        n /= 4;
+        back_row[n % 4 * 2] = Some(ChessPiece::Bishop);
+        n /= 4;
+        let mut place = |nth: usize, piece: Constructor| {
+            let file = (0..8)
+                .filter(|file| back_row[*file].is_none())
+                .nth(nth)
+                .unwrap();
+            back_row[file] = Some(piece);
+        };
+        place(n % 6, ChessPiece::Queen);
+        n /= 6;
+        let (first, second) = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ][n];
+        // The second knight is placed after the first, so its index among the empty squares drops by one.
+        place(first, ChessPiece::Knight);
+        place(second - 1, ChessPiece::Knight);
+        place(0, ChessPiece::Rook);
+        place(0, ChessPiece::King);
+        place(0, ChessPiece::Rook);
+
+        let mut board = ChessBoard::create();
+        let mut rooks = vec![];
+        for (file, piece) in back_row.iter().enumerate() {
+            let piece = piece.unwrap();
+            board.board[0][file] = Some(piece(Color::Black));
+            board.board[7][file] = Some(piece(Color::White));
+            match piece(Color::White) {
+                ChessPiece::King(_) => {
+                    board.white_king_pos = (file, 7);
+                    board.black_king_pos = (file, 0);
+                }
+                ChessPiece::Rook(_) => rooks.push(file),
+                _ => {}
+            }
         }
+        for color in [Color::White, Color::Black] {
+            board.castling_rights.grant(&color, false, rooks[0]);
+            board.castling_rights.grant(&color, true, rooks[1]);
+        }
+        board.chess960 = true;
+        board
     }
     pub fn select_piece(
         &self,
@@ -114,32 +550,9 @@ impl ChessBoard {
         let (x, y) = location;
 
         let selected_piece = &self.board[y][x];
-        let mut return_value: Option<Vec<(usize, usize)>> = None;
-        match selected_piece {
-            Some(piece) => match piece {
-                ChessPiece::Pawn(color) => {
-                    return_value = Some(self.generate_pawn_moves(x, y, color))
-                }
-                ChessPiece::Rook(color) => {
-                    return_value = Some(self.generate_rook_moves(x, y, color))
-                }
-                ChessPiece::Knight(color) => {
-                    return_value = Some(self.generate_knight_moves(x, y, color))
-                }
-                ChessPiece::Bishop(color) => {
-                    return_value = Some(self.generate_bishop_moves(x, y, color))
-                }
-                ChessPiece::Queen(color) => {
-                    return_value = Some(self.generate_queen_moves(x, y, color))
-                }
-                ChessPiece::King(color) => {
-                    return_value = Some(self.generate_king_moves(x, y, color))
-                }
-            },
-            None => return_value = None,
-        }
+        let return_value = selected_piece.map(|piece| self.generate_moves(x, y, &piece));
         if let Some(value) = return_value {
-            if selected_piece.clone().unwrap().get_color() == faction {
+            if selected_piece.unwrap().get_color() == faction {
                 Some(value)
             } else {
                 None
@@ -151,627 +564,516 @@ impl ChessBoard {
     pub fn set_piece(&mut self, source: (usize, usize), destination: (usize, usize)) {
         /// Given the original location of the piece (source) that is to be moved and the destination
         /// it removes any pieces at destination into on of the removed_{color} vectors
-        /// and places the source piece at the destination position
-        let selected_piece = &self.board[source.1][source.0];
-        let selected_square = &self.board[destination.1][destination.0];
-
-        if let Some(piece) = selected_square {
-            match piece.get_color() {
-                Color::Black => self.removed_black.push(piece.clone()),
-                Color::White => self.removed_white.push(piece.clone()),
-            }
-        }
-        if let Some(piece) = selected_piece {
-            if let ChessPiece::King(color) = piece {
-                match color {
-                    Color::White => self.white_king_pos = destination,
-                    Color::Black => self.black_king_pos = destination,
-                }
-            }
-
-            self.board[destination.1][destination.0] = Some(selected_piece.clone().unwrap());
-            self.board[source.1][source.0] = None;
-        }
+        /// and places the source piece at the destination position.
+        /// Castling also moves the rook, en passant removes the passed pawn and pawns reaching
+        /// the last rank are promoted to queens (use make_move to pick another piece).
+        self.apply_move(&Move::new(source, destination));
+    }
+    pub fn make_move(&mut self, mv: &Move) {
+        /// Plays the move like set_piece, including the chosen promotion piece, and increases the turn.
+        self.apply_move(mv);
+        self.increase_turn();
     }
     pub fn increase_turn(&mut self) {
         self.turn += 1;
     }
     pub fn faction_decider(&self) -> Color {
-        if self.turn % 2 == 0 {
+        if self.turn.is_multiple_of(2) {
             Color::Black
         } else {
             Color::White
         }
     }
-    fn generate_king_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
-        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
-        if !((y as i32 - 1) < 0) {
-            if !((x as i32 - 1) < 0) {
-                let square = &self.board[y - 1][x - 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 1, y - 1))
+    pub fn legal_moves(&self) -> Vec<Move> {
+        /// Returns every legal move for the side whose turn it is, with one move per promotion piece.
+        /// Unlike select_piece this leaves out moves that would leave the own king in check.
+        let color = self.faction_decider();
+        let mut moves = vec![];
+        for y in 0..8 {
+            for x in 0..8 {
+                let piece = match self.board[y][x] {
+                    Some(piece) if *piece.get_color() == color => piece,
+                    _ => continue,
+                };
+                for destination in self.generate_moves(x, y, &piece) {
+                    let last_row = if color == Color::White { 0 } else { 7 };
+                    if matches!(piece, ChessPiece::Pawn(_)) && destination.1 == last_row {
+                        for promotion in [
+                            ChessPiece::Queen(color),
+                            ChessPiece::Rook(color),
+                            ChessPiece::Bishop(color),
+                            ChessPiece::Knight(color),
+                        ] {
+                            moves.push(Move::with_promotion((x, y), destination, promotion));
+                        }
+                        if self.variant == Variant::Antichess {
+                            moves.push(Move::with_promotion(
+                                (x, y),
+                                destination,
+                                ChessPiece::King(color),
+                            ));
+                        }
+                    } else {
+                        moves.push(Move::new((x, y), destination));
                     }
-                } else {
-                    moves.push((x - 1, y - 1))
                 }
             }
-            if x + 1 < 8 {
-                let square = &self.board[y - 1][x + 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 1, y - 1))
-                    }
-                } else {
-                    moves.push((x + 1, y - 1))
+        }
+        if self.variant == Variant::Crazyhouse {
+            let mut kinds: Vec<ChessPiece> = vec![];
+            for piece in self.pocket(&color) {
+                if !kinds.contains(&piece) {
+                    kinds.push(piece);
                 }
             }
-            let square = &self.board[y - 1][x];
-            if let Some(piece) = square {
-                if piece.get_color() != color {
-                    moves.push((x, y - 1))
+            for piece in kinds {
+                for y in 0..8 {
+                    // Pawns can not be dropped on the first or last rank.
+                    if matches!(piece, ChessPiece::Pawn(_)) && (y == 0 || y == 7) {
+                        continue;
+                    }
+                    for x in 0..8 {
+                        if self.board[y][x].is_none() {
+                            moves.push(Move::drop_piece(piece, (x, y)));
+                        }
+                    }
                 }
-            } else {
-                moves.push((x, y - 1))
             }
         }
-        if y + 1 < 8 {
-            if !((x as i32 - 1) < 0) {
-                let square = &self.board[y + 1][x - 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 1, y + 1))
+        self.variant.retain_legal(self, &mut moves);
+        moves
+    }
+    pub fn is_in_check(&self) -> bool {
+        /// Returns true if the king of the side whose turn it is is attacked.
+        self.variant.in_check(self, &self.faction_decider())
+    }
+    pub fn is_checkmate(&self) -> bool {
+        // Returns true if the side whose turn it is is in check and has no legal moves.
+        self.legal_moves().is_empty() && self.is_in_check()
+    }
+    pub fn is_stalemate(&self) -> bool {
+        // Returns true if the side whose turn it is is not in check but has no legal moves.
+        self.legal_moves().is_empty() && !self.is_in_check()
+    }
+    pub fn has_insufficient_material(&self, color: &Color) -> bool {
+        /// Returns true if the given side cannot possibly checkmate, e.g. when it only has its king
+        /// or a king and a single knight against a king. Never true in crazyhouse, where captured
+        /// pieces can be dropped back on the board, or in antichess, where the goal is to lose them.
+        if matches!(self.variant, Variant::Crazyhouse | Variant::Antichess) {
+            return false;
+        }
+        let mut knights = 0;
+        let mut bishop_squares = vec![];
+        let mut opponent_has_blockers = false;
+        let mut opponent_bishop_squares = vec![];
+        for (y, row) in self.board.iter().enumerate() {
+            for (x, square) in row.iter().enumerate() {
+                let piece = match square {
+                    Some(piece) => piece,
+                    None => continue,
+                };
+                if piece.get_color() == color {
+                    match piece {
+                        ChessPiece::Pawn(_) | ChessPiece::Rook(_) | ChessPiece::Queen(_) => {
+                            return false
+                        }
+                        ChessPiece::Knight(_) => knights += 1,
+                        ChessPiece::Bishop(_) => bishop_squares.push((x + y) % 2),
+                        ChessPiece::King(_) => {}
                     }
                 } else {
-                    moves.push((x - 1, y + 1))
-                }
-            }
-            if x + 1 < 8 {
-                let square = &self.board[y + 2][x + 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 1, y + 1))
+                    match piece {
+                        ChessPiece::Bishop(_) => opponent_bishop_squares.push((x + y) % 2),
+                        ChessPiece::Pawn(_) | ChessPiece::Knight(_) | ChessPiece::Rook(_) => {
+                            opponent_has_blockers = true
+                        }
+                        _ => {}
                     }
-                } else {
-                    moves.push((x + 1, y + 1))
-                }
-            }
-            let square = &self.board[y - 1][x];
-            if let Some(piece) = square {
-                if piece.get_color() != color {
-                    moves.push((x, y + 1))
                 }
-            } else {
-                moves.push((x, y + 1))
             }
         }
-        if !((x as i32 - 1) < 0) {
-            let square = &self.board[y][x - 1];
-            if let Some(piece) = square {
-                if piece.get_color() != color {
-                    moves.push((x - 1, y))
-                }
-            } else {
-                moves.push((x - 1, y))
-            }
+        if knights == 0 && bishop_squares.is_empty() {
+            return true;
         }
-
-        if x + 1 < 8 {
-            let square = &self.board[y][x + 1];
-            if let Some(piece) = square {
-                if piece.get_color() != color {
-                    moves.push((x + 1, y))
-                }
-            } else {
-                moves.push((x + 1, y))
-            }
+        if knights > 0 {
+            // A lone knight can only mate if the opponent has pieces to block its own king in.
+            return knights == 1
+                && bishop_squares.is_empty()
+                && !opponent_has_blockers
+                && opponent_bishop_squares.is_empty();
         }
-        moves
+        // Bishops on a single square color can only mate with the help of other pieces.
+        let mut all_bishops = bishop_squares.iter().chain(&opponent_bishop_squares);
+        let same_square_color = match all_bishops.next() {
+            Some(first) => all_bishops.all(|other| other == first),
+            None => true,
+        };
+        same_square_color && !opponent_has_blockers
     }
-    fn generate_queen_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
-        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y));
-                        break;
-                    }
-                    break;
-                }
+    pub fn is_insufficient_material(&self) -> bool {
+        // Returns true if neither side can checkmate, which makes the game a draw.
+        self.has_insufficient_material(&Color::White)
+            && self.has_insufficient_material(&Color::Black)
+    }
+    pub fn pocket(&self, color: &Color) -> Vec<ChessPiece> {
+        /// The pieces the given side can drop in crazyhouse. These are the pieces it captured,
+        /// which are kept in the other side's removed vector, turned into its own color.
+        let captured = match color {
+            Color::White => &self.removed_black,
+            Color::Black => &self.removed_white,
+        };
+        captured
+            .iter()
+            .map(|piece| piece.with_color(*color))
+            .collect()
+    }
+    pub fn is_castling(&self, mv: &Move) -> bool {
+        /// Returns true if the move is castling, written either as the king moving two squares or,
+        /// in Chess960, as the king moving onto its own rook.
+        self.castling_rook(mv).is_some()
+    }
+    pub fn parse_uci_move(&self, uci: &str) -> Option<Move> {
+        /// Finds the legal move matching a move in UCI notation like "e2e4" or "e7e8q".
+        self.legal_moves().into_iter().find(|mv| mv.to_uci() == uci)
+    }
+    pub(crate) fn king_attacked(&self, color: &Color) -> bool {
+        let hint = match color {
+            Color::White => self.white_king_pos,
+            Color::Black => self.black_king_pos,
+        };
+        match find_king(&self.board, hint, color) {
+            Some(king) => square_attacked(&self.board, king, &color.opposite()),
+            None => false,
+        }
+    }
+    fn castling_rook(&self, mv: &Move) -> Option<(usize, usize)> {
+        let (source, destination) = (mv.source, mv.destination);
+        let color = match self.board[source.1][source.0] {
+            Some(ChessPiece::King(color)) => color,
+            _ => return None,
+        };
+        if source.1 != destination.1 || source.1 != home_row(&color) {
+            return None;
+        }
+        if self.board[destination.1][destination.0] == Some(ChessPiece::Rook(color)) {
+            return Some(destination);
+        }
+        if self.chess960 || source.0 != 4 || source.0.abs_diff(destination.0) != 2 {
+            return None;
+        }
+        let file = self
+            .castling_rights
+            .rook_file(&color, destination.0 > source.0);
+        Some((file, source.1))
+    }
+    pub(crate) fn keeps_king_safe(&self, mv: &Move) -> bool {
+        if self.castling_rook(mv).is_some() {
+            // Castling only is generated when every square the king passes is safe.
+            return true;
+        }
+        let mut squares = self.board;
+        let piece = match mv.dropped.or(squares[mv.source.1][mv.source.0]) {
+            Some(piece) => piece,
+            None => return false,
+        };
+        let color = *piece.get_color();
+        if mv.dropped.is_none()
+            && matches!(piece, ChessPiece::Pawn(_))
+            && mv.source.0 != mv.destination.0
+            && squares[mv.destination.1][mv.destination.0].is_none()
+        {
+            squares[mv.source.1][mv.destination.0] = None;
+        }
+        if mv.dropped.is_none() {
+            squares[mv.source.1][mv.source.0] = None;
+        }
+        squares[mv.destination.1][mv.destination.0] = Some(piece);
+        let hint = match color {
+            Color::White => self.white_king_pos,
+            Color::Black => self.black_king_pos,
+        };
+        match find_king(&squares, hint, &color) {
+            Some(king) => !square_attacked(&squares, king, &color.opposite()),
+            None => true,
+        }
+    }
+    pub(crate) fn apply_move(&mut self, mv: &Move) {
+        let mover = mv
+            .dropped
+            .or(self.board[mv.source.1][mv.source.0])
+            .map(|piece| piece.color());
+        self.move_pieces(mv);
+        if let Some(mover) = mover {
+            self.count_check(&mover);
+        }
+    }
+    fn move_pieces(&mut self, mv: &Move) {
+        let (source, destination) = (mv.source, mv.destination);
+        let selected_piece = self.board[source.1][source.0];
+        let selected_square = self.board[destination.1][destination.0];
+        if let Some(piece) = mv.dropped {
+            self.drop_from_pocket(piece, destination);
+            return;
+        }
+        if let Some(rook) = self.castling_rook(mv) {
+            self.castle(source, rook);
+            return;
+        }
 
-                None => moves.push((x + counter as usize, y)),
+        if let Some(mut piece) = selected_square {
+            if self.promoted[destination.1][destination.0] {
+                piece = ChessPiece::Pawn(piece.color());
             }
-            counter += 1;
-            if (x + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y));
-                        break;
-                    }
-                    break;
-                }
-
-                None => moves.push((x - counter as usize, y)),
+            match piece.get_color() {
+                Color::Black => self.removed_black.push(piece),
+                Color::White => self.removed_white.push(piece),
             }
-            counter += 1;
-            if (x as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y][x - counter as usize];
         }
+        let mut promoted = self.promoted[source.1][source.0];
+        let mut captured = selected_square.is_some();
+        let mut piece = match selected_piece {
+            Some(piece) => piece,
+            None => return,
+        };
+        let color = *piece.get_color();
+        let mut resets_clock = selected_square.is_some();
+        let mut en_passant = None;
 
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x, y + counter as usize));
-                        break;
-                    }
-                    break;
+        match piece {
+            ChessPiece::King(_) => match color {
+                Color::White => {
+                    self.white_king_pos = destination;
+                    self.castling_rights.white_kingside = false;
+                    self.castling_rights.white_queenside = false;
                 }
-                None => moves.push((x, y + counter as usize)),
-            }
-            counter += 1;
-            if (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x, y - counter as usize));
-                        break;
-                    }
-                    break;
+                Color::Black => {
+                    self.black_king_pos = destination;
+                    self.castling_rights.black_kingside = false;
+                    self.castling_rights.black_queenside = false;
                 }
-                None => moves.push((x, y - counter as usize)),
-            }
-            counter += 1;
-            if (y as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y + counter as usize));
-                        break;
+            },
+            ChessPiece::Pawn(_) => {
+                resets_clock = true;
+                if source.0 != destination.0
+                    && selected_square.is_none()
+                    && self.en_passant == Some(destination)
+                {
+                    captured = true;
+                    if let Some(passed) = self.board[source.1][destination.0].take() {
+                        match passed.get_color() {
+                            Color::Black => self.removed_black.push(passed),
+                            Color::White => self.removed_white.push(passed),
+                        }
                     }
-                    break;
                 }
-
-                None => moves.push((x + counter as usize, y + counter as usize)),
-            }
-            counter += 1;
-            if (x + counter as usize) >= 8 || (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y + counter as usize));
-                        break;
-                    }
-                    break;
+                if source.1.abs_diff(destination.1) == 2 {
+                    en_passant = Some((source.0, (source.1 + destination.1) / 2));
                 }
-
-                None => moves.push((x - counter as usize, y + counter as usize)),
-            }
-            counter += 1;
-            if (x as i32 - counter) <= -1 || (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x - counter as usize];
-        }
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y - counter as usize));
-                        break;
-                    }
-                    break;
+                if destination.1 == 0 || destination.1 == 7 {
+                    piece = mv.promotion.unwrap_or(ChessPiece::Queen(color));
+                    promoted = self.variant == Variant::Crazyhouse;
                 }
-                None => moves.push((x + counter as usize, y - counter as usize)),
             }
+            _ => {}
+        }
+        self.castling_rights.remove_rook(source);
+        self.castling_rights.remove_rook(destination);
 
-            counter += 1;
-            if (x + counter as usize) >= 8 || (y as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y - counter as usize));
-                        break;
-                    }
-                    break;
-                }
-
-                None => moves.push((x - counter as usize, y - counter as usize)),
-            }
-            counter += 1;
-            if (y as i32 - counter) <= -1 || (x as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x - counter as usize];
+        self.board[destination.1][destination.0] = Some(piece);
+        self.board[source.1][source.0] = None;
+        self.promoted[source.1][source.0] = false;
+        self.promoted[destination.1][destination.0] = promoted;
+        if captured && self.variant == Variant::Atomic {
+            self.explode(destination);
+        }
+        self.en_passant = en_passant;
+        if resets_clock {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
         }
-        moves
     }
-    fn generate_bishop_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
-        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y + counter as usize));
-                        break;
-                    }
-                    break;
-                }
-
-                None => moves.push((x + counter as usize, y + counter as usize)),
+    fn drop_from_pocket(&mut self, piece: ChessPiece, destination: (usize, usize)) {
+        let color = piece.color();
+        let pocket = match color {
+            Color::White => &mut self.removed_black,
+            Color::Black => &mut self.removed_white,
+        };
+        let captured = piece.with_color(color.opposite());
+        if let Some(index) = pocket.iter().position(|other| *other == captured) {
+            pocket.remove(index);
+            self.board[destination.1][destination.0] = Some(piece);
+            self.en_passant = None;
+            self.halfmove_clock += 1;
+        }
+    }
+    fn castle(&mut self, king: (usize, usize), rook: (usize, usize)) {
+        let row = king.1;
+        let (king_file, rook_file) = if rook.0 > king.0 { (6, 5) } else { (2, 3) };
+        let king_piece = self.board[row][king.0].take();
+        let rook_piece = self.board[row][rook.0].take();
+        self.board[row][king_file] = king_piece;
+        self.board[row][rook_file] = rook_piece;
+        if row == 7 {
+            self.white_king_pos = (king_file, row);
+            self.castling_rights.white_kingside = false;
+            self.castling_rights.white_queenside = false;
+        } else {
+            self.black_king_pos = (king_file, row);
+            self.castling_rights.black_kingside = false;
+            self.castling_rights.black_queenside = false;
+        }
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+    }
+    fn generate_moves(&self, x: usize, y: usize, piece: &ChessPiece) -> Vec<(usize, usize)> {
+        match piece {
+            ChessPiece::Pawn(color) => self.generate_pawn_moves(x, y, color),
+            ChessPiece::Rook(color) => self.generate_rook_moves(x, y, color),
+            ChessPiece::Knight(color) => self.generate_knight_moves(x, y, color),
+            ChessPiece::Bishop(color) => self.generate_bishop_moves(x, y, color),
+            ChessPiece::Queen(color) => self.generate_queen_moves(x, y, color),
+            ChessPiece::King(color) => self.generate_king_moves(x, y, color),
+        }
+    }
+    fn generate_steps(
+        &self,
+        x: usize,
+        y: usize,
+        color: &Color,
+        steps: &[(i32, i32)],
+        moves: &mut Vec<(usize, usize)>,
+    ) {
+        for (dx, dy) in steps {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            if !on_board(nx, ny) {
+                continue;
             }
-            counter += 1;
-            if (x + counter as usize) >= 8 || (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y + counter as usize));
-                        break;
-                    }
-                    break;
+            let square = &self.board[ny as usize][nx as usize];
+            if let Some(piece) = square {
+                if piece.get_color() != color {
+                    moves.push((nx as usize, ny as usize))
                 }
-
-                None => moves.push((x - counter as usize, y + counter as usize)),
+            } else {
+                moves.push((nx as usize, ny as usize))
             }
-            counter += 1;
-            if (x as i32 - counter) <= -1 || (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x - counter as usize];
         }
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y - counter as usize));
-                        break;
-                    }
-                    break;
-                }
-                None => moves.push((x + counter as usize, y - counter as usize)),
-            }
-
-            counter += 1;
-            if (x + counter as usize) >= 8 || (y as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y - counter as usize));
+    }
+    fn generate_slides(
+        &self,
+        x: usize,
+        y: usize,
+        color: &Color,
+        directions: &[(i32, i32)],
+        moves: &mut Vec<(usize, usize)>,
+    ) {
+        for (dx, dy) in directions {
+            let (mut nx, mut ny) = (x as i32 + dx, y as i32 + dy);
+            while on_board(nx, ny) {
+                match &self.board[ny as usize][nx as usize] {
+                    Some(piece) => {
+                        if piece.get_color() != color {
+                            moves.push((nx as usize, ny as usize));
+                        }
                         break;
                     }
-                    break;
+                    None => moves.push((nx as usize, ny as usize)),
                 }
-
-                None => moves.push((x - counter as usize, y - counter as usize)),
+                nx += dx;
+                ny += dy;
             }
-            counter += 1;
-            if (y as i32 - counter) <= -1 || (x as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x - counter as usize];
         }
-        moves
     }
-    fn generate_knight_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
+    fn generate_king_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
         let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
+        self.generate_steps(x, y, color, &KING_STEPS, &mut moves);
 
-        if !((y as i32 - 2) < 0) {
-            if !((x as i32 - 1) < 0) {
-                let square = &self.board[y - 2][x - 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 1, y - 2))
-                    }
-                } else {
-                    moves.push((x - 1, y - 2))
-                }
-            }
-            if x + 1 < 8 {
-                let square = &self.board[y - 2][x + 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 1, y - 2))
-                    }
-                } else {
-                    moves.push((x + 1, y - 2))
-                }
-            }
-        }
-        if y + 2 < 8 {
-            if !((x as i32 - 1) < 0) {
-                let square = &self.board[y + 2][x - 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 1, y + 2))
-                    }
-                } else {
-                    moves.push((x - 1, y + 2))
-                }
-            }
-            if x + 1 < 8 {
-                let square = &self.board[y + 2][x + 1];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 1, y + 2))
-                    }
-                } else {
-                    moves.push((x + 1, y + 2))
-                }
-            }
+        let home_row = home_row(color);
+        if y != home_row || !(self.chess960 || x == 4) {
+            return moves;
         }
-        if !((x as i32 - 2) < 0) {
-            if !((y as i32 - 1) < 0) {
-                let square = &self.board[y - 1][x - 2];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 2, y - 1))
-                    }
-                } else {
-                    moves.push((x - 2, y - 1))
-                }
+        for (kingside, king_to, rook_to) in [(true, 6, 5), (false, 2, 3)] {
+            let rook_file = self.castling_rights.rook_file(color, kingside);
+            if !self.castling_rights.available(color, kingside)
+                || self.board[home_row][rook_file] != Some(ChessPiece::Rook(*color))
+                || (rook_file > x) != (king_to > 3)
+            {
+                continue;
             }
-            if y + 1 < 8 {
-                let square = &self.board[y + 2][x - 2];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x - 2, y + 1))
-                    }
-                } else {
-                    moves.push((x - 2, y + 1))
-                }
+            // Every square the king and rook cross or land on has to be empty apart from the two
+            // of them, and the king may not pass through or land on an attacked square. The rook
+            // is lifted first, since it may be blocking an attack along the back row.
+            let mut squares = self.board;
+            squares[home_row][x] = None;
+            squares[home_row][rook_file] = None;
+            let low = x.min(rook_file).min(king_to).min(rook_to);
+            let high = x.max(rook_file).max(king_to).max(rook_to);
+            if (low..=high).any(|file| squares[home_row][file].is_some()) {
+                continue;
             }
-        }
-
-        if x + 2 < 8 {
-            if !((y as i32 - 1) < 0) {
-                let square = &self.board[y - 1][x + 2];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 2, y - 1))
-                    }
-                } else {
-                    moves.push((x + 2, y - 1))
-                }
-            }
-            if y + 1 < 8 {
-                let square = &self.board[y + 1][x + 2];
-                if let Some(piece) = square {
-                    if piece.get_color() != color {
-                        moves.push((x + 2, y + 1))
-                    }
-                } else {
-                    moves.push((x + 2, y + 1))
-                }
+            let enemy = color.opposite();
+            if (x.min(king_to)..=x.max(king_to))
+                .any(|file| square_attacked(&squares, (file, home_row), &enemy))
+            {
+                continue;
             }
+            moves.push(if self.chess960 {
+                (rook_file, home_row)
+            } else {
+                (king_to, home_row)
+            });
         }
-
+        moves
+    }
+    fn generate_queen_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
+        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
+        self.generate_slides(x, y, color, &ROOK_DIRECTIONS, &mut moves);
+        self.generate_slides(x, y, color, &BISHOP_DIRECTIONS, &mut moves);
+        moves
+    }
+    fn generate_bishop_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
+        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
+        self.generate_slides(x, y, color, &BISHOP_DIRECTIONS, &mut moves);
+        moves
+    }
+    fn generate_knight_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
+        let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
+        self.generate_steps(x, y, color, &KNIGHT_STEPS, &mut moves);
         moves
     }
     fn generate_rook_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
         let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y][x + counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x + counter as usize, y));
-                        break;
-                    }
-                    break;
-                }
-
-                None => moves.push((x + counter as usize, y)),
-            }
-            counter += 1;
-            if (x + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y][x + counter as usize];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y][x - counter as usize];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x - counter as usize, y));
-                        break;
-                    }
-                    break;
-                }
-
-                None => moves.push((x - counter as usize, y)),
-            }
-            counter += 1;
-            if (x as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y][x - counter as usize];
-        }
-
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y + counter as usize][x];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x, y + counter as usize));
-                        break;
-                    }
-                    break;
-                }
-                None => moves.push((x, y + counter as usize)),
-            }
-            counter += 1;
-            if (y + counter as usize) >= 8 {
-                break;
-            };
-            square = &self.board[y + counter as usize][x];
-        }
-        let mut counter: i32 = 1;
-        let mut square = &self.board[y - counter as usize][x];
-
-        loop {
-            match square {
-                Some(piece) => {
-                    if piece.get_color() != color {
-                        moves.push((x, y - counter as usize));
-                        break;
-                    }
-                    break;
-                }
-                None => moves.push((x, y - counter as usize)),
-            }
-            counter += 1;
-            if (y as i32 - counter) <= -1 {
-                break;
-            };
-            square = &self.board[y - counter as usize][x];
-        }
+        self.generate_slides(x, y, color, &ROOK_DIRECTIONS, &mut moves);
         moves
     }
     fn generate_pawn_moves(&self, x: usize, y: usize, color: &Color) -> Vec<(usize, usize)> {
         let mut moves: Vec<(usize, usize)> = vec![(0, 0); 0];
 
-        if matches!(color, Color::White) {
-            if (y as i32 - 1) >= 0 {
-                let front_piece = &self.board[y - 1][x];
-                if matches!(front_piece, &Option::None) {
-                    if y == 6 || y == 1 {
-                        moves.push((x, y - 2))
-                    }
-                    moves.push((x, y - 1))
-                }
-                if (x as i32 - 1) >= 0 {
-                    let fleft_piece = &self.board[y - 1][x - 1];
-                    if matches!(fleft_piece, Some(_)) {
-                        let piece = fleft_piece.as_ref().unwrap().get_color();
-                        if let &Color::Black = piece {
-                            moves.push((x - 1, y - 1))
-                        }
-                    }
-                }
-                if x + 1 < 8 {
-                    let fright_piece = &self.board[y - 1][x + 1];
-                    if matches!(fright_piece, Some(_)) {
-                        let piece = fright_piece.as_ref().unwrap().get_color();
-                        if let &Color::Black = piece {
-                            moves.push((x + 1, y - 1))
-                        }
-                    }
-                }
+        let (forward, start_row): (i32, usize) = match color {
+            Color::White => (-1, 6),
+            Color::Black => (1, 1),
+        };
+        let front_row = y as i32 + forward;
+        if !(0..8).contains(&front_row) {
+            return moves;
+        }
+        let front_row = front_row as usize;
+        if self.board[front_row][x].is_none() {
+            let double_row = (y as i32 + 2 * forward) as usize;
+            if y == start_row && self.board[double_row][x].is_none() {
+                moves.push((x, double_row))
             }
-        } else {
-            if y + 1 < 8 {
-                let front_piece = &self.board[y + 1][x];
-                if matches!(front_piece, &Option::None) {
-                    if (y == 6 || y == 1) && !(y > 7) {
-                        moves.push((x, y + 2))
-                    }
-                    moves.push((x, y + 1))
-                }
-                if (x as i32 - 1) >= 0 {
-                    let fleft_piece = &self.board[y + 1][x - 1];
-                    if matches!(fleft_piece, Some(_)) {
-                        let piece = fleft_piece.as_ref().unwrap().get_color();
-                        if let &Color::White = piece {
-                            moves.push((x - 1, y + 1))
-                        }
-                    }
-                }
-                if x + 1 < 8 {
-                    let fright_piece = &self.board[y + 1][x + 1];
-                    if matches!(fright_piece, Some(_)) {
-                        let piece = fright_piece.as_ref().unwrap().get_color();
-                        if let &Color::White = piece {
-                            moves.push((x + 1, y + 1))
-                        }
-                    }
-                }
+            moves.push((x, front_row))
+        }
+        for side in [x as i32 - 1, x as i32 + 1] {
+            if !(0..8).contains(&side) {
+                continue;
+            }
+            let side = side as usize;
+            match &self.board[front_row][side] {
+                Some(piece) if piece.get_color() != color => moves.push((side, front_row)),
+                None if self.en_passant == Some((side, front_row)) => moves.push((side, front_row)),
+                _ => {}
             }
         }
         moves
@@ -783,6 +1085,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn board_creation_test() {
         let correct_board = [
             [
@@ -834,11 +1137,11 @@ mod tests {
         for i in 0..8 {
             for j in 0..8 {
                 let correct_state = match &correct_board[i][j] {
-                    Some(x) => x.to_string(),
+                    Some(x) => x.name(),
                     None => ".",
                 };
                 let test_state = match &test_board.board[i][j] {
-                    Some(x) => x.to_string(),
+                    Some(x) => x.name(),
                     None => ".",
                 };
                 assert_eq!(test_state, correct_state)
@@ -891,6 +1194,14 @@ mod tests {
             turn: 1,
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
         };
         let coord = board
             .select_piece((3, 3), &board.faction_decider())
@@ -958,6 +1269,14 @@ mod tests {
             turn: 2,
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
         };
         let coord = board
             .select_piece((3, 3), &board.faction_decider())
@@ -1002,6 +1321,14 @@ mod tests {
             turn: 1,
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
         };
         let correct_coord = vec![
             (5, 3),
@@ -1060,6 +1387,14 @@ mod tests {
             turn: 1,
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
         };
         let correct_coord = vec![
             (5, 3),
@@ -1089,6 +1424,14 @@ mod tests {
 
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
             board: [
                 [None, None, None, None, None, None, None, None],
                 [
@@ -1182,6 +1525,14 @@ mod tests {
 
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
             board: [
                 [None, None, None, None, None, None, None, None],
                 [None, None, None, None, None, None, None, None],
@@ -1255,6 +1606,14 @@ mod tests {
 
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
             board: [
                 [
                     None,
@@ -1324,6 +1683,14 @@ mod tests {
 
             removed_black: vec![ChessPiece::Pawn(Color::Black); 0],
             removed_white: vec![ChessPiece::Pawn(Color::White); 0],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            halfmove_clock: 0,
+            chess960: false,
+            variant: Variant::Standard,
+            white_checks: 0,
+            black_checks: 0,
+            promoted: [[false; 8]; 8],
             board: [
                 [
                     None,
@@ -1405,6 +1772,7 @@ mod tests {
         }
     }
     #[test]
+    #[allow(clippy::useless_vec)]
     fn set_piece_test() {
         let mut board = ChessBoard::create();
         board.set_piece((0, 6), (0, 4));
@@ -1425,4 +1793,133 @@ mod tests {
         board.set_piece(board.black_king_pos, (5, 4));
         assert_eq!(board.black_king_pos, (5, 4));
     }
+
+    fn perft(board: &ChessBoard, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for mv in board.legal_moves() {
+            let mut child = board.clone();
+            child.make_move(&mv);
+            nodes += perft(&child, depth - 1);
+        }
+        nodes
+    }
+
+    #[test]
+    fn legal_move_count_test() {
+        assert_eq!(perft(&ChessBoard::create(), 3), 8902);
+        let kiwipete = ChessBoard::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(perft(&kiwipete, 2), 2039);
+        let en_passant = ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&en_passant, 4), 43238);
+        let promotions =
+            ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8")
+                .unwrap();
+        assert_eq!(perft(&promotions, 2), 1486);
+    }
+
+    #[test]
+    fn special_moves_test() {
+        let mut board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(board
+            .select_piece((4, 7), &Color::White)
+            .unwrap()
+            .contains(&(6, 7)));
+        board.set_piece((4, 7), (6, 7));
+        assert_eq!(board.board[7][5], Some(ChessPiece::Rook(Color::White)));
+        assert_eq!(board.white_king_pos, (6, 7));
+        assert!(!board.castling_rights.white_queenside);
+
+        let mut board = ChessBoard::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let mv = board.parse_uci_move("e5d6").unwrap();
+        board.make_move(&mv);
+        assert_eq!(board.board[3][3], None);
+        assert_eq!(board.removed_black, vec![ChessPiece::Pawn(Color::Black)]);
+
+        let mut board = ChessBoard::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.legal_moves().len(), 9);
+        let mv = board.parse_uci_move("b7b8n").unwrap();
+        board.make_move(&mv);
+        assert_eq!(board.board[0][1], Some(ChessPiece::Knight(Color::White)));
+        assert!(!board.is_in_check());
+    }
+
+    #[test]
+    fn chess960_test() {
+        let standard = ChessBoard::create_chess960(518);
+        assert_eq!(standard.board, ChessBoard::create().board);
+        assert_eq!(
+            ChessBoard::create_chess960(0).to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        let mut back_rows: Vec<[Option<ChessPiece>; 8]> = (0..960)
+            .map(|index| ChessBoard::create_chess960(index).board[7])
+            .collect();
+        back_rows.sort_by_key(|row| format!("{:?}", row));
+        back_rows.dedup();
+        assert_eq!(back_rows.len(), 960);
+
+        // Perft counts from the published Chess960 test positions.
+        let board = ChessBoard::from_fen(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        )
+        .unwrap();
+        assert!(board.chess960);
+        assert_eq!(perft(&board, 1), 21);
+        assert_eq!(perft(&board, 2), 528);
+        assert_eq!(perft(&board, 3), 12189);
+        let board =
+            ChessBoard::from_fen("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9")
+                .unwrap();
+        assert_eq!(perft(&board, 3), 18002);
+    }
+
+    #[test]
+    fn chess960_castling_test() {
+        // The king is already on g1, so castling only moves the rook from h1 to f1.
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R5KR w HA - 0 1").unwrap();
+        let castle = Move::new((6, 7), (7, 7));
+        assert!(board.legal_moves().contains(&castle));
+        assert!(board.is_castling(&castle));
+        assert_eq!(castle.to_uci(), "g1h1");
+        board.make_move(&castle);
+        assert_eq!(board.board[7][6], Some(ChessPiece::King(Color::White)));
+        assert_eq!(board.board[7][5], Some(ChessPiece::Rook(Color::White)));
+        assert_eq!(board.board[7][7], None);
+        assert!(board.removed_white.is_empty());
+        assert!(!board.castling_rights.white_queenside);
+
+        // Long castling from b1 is blocked because the rook on a1 shields c1 from the queen.
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/qR1K4 w B - 0 1").unwrap();
+        assert!(!board.legal_moves().iter().any(|mv| board.is_castling(mv)));
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/1R1K4 w B - 0 1").unwrap();
+        assert!(board.legal_moves().contains(&Move::new((3, 7), (1, 7))));
+
+        // In Chess960 a king moving two squares without taking its rook is not castling.
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+        board.chess960 = true;
+        board.set_piece((4, 7), (6, 7));
+        assert_eq!(board.board[7][5], None);
+        assert_eq!(board.board[7][7], Some(ChessPiece::Rook(Color::White)));
+    }
+
+    #[test]
+    fn insufficient_material_test() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert!(board.has_insufficient_material(&Color::White));
+        assert!(board.is_insufficient_material());
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert!(!board.has_insufficient_material(&Color::White));
+        let board = ChessBoard::from_fen("4k3/8/2b5/8/8/8/8/4KB2 w - - 0 1").unwrap();
+        assert!(board.is_insufficient_material());
+        let board = ChessBoard::from_fen("4k2r/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+        assert!(!board.has_insufficient_material(&Color::White));
+        assert!(!board.has_insufficient_material(&Color::Black));
+        assert!(!ChessBoard::create().is_insufficient_material());
+    }
 }
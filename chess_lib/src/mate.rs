@@ -0,0 +1,210 @@
+use crate::{ChessBoard, Move};
+use std::collections::HashMap;
+
+/// What is known about a position with the attacker to move.
+#[derive(Clone, Copy, Default)]
+struct Known {
+    /// No mate in this many moves or fewer.
+    fails_up_to: u32,
+    /// A move that mates in this many moves.
+    mates: Option<(u32, Move)>,
+}
+
+/// An exact search for forced mates: every move of the attacker is tried, and a move only counts
+/// if every reply of the defender still loses. Results are kept per position, so transpositions
+/// and the deeper iterations are cheap.
+struct MateSolver {
+    known: HashMap<u64, Known>,
+}
+
+pub fn solve_mate(board: &ChessBoard, n: u32) -> Option<Vec<Move>> {
+    /// Proves that the side to move can force checkmate in at most `n` moves and returns the
+    /// shortest such mate: the attacker's moves, each followed by the defence that holds out
+    /// longest, ending with the mating move. None means there is no forced mate in `n` moves.
+    /// Only checkmate counts, so a stalemate is a defence, and draws by repetition or the fifty
+    /// move rule are ignored, as in composed problems.
+    let mut solver = MateSolver {
+        known: HashMap::new(),
+    };
+    let depth = (1..=n).find(|&depth| solver.mating_move(board, depth).is_some())?;
+    Some(solver.line(board, depth))
+}
+
+impl MateSolver {
+    fn mating_move(&mut self, board: &ChessBoard, depth: u32) -> Option<Move> {
+        // A move of the side to move that mates in at most `depth` moves.
+        let key = board.zobrist_hash();
+        let known = self.known.get(&key).copied().unwrap_or_default();
+        match known.mates {
+            Some((moves, mv)) if moves <= depth => return Some(mv),
+            _ if depth <= known.fails_up_to => return None,
+            _ => {}
+        }
+        let mut found = None;
+        for (mv, next) in candidates(board, depth == 1) {
+            if !self.escapes(&next, depth - 1) {
+                found = Some(mv);
+                break;
+            }
+        }
+        let entry = self.known.entry(key).or_default();
+        match found {
+            Some(mv) => entry.mates = Some((depth, mv)),
+            None => entry.fails_up_to = depth,
+        }
+        found
+    }
+    fn escapes(&mut self, board: &ChessBoard, depth: u32) -> bool {
+        // Whether the defender, to move, avoids being mated within `depth` more moves.
+        if depth == 0 && !board.is_in_check() {
+            return true;
+        }
+        let replies = board.legal_moves();
+        if replies.is_empty() {
+            return !board.is_in_check();
+        }
+        if depth == 0 {
+            return true;
+        }
+        replies.iter().any(|reply| {
+            let mut next = board.clone();
+            next.make_move(reply);
+            self.mating_move(&next, depth).is_none()
+        })
+    }
+    fn shortest_mate(&mut self, board: &ChessBoard, depth: u32) -> Option<(u32, Move)> {
+        let moves = (1..=depth).find(|&moves| self.mating_move(board, moves).is_some())?;
+        self.mating_move(board, moves).map(|mv| (moves, mv))
+    }
+    fn line(&mut self, board: &ChessBoard, depth: u32) -> Vec<Move> {
+        // The main line of a mate in at most `depth` moves, which must have been proven.
+        let mut board = board.clone();
+        let mut line = vec![];
+        let mut depth = depth;
+        while let Some((moves, mv)) = self.shortest_mate(&board, depth) {
+            line.push(mv);
+            board.make_move(&mv);
+            // The reply after which the mate takes longest.
+            let mut longest: Option<(u32, Move)> = None;
+            for reply in board.legal_moves() {
+                let mut next = board.clone();
+                next.make_move(&reply);
+                let remaining = self.shortest_mate(&next, moves - 1).map_or(0, |(n, _)| n);
+                if longest.is_none_or(|(most, _)| remaining > most) {
+                    longest = Some((remaining, reply));
+                }
+            }
+            match longest {
+                Some((remaining, reply)) => {
+                    line.push(reply);
+                    board.make_move(&reply);
+                    depth = remaining;
+                }
+                None => break,
+            }
+        }
+        line
+    }
+}
+
+fn candidates(board: &ChessBoard, checks_only: bool) -> Vec<(Move, ChessBoard)> {
+    // The attacker's moves with the positions they lead to, checks first and then captures, as
+    // they are the most likely to mate. A mate in one can only be a check.
+    let mut moves: Vec<(bool, bool, Move, ChessBoard)> = board
+        .legal_moves()
+        .into_iter()
+        .map(|mv| {
+            let capture = board.board[mv.destination.1][mv.destination.0].is_some();
+            let mut next = board.clone();
+            next.make_move(&mv);
+            (!next.is_in_check(), !capture, mv, next)
+        })
+        .filter(|(quiet, ..)| !(checks_only && *quiet))
+        .collect();
+    moves.sort_by_key(|(quiet, no_capture, ..)| (*quiet, *no_capture));
+    moves
+        .into_iter()
+        .map(|(_, _, mv, next)| (mv, next))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(fen: &str, n: u32) -> Option<Vec<String>> {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        let line = solve_mate(&board, n)?;
+        let mut end = board.clone();
+        for mv in &line {
+            end.make_move(mv);
+        }
+        assert!(end.is_checkmate());
+        Some(line.iter().map(Move::to_uci).collect())
+    }
+
+    #[test]
+    fn mate_in_one_test() {
+        assert_eq!(
+            solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1).unwrap(),
+            ["a1a8"]
+        );
+        // Promoting to a knight is the only mate.
+        assert_eq!(
+            solve("6br/5Ppk/7p/8/8/8/8/K7 w - - 0 1", 3).unwrap(),
+            ["f7f8n"]
+        );
+        // Castling and Rf1 both mate.
+        let line = solve("4rkr1/4p1p1/8/8/8/8/8/4K2R w K - 0 1", 2).unwrap();
+        assert!(line == ["e1g1"] || line == ["h1f1"]);
+        // Castling is the only mate: after Rf1+ the king escapes to g2, which the castled king
+        // covers.
+        let fen = "8/8/8/8/4ppp1/4pkp1/8/2N1K2R w K - 0 1";
+        let board = ChessBoard::from_fen(fen).unwrap();
+        let mates: Vec<String> = board
+            .legal_moves()
+            .iter()
+            .filter(|mv| {
+                let mut after = board.clone();
+                after.make_move(mv);
+                after.is_checkmate()
+            })
+            .map(Move::to_uci)
+            .collect();
+        assert_eq!(mates, ["e1g1"]);
+        assert_eq!(solve(fen, 2).unwrap(), ["e1g1"]);
+        // Promoting to a queen or a rook both mate.
+        let line = solve("k7/2P5/1K6/8/8/8/8/8 w - - 0 1", 1).unwrap();
+        assert!(line == ["c7c8q"] || line == ["c7c8r"]);
+    }
+
+    #[test]
+    fn mate_in_two_test() {
+        // Legall's mate: 6. Bxf7+ Ke7 7. Nd5#.
+        let mut board = ChessBoard::create();
+        for san in [
+            "e4", "e5", "Nf3", "d6", "Bc4", "Bg4", "Nc3", "g6", "Nxe5", "Bxd1",
+        ] {
+            let mv = board.parse_san(san).unwrap();
+            board.make_move(&mv);
+        }
+        assert_eq!(solve_mate(&board, 1), None);
+        let line: Vec<String> = solve_mate(&board, 2)
+            .unwrap()
+            .iter()
+            .map(Move::to_uci)
+            .collect();
+        assert_eq!(line, ["c4f7", "e8e7", "c3d5"]);
+        // Black mates by promoting: h1 is covered through the king.
+        let line = solve("8/8/8/8/8/6k1/4p3/6K1 b - - 0 1", 3).unwrap();
+        assert!(line == ["e2e1q"] || line == ["e2e1r"]);
+    }
+
+    #[test]
+    fn no_mate_test() {
+        assert_eq!(solve("8/8/8/4k3/8/8/8/K6Q w - - 0 1", 2), None);
+        assert_eq!(solve_mate(&ChessBoard::create(), 2), None);
+        // Already mated.
+        assert_eq!(solve("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", 3), None);
+    }
+}
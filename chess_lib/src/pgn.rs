@@ -0,0 +1,293 @@
+use crate::fen::FenError;
+use crate::game::Outcome;
+use crate::{ChessBoard, Color, Move, Variant};
+use std::fmt;
+
+/// Reasons the moves of a PGN game could not be replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PgnError {
+    InvalidFen(FenError),
+    UnknownVariant(String),
+    /// The move at the given ply (0 for White's first move) is not legal or not valid SAN.
+    IllegalMove {
+        ply: usize,
+        san: String,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidFen(error) => write!(f, "invalid FEN tag: {}", error),
+            PgnError::UnknownVariant(variant) => write!(f, "unknown variant '{}'", variant),
+            PgnError::IllegalMove { ply, san } => {
+                write!(f, "illegal move '{}' at ply {}", san, ply + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A game as read from a PGN file: its tags, the moves of the main line in SAN and the result.
+/// Comments, variations and numeric annotation glyphs are skipped.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    /// None for an unfinished game, written "*".
+    pub result: Option<Outcome>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn starting_position(&self) -> Result<ChessBoard, PgnError> {
+        /// The position the game starts from, taken from the FEN and Variant tags.
+        let chess960 = self
+            .tag("Variant")
+            .is_some_and(|name| name.to_lowercase().contains("960"));
+        let variant = match self.tag("Variant") {
+            Some(_) if chess960 => Variant::Standard,
+            Some(name) => Variant::from_name(name)
+                .ok_or_else(|| PgnError::UnknownVariant(name.to_string()))?,
+            None => Variant::Standard,
+        };
+        let mut board = match self.tag("FEN") {
+            Some(fen) => ChessBoard::from_fen(fen).map_err(PgnError::InvalidFen)?,
+            None => ChessBoard::create_variant(variant),
+        };
+        board.variant = variant;
+        board.chess960 |= chess960;
+        Ok(board)
+    }
+    pub fn replay(&self) -> Result<Vec<(ChessBoard, Move)>, PgnError> {
+        /// Plays through the moves, returning every move together with the position it was played
+        /// in.
+        let mut board = self.starting_position()?;
+        let mut played = Vec::with_capacity(self.moves.len());
+        for (ply, san) in self.moves.iter().enumerate() {
+            let mv = board.parse_san(san).ok_or_else(|| PgnError::IllegalMove {
+                ply,
+                san: san.clone(),
+            })?;
+            played.push((board.clone(), mv));
+            board.make_move(&mv);
+        }
+        Ok(played)
+    }
+}
+
+fn parse_result(token: &str) -> Option<Option<Outcome>> {
+    let result = match token {
+        "1-0" => Some(Outcome::Win(Color::White)),
+        "0-1" => Some(Outcome::Win(Color::Black)),
+        "1/2-1/2" => Some(Outcome::Draw),
+        "*" => None,
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn parse_tag(text: &str) -> Option<(String, String)> {
+    let (name, value) = text.trim().split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+    Some((name.to_string(), value))
+}
+
+fn add_move(game: &mut PgnGame, word: &str) {
+    // Move numbers may be glued to the move, as in "12.e4" or "12...Nf6", and annotations like
+    // "!?" to its end. Only digits ending in a dot are a move number, castling can be "0-0".
+    let after_number = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    let word = match after_number.strip_prefix('.') {
+        Some(rest) => rest.trim_start_matches('.'),
+        None => word,
+    };
+    let word = word.trim_end_matches(['!', '?']);
+    if !word.is_empty() {
+        game.moves.push(word.to_string());
+    }
+}
+
+pub(crate) fn write_tags(tags: &[(&str, &str)]) -> String {
+    /// The tag pairs of a PGN game, each on its own line, followed by the empty line before the
+    /// moves.
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    pgn.push('\n');
+    pgn
+}
+
+pub(crate) fn wrap_movetext(tokens: &[String]) -> String {
+    /// Joins movetext tokens with spaces into lines that stay under 80 characters, as PGN asks.
+    let mut text = String::new();
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + token.len() >= 80 {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+pub fn read_games(text: &str) -> Vec<PgnGame> {
+    /// Reads every game of a PGN file. A game ends at its result token or where the tags of the
+    /// next game start, so files missing results are read as well.
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut chars = text.chars().peekable();
+    let mut depth: usize = 0;
+    let mut word = String::new();
+    let flush = |game: &mut PgnGame, games: &mut Vec<PgnGame>| {
+        if !game.tags.is_empty() || !game.moves.is_empty() {
+            games.push(std::mem::take(game));
+        }
+    };
+    while let Some(c) = chars.next() {
+        let separator = c.is_whitespace() || "{;()[$".contains(c);
+        if separator && !word.is_empty() {
+            if depth == 0 {
+                match parse_result(&word) {
+                    Some(result) => {
+                        game.result = result;
+                        flush(&mut game, &mut games);
+                    }
+                    None => add_move(&mut game, &word),
+                }
+            }
+            word.clear();
+        }
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' | '%' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '$' => {
+                while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    chars.next();
+                }
+            }
+            '[' if depth == 0 => {
+                if !game.moves.is_empty() {
+                    flush(&mut game, &mut games);
+                }
+                let mut tag = String::new();
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    if c == ']' && !escaped {
+                        break;
+                    }
+                    escaped = c == '\\' && !escaped;
+                    tag.push(c);
+                }
+                if let Some(tag) = parse_tag(&tag) {
+                    game.tags.push(tag);
+                }
+            }
+            _ if separator => {}
+            _ => word.push(c),
+        }
+    }
+    if !word.is_empty() && depth == 0 {
+        match parse_result(&word) {
+            Some(result) => game.result = result,
+            None => add_move(&mut game, &word),
+        }
+    }
+    flush(&mut game, &mut games);
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn read_games_test() {
+        let text = r#"[Event "Club \"Open\""]
+[White "Anna"]
+[Black "Ben"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3.Bb5 a6; Ruy Lopez
+4. Ba4 1-0
+
+[White "Ben"]
+[Black "Anna"]
+
+1. d4 d5?! 2. c4!! *
+"#;
+        let games = read_games(text);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("Club \"Open\""));
+        assert_eq!(
+            games[0].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]
+        );
+        assert_eq!(games[0].result, Some(Outcome::Win(Color::White)));
+        assert_eq!(games[1].moves, ["d4", "d5", "c4"]);
+        assert_eq!(games[1].result, None);
+        let played = games[0].replay().unwrap();
+        assert_eq!(played.len(), 7);
+        assert_eq!(played[6].1.to_uci(), "b5a4");
+    }
+
+    #[test]
+    fn replay_errors_test() {
+        let game = &read_games("1. e4 e5 2. Ke3 1-0")[0];
+        assert_eq!(
+            game.replay(),
+            Err(PgnError::IllegalMove {
+                ply: 2,
+                san: "Ke3".to_string()
+            })
+        );
+        let mut game = Game::new("White", "Black");
+        game.play_san("f3").unwrap();
+        game.play_san("e5").unwrap();
+        game.play_san("g4").unwrap();
+        game.play_san("Qh4#").unwrap();
+        let read = &read_games(&game.to_pgn())[0];
+        assert_eq!(read.result, Some(Outcome::Win(Color::Black)));
+        assert_eq!(read.moves, ["f3", "e5", "g4", "Qh4#"]);
+    }
+
+    #[test]
+    fn castling_with_zeros_test() {
+        let game = &read_games("1. e4 e5 2. Nf3 Nf6 3. Bc4 Bc5 4.0-0 0-0 5. d3 ... *")[0];
+        assert_eq!(game.moves[6..], ["0-0", "0-0", "d3"]);
+        let played = game.replay().unwrap();
+        assert_eq!(played[6].1.to_uci(), "e1g1");
+        assert_eq!(played[7].1.to_uci(), "e8g8");
+    }
+}
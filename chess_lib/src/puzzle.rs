@@ -0,0 +1,321 @@
+use crate::engine::DEFAULT_HASH_MB;
+use crate::pgn::{PgnError, PgnGame};
+use crate::search::{Score, SearchLimits, Searcher};
+use crate::{ChessBoard, Move};
+
+/// What solving a puzzle achieves.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PuzzleKind {
+    /// Mate in this many moves.
+    Mate(u32),
+    /// A decisive material gain.
+    Material,
+}
+
+/// A position from a game where only one move wins, with the line that proves it. The solution
+/// alternates the solver's moves with the best replies and ends with a move of the solver.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Puzzle {
+    pub fen: String,
+    pub solution: Vec<Move>,
+    pub kind: PuzzleKind,
+    /// The engine's score of the position for the solver.
+    pub score: Score,
+    /// The ply of the game the position comes from, 0 for the starting position.
+    pub ply: usize,
+}
+
+pub const CSV_HEADER: &str = "fen,solution,kind,score,ply";
+
+impl Puzzle {
+    pub fn to_csv(&self) -> String {
+        /// One CSV row with the columns of CSV_HEADER. The solution is written as UCI moves
+        /// separated by spaces.
+        let solution: Vec<String> = self.solution.iter().map(Move::to_uci).collect();
+        format!(
+            "{},{},{},{},{}",
+            self.fen,
+            solution.join(" "),
+            self.kind_name(),
+            self.score.to_uci(),
+            self.ply
+        )
+    }
+    pub fn to_json(&self) -> String {
+        /// A JSON object, e.g. {"fen":"...","solution":["h5f7"],"kind":"mate","mate_in":1,...}.
+        let solution: Vec<String> = self
+            .solution
+            .iter()
+            .map(|mv| format!("\"{}\"", mv.to_uci()))
+            .collect();
+        let mate_in = match self.kind {
+            PuzzleKind::Mate(moves) => moves.to_string(),
+            PuzzleKind::Material => "null".to_string(),
+        };
+        format!(
+            "{{\"fen\":\"{}\",\"solution\":[{}],\"kind\":\"{}\",\"mate_in\":{},\"score\":\"{}\",\"ply\":{}}}",
+            self.fen,
+            solution.join(","),
+            self.kind_name(),
+            mate_in,
+            self.score.to_uci(),
+            self.ply
+        )
+    }
+    fn kind_name(&self) -> &'static str {
+        match self.kind {
+            PuzzleKind::Mate(_) => "mate",
+            PuzzleKind::Material => "material",
+        }
+    }
+}
+
+pub fn to_csv(puzzles: &[Puzzle]) -> String {
+    /// A CSV file with a header line and a row per puzzle.
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for puzzle in puzzles {
+        csv.push_str(&puzzle.to_csv());
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn to_json(puzzles: &[Puzzle]) -> String {
+    /// A JSON array with an object per puzzle, one per line.
+    let objects: Vec<String> = puzzles.iter().map(Puzzle::to_json).collect();
+    match objects.is_empty() {
+        true => "[]\n".to_string(),
+        false => format!("[\n{}\n]\n", objects.join(",\n")),
+    }
+}
+
+/// Scans games for puzzles, checking every candidate with the engine.
+pub struct PuzzleFinder {
+    searcher: Searcher,
+    limits: SearchLimits,
+    /// The centipawns the best move has to gain for the solver.
+    min_advantage: i32,
+    /// The most the second best move may gain for the best one to count as the only one.
+    max_alternative: i32,
+    /// The longest solution, in moves of the solver.
+    max_moves: usize,
+}
+
+impl PuzzleFinder {
+    pub fn new(limits: SearchLimits) -> PuzzleFinder {
+        /// A finder that searches every position with the given limits, which should include a
+        /// depth, node or time limit. Mates, and gains worth 3 pawns when the next best move is
+        /// worth at most 1, make puzzles.
+        let searcher = Searcher::new(DEFAULT_HASH_MB);
+        PuzzleFinder {
+            searcher,
+            limits,
+            min_advantage: 300,
+            max_alternative: 100,
+            max_moves: 5,
+        }
+    }
+    pub fn with_margins(mut self, min_advantage: i32, max_alternative: i32) -> PuzzleFinder {
+        // Changes how much the winning move has to be worth, and how much the second best move
+        // may be worth, in centipawns.
+        self.min_advantage = min_advantage;
+        self.max_alternative = max_alternative;
+        self
+    }
+    pub fn with_max_moves(mut self, max_moves: usize) -> PuzzleFinder {
+        self.max_moves = max_moves.max(1);
+        self
+    }
+    pub fn searcher(&mut self) -> &mut Searcher {
+        // The searcher that checks the candidates, e.g. to give it more threads.
+        &mut self.searcher
+    }
+    pub fn find(&mut self, game: &PgnGame) -> Result<Vec<Puzzle>, PgnError> {
+        /// The puzzles of a game. Positions that are part of the solution of an earlier puzzle are
+        /// skipped.
+        let played = game.replay()?;
+        let mut puzzles: Vec<Puzzle> = vec![];
+        let mut history = vec![];
+        let mut skip_until = 0;
+        // The score of the previous position for the side that has moved since.
+        let mut before = None;
+        for (ply, (board, _)) in played.iter().enumerate() {
+            let score = if ply >= skip_until {
+                let (score, puzzle) = self.examine(board, &history, ply, before);
+                if let Some(puzzle) = puzzle {
+                    skip_until = ply + puzzle.solution.len();
+                    puzzles.push(puzzle);
+                }
+                score
+            } else {
+                let lines = self.searcher.top_moves(board, &history, &self.limits, 1);
+                lines.first().map(|line| line.score)
+            };
+            before = score.map(|score| match score {
+                Score::Centipawns(cp) => Score::Centipawns(-cp),
+                Score::Mate(moves) => Score::Mate(-moves),
+            });
+            history.push(board.zobrist_hash());
+        }
+        Ok(puzzles)
+    }
+    pub fn check(
+        &mut self,
+        board: &ChessBoard,
+        history: &[u64],
+        ply: usize,
+        before: Option<Score>,
+    ) -> Option<Puzzle> {
+        /// The puzzle starting at this position, if the side to move has exactly one winning move.
+        /// Mates must stay unique up to the mating move, where any mate is accepted, and are only
+        /// puzzles if they fit in max_moves. Material puzzles end before the first move that isn't
+        /// the only good one, and must gain min_advantage over `before`, the solver's score of the
+        /// position before the opponent's last move, or over an even position if it isn't known.
+        self.examine(board, history, ply, before).1
+    }
+    fn examine(
+        &mut self,
+        board: &ChessBoard,
+        history: &[u64],
+        ply: usize,
+        before: Option<Score>,
+    ) -> (Option<Score>, Option<Puzzle>) {
+        // Also returns the score of the position, which find passes on as `before` of the next.
+        let baseline = match before {
+            Some(Score::Centipawns(cp)) => cp,
+            // A solver that was already mating can't win material, one that was getting mated
+            // gains with any winning move.
+            Some(Score::Mate(moves)) if moves > 0 => i32::MAX,
+            Some(Score::Mate(_)) => i32::MIN,
+            None => 0,
+        };
+        let fen = board.to_fen();
+        let mut board = board.clone();
+        let mut history = history.to_vec();
+        let mut solution = vec![];
+        let mut start: Option<(Score, PuzzleKind)> = None;
+        while solution.len() < self.max_moves * 2 {
+            let lines = self.searcher.top_moves(&board, &history, &self.limits, 2);
+            let Some(best) = lines.first() else {
+                break;
+            };
+            let winning = match best.score {
+                Score::Mate(moves) => moves > 0,
+                Score::Centipawns(cp) => cp >= baseline.saturating_add(self.min_advantage),
+            };
+            let kind = match (start, best.score) {
+                (Some((_, kind)), _) => kind,
+                (None, Score::Mate(moves)) if moves > 0 => PuzzleKind::Mate(moves as u32),
+                (None, _) if winning => PuzzleKind::Material,
+                (None, score) => return (Some(score), None),
+            };
+            start.get_or_insert((best.score, kind));
+            let last_move = best.score == Score::Mate(1);
+            let alternative = lines.get(1).map(|second| match second.score {
+                Score::Mate(moves) => moves > 0,
+                Score::Centipawns(cp) => {
+                    kind == PuzzleKind::Material
+                        && cp > baseline.saturating_add(self.max_alternative)
+                }
+            });
+            if !winning || (alternative == Some(true) && !last_move) {
+                match kind {
+                    PuzzleKind::Material if !solution.is_empty() => break,
+                    _ => return (start.map(|(score, _)| score), None),
+                }
+            }
+            let mv = best.pv[0];
+            solution.push(mv);
+            history.push(board.zobrist_hash());
+            board.make_move(&mv);
+            let reply = match best.pv.get(1) {
+                Some(reply) if !last_move => *reply,
+                _ => break,
+            };
+            solution.push(reply);
+            history.push(board.zobrist_hash());
+            board.make_move(&reply);
+        }
+        // The solution ends with a move of the solver.
+        if solution.len() % 2 == 0 {
+            solution.pop();
+        }
+        let Some((score, kind)) = start else {
+            return (None, None);
+        };
+        if let PuzzleKind::Mate(moves) = kind {
+            if solution.len() != moves as usize * 2 - 1 {
+                return (Some(score), None);
+            }
+        }
+        let puzzle = Puzzle {
+            fen,
+            solution,
+            kind,
+            score,
+            ply,
+        };
+        (Some(score), Some(puzzle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::read_games;
+
+    fn finder() -> PuzzleFinder {
+        PuzzleFinder::new(SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn mate_puzzle_test() {
+        let game = &read_games("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0")[0];
+        let puzzles = finder().find(game).unwrap();
+        assert_eq!(puzzles.len(), 1);
+        let puzzle = &puzzles[0];
+        assert_eq!(puzzle.ply, 6);
+        assert_eq!(puzzle.kind, PuzzleKind::Mate(1));
+        assert_eq!(
+            puzzle.fen,
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4"
+        );
+        assert_eq!(puzzle.solution[0].to_uci(), "h5f7");
+        assert!(puzzle.to_json().starts_with(&format!(
+            "{{\"fen\":\"{}\",\"solution\":[\"h5f7\"],\"kind\":\"mate\",\"mate_in\":1,",
+            puzzle.fen
+        )));
+        let csv = to_csv(&puzzles);
+        assert!(csv.starts_with("fen,solution,kind,score,ply\n"));
+        assert!(csv.ends_with(",h5f7,mate,mate 1,6\n"));
+    }
+
+    #[test]
+    fn material_puzzle_test() {
+        let pgn = "[FEN \"k7/8/8/3q4/8/8/3R4/K7 w - - 0 1\"]\n[SetUp \"1\"]\n\n1. Rxd5 Kb7 *";
+        let game = &read_games(pgn)[0];
+        let puzzles = finder().find(game).unwrap();
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].kind, PuzzleKind::Material);
+        let solution: Vec<String> = puzzles[0].solution.iter().map(Move::to_uci).collect();
+        assert_eq!(solution, ["d2d5"]);
+        assert!(to_json(&puzzles).contains("\"kind\":\"material\",\"mate_in\":null"));
+        // With two winning moves the position is no puzzle.
+        let board = ChessBoard::from_fen("k7/8/8/3q4/8/8/3R4/K2R4 w - - 0 1").unwrap();
+        assert_eq!(finder().check(&board, &[], 0, None), None);
+        // A forced recapture in a game that was already won gains nothing.
+        let pgn =
+            "[FEN \"k2q4/pp6/8/8/8/8/PP6/K2Q3R b - - 0 1\"]\n[SetUp \"1\"]\n\n1... Qxd1+ 2. Rxd1 *";
+        let game = &read_games(pgn)[0];
+        assert_eq!(finder().find(game).unwrap(), vec![]);
+        let board = ChessBoard::from_fen("k7/pp6/8/8/8/8/PP6/K2q3R w - - 0 2").unwrap();
+        assert!(finder().check(&board, &[], 1, None).is_some());
+        let before = Some(Score::Centipawns(500));
+        assert_eq!(finder().check(&board, &[], 1, before), None);
+    }
+}
@@ -0,0 +1,484 @@
+use crate::clock::TimeSource;
+use crate::game::{Game, Outcome};
+use crate::Color;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// The rating of a new player.
+const INITIAL_RATING: f64 = 1500.0;
+/// The Glicko-2 deviation and volatility of a new player.
+const INITIAL_DEVIATION: f64 = 350.0;
+const INITIAL_VOLATILITY: f64 = 0.06;
+/// Converts between the Glicko and the Glicko-2 scale.
+const GLICKO2_SCALE: f64 = 173.7178;
+/// How precisely the Glicko-2 volatility is computed.
+const VOLATILITY_EPSILON: f64 = 0.000001;
+
+/// How ratings are updated at the end of a rating period.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RatingSystem {
+    /// Elo with the given K-factor: every game moves the rating by K times the difference between
+    /// the result and the expected result.
+    Elo { k: f64 },
+    /// Glickman's Glicko-2 with the given system constant tau, which limits how fast the
+    /// volatility changes. Values between 0.3 and 1.2 are usual.
+    Glicko2 { tau: f64 },
+}
+
+impl Default for RatingSystem {
+    fn default() -> Self {
+        RatingSystem::Glicko2 { tau: 0.5 }
+    }
+}
+
+/// A player's strength. Elo only uses `rating`; the deviation and volatility stay 0.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rating {
+    pub rating: f64,
+    /// How uncertain the rating is, as a standard deviation in rating points.
+    pub deviation: f64,
+    /// How erratic the player's results are.
+    pub volatility: f64,
+}
+
+impl Rating {
+    pub fn initial(system: RatingSystem) -> Rating {
+        match system {
+            RatingSystem::Elo { .. } => Rating {
+                rating: INITIAL_RATING,
+                deviation: 0.0,
+                volatility: 0.0,
+            },
+            RatingSystem::Glicko2 { .. } => Rating {
+                rating: INITIAL_RATING,
+                deviation: INITIAL_DEVIATION,
+                volatility: INITIAL_VOLATILITY,
+            },
+        }
+    }
+}
+
+/// A player's rating at the end of a rating period.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RatingEntry {
+    pub period: u32,
+    pub rating: Rating,
+    /// The games the player played in the period.
+    pub games: u32,
+}
+
+/// A game waiting for the end of its rating period.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RatedGame {
+    pub white: String,
+    pub black: String,
+    pub outcome: Outcome,
+}
+
+/// A line of a saved rating list that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RatingError {
+    /// The line number, counting from 1.
+    InvalidLine(usize),
+}
+
+impl fmt::Display for RatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RatingError::InvalidLine(line) => write!(f, "invalid rating list line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for RatingError {}
+
+/// The players of a club or ladder with their rating histories. Games are collected during a
+/// rating period and rated together when it ends, so the order of the games within a period
+/// doesn't matter.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RatingList {
+    system: RatingSystem,
+    /// The number of the current period, starting at 1.
+    period: u32,
+    histories: BTreeMap<String, Vec<RatingEntry>>,
+    pending: Vec<RatedGame>,
+}
+
+impl RatingList {
+    pub fn new(system: RatingSystem) -> RatingList {
+        RatingList {
+            system,
+            period: 1,
+            histories: BTreeMap::new(),
+            pending: vec![],
+        }
+    }
+    pub fn system(&self) -> RatingSystem {
+        self.system
+    }
+    pub fn period(&self) -> u32 {
+        self.period
+    }
+    pub fn pending(&self) -> &[RatedGame] {
+        &self.pending
+    }
+    pub fn add_game(&mut self, white: &str, black: &str, outcome: Outcome) {
+        /// Adds a game to the current rating period. Tabs and line breaks in names are replaced
+        /// by spaces so that the list can be saved.
+        let name = |name: &str| name.replace(['\t', '\n', '\r'], " ");
+        let game = RatedGame {
+            white: name(white),
+            black: name(black),
+            outcome,
+        };
+        self.pending.push(game);
+    }
+    pub fn add_result<T: TimeSource>(&mut self, game: &Game<T>) -> bool {
+        /// Adds a finished game between its White and Black players. Returns false, adding
+        /// nothing, if the game isn't over.
+        let result = game.result();
+        if let Some(result) = result {
+            self.add_game(&game.white, &game.black, result.outcome);
+        }
+        result.is_some()
+    }
+    pub fn rating(&self, name: &str) -> Rating {
+        /// The player's current rating, or the initial one for a player without rated games.
+        let entry = self.histories.get(name).and_then(|history| history.last());
+        entry.map_or(Rating::initial(self.system), |entry| entry.rating)
+    }
+    pub fn history(&self, name: &str) -> &[RatingEntry] {
+        /// The player's ratings after each period the rating changed in, oldest first.
+        self.histories.get(name).map_or(&[], Vec::as_slice)
+    }
+    pub fn standings(&self) -> Vec<(&str, Rating)> {
+        /// All rated players, best first.
+        let mut standings: Vec<(&str, Rating)> = self
+            .histories
+            .keys()
+            .map(|name| (name.as_str(), self.rating(name)))
+            .collect();
+        standings.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        standings
+    }
+    pub fn end_period(&mut self) {
+        /// Rates the games of the current period and starts the next one. Every player's new
+        /// rating is computed from the ratings everybody had at the start of the period. In
+        /// Glicko-2 the deviation of players who didn't play grows.
+        let mut results: BTreeMap<String, Vec<(Rating, f64)>> = BTreeMap::new();
+        for game in &self.pending {
+            let white_score = match game.outcome {
+                Outcome::Win(Color::White) => 1.0,
+                Outcome::Win(Color::Black) => 0.0,
+                Outcome::Draw => 0.5,
+            };
+            let (white, black) = (self.rating(&game.white), self.rating(&game.black));
+            results
+                .entry(game.white.clone())
+                .or_default()
+                .push((black, white_score));
+            results
+                .entry(game.black.clone())
+                .or_default()
+                .push((white, 1.0 - white_score));
+        }
+        let mut names: Vec<String> = results.keys().cloned().collect();
+        if let RatingSystem::Glicko2 { .. } = self.system {
+            names.extend(self.histories.keys().cloned());
+            names.sort();
+            names.dedup();
+        }
+        let updates: Vec<(String, RatingEntry)> = names
+            .into_iter()
+            .map(|name| {
+                let games = results.get(&name).map_or(&[][..], Vec::as_slice);
+                let rating = match self.system {
+                    RatingSystem::Elo { k } => elo_update(self.rating(&name), games, k),
+                    RatingSystem::Glicko2 { tau } => glicko2_update(self.rating(&name), games, tau),
+                };
+                let entry = RatingEntry {
+                    period: self.period,
+                    rating,
+                    games: games.len() as u32,
+                };
+                (name, entry)
+            })
+            .collect();
+        for (name, entry) in updates {
+            self.histories.entry(name).or_default().push(entry);
+        }
+        self.pending.clear();
+        self.period += 1;
+    }
+    pub fn to_text(&self) -> String {
+        /// The list in a tab separated text format that keeps the full histories and the games
+        /// of the current period:
+        /// "system\telo\t<k>" or "system\tglicko2\t<tau>", then "period\t<n>", a
+        /// "rating\t<name>\t<period>\t<rating>\t<deviation>\t<volatility>\t<games>" line per entry
+        /// and a "game\t<white>\t<black>\t<result>" line per pending game.
+        let mut text = match self.system {
+            RatingSystem::Elo { k } => format!("system\telo\t{}\n", k),
+            RatingSystem::Glicko2 { tau } => format!("system\tglicko2\t{}\n", tau),
+        };
+        text.push_str(&format!("period\t{}\n", self.period));
+        for (name, history) in &self.histories {
+            for entry in history {
+                let rating = entry.rating;
+                text.push_str(&format!(
+                    "rating\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    name,
+                    entry.period,
+                    rating.rating,
+                    rating.deviation,
+                    rating.volatility,
+                    entry.games
+                ));
+            }
+        }
+        for game in &self.pending {
+            text.push_str(&format!(
+                "game\t{}\t{}\t{}\n",
+                game.white,
+                game.black,
+                game.outcome.to_pgn()
+            ));
+        }
+        text
+    }
+    pub fn from_text(text: &str) -> Result<RatingList, RatingError> {
+        /// Reads a list written by to_text. Empty lines and lines starting with '#' are skipped.
+        let mut list = RatingList::new(RatingSystem::default());
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = RatingError::InvalidLine(i + 1);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let number = |index: usize| -> Result<f64, RatingError> {
+                let value = fields.get(index).and_then(|field| field.parse().ok());
+                value
+                    .filter(|value: &f64| value.is_finite())
+                    .ok_or(error.clone())
+            };
+            match fields[..] {
+                ["system", "elo", _] => list.system = RatingSystem::Elo { k: number(2)? },
+                ["system", "glicko2", _] => list.system = RatingSystem::Glicko2 { tau: number(2)? },
+                ["period", _] => list.period = number(1)? as u32,
+                ["rating", name, ..] if fields.len() == 7 => {
+                    let entry = RatingEntry {
+                        period: number(2)? as u32,
+                        rating: Rating {
+                            rating: number(3)?,
+                            deviation: number(4)?,
+                            volatility: number(5)?,
+                        },
+                        games: number(6)? as u32,
+                    };
+                    list.histories
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(entry);
+                }
+                ["game", white, black, result] => {
+                    let outcome = match result {
+                        "1-0" => Outcome::Win(Color::White),
+                        "0-1" => Outcome::Win(Color::Black),
+                        "1/2-1/2" => Outcome::Draw,
+                        _ => return Err(error),
+                    };
+                    list.add_game(white, black, outcome);
+                }
+                _ => return Err(error),
+            }
+        }
+        Ok(list)
+    }
+    pub fn open(path: impl AsRef<Path>) -> io::Result<RatingList> {
+        let text = std::fs::read_to_string(path)?;
+        RatingList::from_text(&text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+}
+
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    /// The score a player is expected to make against the opponent under the Elo model.
+    let difference = opponent - rating;
+    1.0 / (1.0 + 10f64.powf(difference / 400.0))
+}
+
+fn elo_update(player: Rating, games: &[(Rating, f64)], k: f64) -> Rating {
+    let change: f64 = games
+        .iter()
+        .map(|(opponent, score)| k * (score - expected_score(player.rating, opponent.rating)))
+        .sum();
+    Rating {
+        rating: player.rating + change,
+        ..player
+    }
+}
+
+fn glicko2_update(player: Rating, games: &[(Rating, f64)], tau: f64) -> Rating {
+    // Step by step as in Glickman's "Example of the Glicko-2 system".
+    let mu = (player.rating - INITIAL_RATING) / GLICKO2_SCALE;
+    let phi = player.deviation / GLICKO2_SCALE;
+    let sigma = player.volatility;
+    if games.is_empty() {
+        let deviation = (phi * phi + sigma * sigma).sqrt() * GLICKO2_SCALE;
+        return Rating {
+            deviation: deviation.min(INITIAL_DEVIATION),
+            ..player
+        };
+    }
+    let g = |phi: f64| 1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt();
+    let mut variance_inverse = 0.0;
+    let mut improvement = 0.0;
+    for (opponent, score) in games {
+        let opponent_mu = (opponent.rating - INITIAL_RATING) / GLICKO2_SCALE;
+        let opponent_g = g(opponent.deviation / GLICKO2_SCALE);
+        let expected = 1.0 / (1.0 + (-opponent_g * (mu - opponent_mu)).exp());
+        variance_inverse += opponent_g * opponent_g * expected * (1.0 - expected);
+        improvement += opponent_g * (score - expected);
+    }
+    let variance = 1.0 / variance_inverse;
+    let delta = variance * improvement;
+
+    // The new volatility is the root of f, found by the Illinois algorithm.
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let denominator = phi * phi + variance + ex;
+        ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator)
+            - (x - a) / (tau * tau)
+    };
+    let mut low = a;
+    let mut high = if delta * delta > phi * phi + variance {
+        (delta * delta - phi * phi - variance).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * tau) < 0.0 {
+            k += 1.0;
+        }
+        a - k * tau
+    };
+    let (mut f_low, mut f_high) = (f(low), f(high));
+    while (high - low).abs() > VOLATILITY_EPSILON {
+        let middle = low + (low - high) * f_low / (f_high - f_low);
+        let f_middle = f(middle);
+        if f_middle * f_high <= 0.0 {
+            (low, f_low) = (high, f_high);
+        } else {
+            f_low /= 2.0;
+        }
+        (high, f_high) = (middle, f_middle);
+    }
+    let volatility = (low / 2.0).exp();
+
+    let phi_star = (phi * phi + volatility * volatility).sqrt();
+    let new_phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance).sqrt();
+    let new_mu = mu + new_phi * new_phi * improvement;
+    Rating {
+        rating: new_mu * GLICKO2_SCALE + INITIAL_RATING,
+        deviation: new_phi * GLICKO2_SCALE,
+        volatility,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn glicko2_test() {
+        // Glickman's example: a 1500 player beats a 1400 and loses to a 1550 and a 1700.
+        let player = Rating {
+            rating: 1500.0,
+            deviation: 200.0,
+            volatility: 0.06,
+        };
+        let opponent = |rating: f64, deviation: f64| Rating {
+            rating,
+            deviation,
+            volatility: 0.06,
+        };
+        let games = [
+            (opponent(1400.0, 30.0), 1.0),
+            (opponent(1550.0, 100.0), 0.0),
+            (opponent(1700.0, 300.0), 0.0),
+        ];
+        let rating = glicko2_update(player, &games, 0.5);
+        assert!(close(rating.rating, 1464.06, 0.01));
+        assert!(close(rating.deviation, 151.52, 0.01));
+        assert!(close(rating.volatility, 0.05999, 0.00001));
+        let idle = glicko2_update(player, &[], 0.5);
+        assert!(idle.deviation > 200.0 && idle.rating == 1500.0);
+    }
+
+    #[test]
+    fn elo_period_test() {
+        let mut list = RatingList::new(RatingSystem::Elo { k: 20.0 });
+        list.add_game("Ann", "Bob", Outcome::Win(Color::White));
+        list.add_game("Bob", "Cid", Outcome::Draw);
+        // Within a period the order of the games doesn't matter: both are rated from 1500.
+        list.add_game("Cid", "Ann", Outcome::Win(Color::White));
+        assert_eq!(list.rating("Ann").rating, 1500.0);
+        list.end_period();
+        assert_eq!(list.period(), 2);
+        assert!(list.pending().is_empty());
+        assert_eq!(list.rating("Ann").rating, 1500.0);
+        assert_eq!(list.rating("Bob").rating, 1490.0);
+        assert_eq!(list.rating("Cid").rating, 1510.0);
+        assert_eq!(list.standings()[0].0, "Cid");
+        assert!(close(expected_score(1600.0, 1400.0), 0.76, 0.001));
+
+        let mut game = Game::new("Ann", "Dee");
+        assert!(!list.add_result(&game));
+        game.resign(Color::Black).unwrap();
+        assert!(list.add_result(&game));
+        list.end_period();
+        // Only players who played get an entry in an Elo period.
+        assert_eq!(list.history("Ann").len(), 2);
+        assert_eq!(list.history("Bob").len(), 1);
+        assert!(list.rating("Ann").rating > 1500.0);
+        assert!(list.history("Nobody").is_empty());
+    }
+
+    #[test]
+    fn persistence_test() {
+        let mut list = RatingList::new(RatingSystem::Glicko2 { tau: 0.5 });
+        list.add_game("Ann Smith", "Bob", Outcome::Win(Color::Black));
+        list.end_period();
+        list.add_game("Cid", "Bob", Outcome::Draw);
+        list.end_period();
+        list.add_game("Ann Smith", "Cid", Outcome::Draw);
+        // Ann didn't play in the second period, but her deviation grew.
+        let history = list.history("Ann Smith");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].games, 0);
+        assert!(history[1].rating.deviation > history[0].rating.deviation);
+        assert!(list.rating("Bob").rating > 1500.0);
+
+        let text = list.to_text();
+        assert!(text.starts_with("system\tglicko2\t0.5\nperiod\t3\n"));
+        assert!(text.ends_with("game\tAnn Smith\tCid\t1/2-1/2\n"));
+        assert_eq!(RatingList::from_text(&text), Ok(list));
+        assert_eq!(
+            RatingList::from_text("system\telo\t20\nrating\tAnn\t1\n"),
+            Err(RatingError::InvalidLine(2))
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{parse_square, square_name, ChessBoard, ChessPiece, Move};
+
+fn piece_letter(piece: &ChessPiece) -> Option<char> {
+    match piece {
+        ChessPiece::Pawn(_) => None,
+        ChessPiece::Knight(_) => Some('N'),
+        ChessPiece::Bishop(_) => Some('B'),
+        ChessPiece::Rook(_) => Some('R'),
+        ChessPiece::Queen(_) => Some('Q'),
+        ChessPiece::King(_) => Some('K'),
+    }
+}
+
+impl ChessBoard {
+    pub fn to_san(&self, mv: &Move) -> String {
+        /// Writes a legal move in Standard Algebraic Notation, e.g. "Nbd7", "exd5", "e8=Q+" or "O-O#".
+        /// Crazyhouse drops are written like "N@f3".
+        let piece = match mv.dropped.or(self.board[mv.source.1][mv.source.0]) {
+            Some(piece) => piece,
+            None => return mv.to_uci(),
+        };
+        let mut san = String::new();
+        if mv.dropped.is_some() {
+            san.push_str(&mv.to_uci());
+        } else if self.is_castling(mv) {
+            san.push_str(if mv.destination.0 > mv.source.0 {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            let capture = self.board[mv.destination.1][mv.destination.0].is_some()
+                || (matches!(piece, ChessPiece::Pawn(_)) && mv.source.0 != mv.destination.0);
+            match piece_letter(&piece) {
+                Some(letter) => {
+                    san.push(letter);
+                    let rivals: Vec<Move> = self
+                        .legal_moves()
+                        .into_iter()
+                        .filter(|other| {
+                            other.destination == mv.destination
+                                && other.source != mv.source
+                                && self.board[other.source.1][other.source.0] == Some(piece)
+                        })
+                        .collect();
+                    if !rivals.is_empty() {
+                        let source = square_name(mv.source);
+                        let same_file = rivals.iter().any(|other| other.source.0 == mv.source.0);
+                        let same_rank = rivals.iter().any(|other| other.source.1 == mv.source.1);
+                        if !same_file {
+                            san.push_str(&source[..1]);
+                        } else if !same_rank {
+                            san.push_str(&source[1..]);
+                        } else {
+                            san.push_str(&source);
+                        }
+                    }
+                }
+                None => {
+                    if capture {
+                        san.push_str(&square_name(mv.source)[..1]);
+                    }
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&square_name(mv.destination));
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.extend(piece_letter(&promotion));
+            }
+        }
+
+        let mut after = self.clone();
+        after.make_move(mv);
+        if after.is_in_check() {
+            san.push(if after.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        /// Finds the legal move written in Standard Algebraic Notation. Check and annotation symbols
+        /// are optional, castling may be written with zeros and the "=" before a promotion piece may be left out.
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let moves = self.legal_moves();
+        if matches!(san, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let kingside = san.len() == 3;
+            return moves
+                .into_iter()
+                .find(|mv| self.is_castling(mv) && (mv.destination.0 > mv.source.0) == kingside);
+        }
+
+        if let Some((letter, square)) = san.split_once('@') {
+            let letter = letter.chars().next().unwrap_or('P');
+            let destination = parse_square(square)?;
+            return moves.into_iter().find(|mv| {
+                mv.destination == destination
+                    && mv
+                        .dropped
+                        .is_some_and(|piece| piece_letter(&piece).unwrap_or('P') == letter)
+            });
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '=').collect();
+        let letter = match chars.first() {
+            Some(c) if "NBRQK".contains(*c) => Some(chars.remove(0)),
+            _ => None,
+        };
+        let promotion = match chars.last() {
+            Some(c) if "NBRQK".contains(c.to_ascii_uppercase()) && chars.len() > 2 => {
+                Some(chars.pop()?.to_ascii_uppercase())
+            }
+            _ => None,
+        };
+        if chars.len() < 2 {
+            return None;
+        }
+        let destination: String = chars[chars.len() - 2..].iter().collect();
+        let destination = parse_square(&destination)?;
+        let hint = &chars[..chars.len() - 2];
+        let file_hint = hint.iter().find(|c| c.is_ascii_lowercase());
+        let rank_hint = hint.iter().find(|c| c.is_ascii_digit());
+
+        let mut candidates = moves.into_iter().filter(|mv| {
+            let piece = match self.board[mv.source.1][mv.source.0] {
+                Some(piece) => piece,
+                None => return false,
+            };
+            let source = square_name(mv.source);
+            mv.destination == destination
+                && piece_letter(&piece) == letter
+                && mv.promotion.and_then(|p| piece_letter(&p)) == promotion
+                && file_hint.is_none_or(|f| source.starts_with(*f))
+                && rank_hint.is_none_or(|r| source.ends_with(*r))
+                && !self.is_castling(mv)
+                && mv.dropped.is_none()
+        });
+        let found = candidates.next()?;
+        match candidates.next() {
+            Some(_) => None,
+            None => Some(found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn san_round_trip_test() {
+        let board = ChessBoard::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        for mv in board.legal_moves() {
+            let san = board.to_san(&mv);
+            assert_eq!(board.parse_san(&san), Some(mv), "{}", san);
+        }
+    }
+
+    #[test]
+    fn san_format_test() {
+        let board = ChessBoard::create();
+        assert_eq!(board.to_san(&board.parse_uci_move("g1f3").unwrap()), "Nf3");
+        assert_eq!(board.to_san(&board.parse_uci_move("e2e4").unwrap()), "e4");
+
+        let board = ChessBoard::from_fen("r3k2r/8/8/3p4/4P3/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.to_san(&board.parse_uci_move("e1g1").unwrap()), "O-O");
+        assert_eq!(board.to_san(&board.parse_uci_move("e4d5").unwrap()), "exd5");
+        assert_eq!(
+            board.to_san(&board.parse_uci_move("a1a8").unwrap()),
+            "Rxa8+"
+        );
+        assert_eq!(board.parse_san("0-0-0"), board.parse_uci_move("e1c1"));
+
+        let board = ChessBoard::from_fen("6k1/1P3ppp/8/8/8/8/8/R3R1K1 w - - 0 1").unwrap();
+        assert_eq!(
+            board.to_san(&board.parse_uci_move("b7b8q").unwrap()),
+            "b8=Q#"
+        );
+        assert_eq!(board.to_san(&board.parse_uci_move("a1d1").unwrap()), "Rad1");
+        assert_eq!(board.parse_san("b8N"), board.parse_uci_move("b7b8n"));
+        assert_eq!(board.parse_san("Rd1"), None);
+        assert_eq!(board.parse_san("Nf3"), None);
+    }
+}
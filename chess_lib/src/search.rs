@@ -0,0 +1,840 @@
+use crate::bitbase::probe_simple_endgame;
+use crate::clock::{TimeSource, WallClock};
+use crate::eval::{evaluate, piece_value};
+use crate::game::Outcome;
+use crate::timeman::TimeManager;
+use crate::{ChessBoard, ChessPiece, Color, Move};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub const MATE_SCORE: i32 = 30000;
+pub const MAX_DEPTH: u32 = 64;
+const INFINITY: i32 = MATE_SCORE + 1;
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+const MAX_PLY: usize = 128;
+/// Bitbase wins score below every mate, so that a found mate is still preferred.
+const BITBASE_WIN: i32 = MATE_THRESHOLD - MAX_PLY as i32;
+
+/// The result of a search as it is reported to users: centipawns, or the number of moves until
+/// mate (negative if the side to move is the one getting mated).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl Score {
+    pub fn from_internal(score: i32) -> Score {
+        /// Converts a score from the search, where mates are stored as MATE_SCORE minus the distance
+        /// in plies, to a Score.
+        if score > MATE_THRESHOLD {
+            Score::Mate((MATE_SCORE - score + 1) / 2)
+        } else if score < -MATE_THRESHOLD {
+            Score::Mate(-(MATE_SCORE + score + 1) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+    pub fn to_uci(&self) -> String {
+        match self {
+            Score::Centipawns(cp) => format!("cp {}", cp),
+            Score::Mate(moves) => format!("mate {}", moves),
+        }
+    }
+}
+
+/// Limits for a single search. A search without any limit runs to MAX_DEPTH.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    pub infinite: bool,
+    /// Only these root moves are searched, if any are given.
+    pub search_moves: Vec<Move>,
+}
+
+/// Progress reported after every completed iteration of the search, once for each line when
+/// several are searched.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchInfo {
+    pub depth: u32,
+    /// Which line this is, 1 for the best.
+    pub multi_pv: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn nps(&self) -> u64 {
+        let millis = self.time.as_millis().max(1) as u64;
+        self.nodes * 1000 / millis
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct TableEntry {
+    key: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Marks a used slot, so that an empty one never matches a key.
+const ENTRY_USED: u64 = 1 << 63;
+
+fn piece_code(piece: Option<ChessPiece>) -> u64 {
+    match piece {
+        None => 0,
+        Some(ChessPiece::Pawn(_)) => 1,
+        Some(ChessPiece::Knight(_)) => 2,
+        Some(ChessPiece::Bishop(_)) => 3,
+        Some(ChessPiece::Rook(_)) => 4,
+        Some(ChessPiece::Queen(_)) => 5,
+        Some(ChessPiece::King(_)) => 6,
+    }
+}
+
+fn piece_from_code(code: u64, color: Color) -> Option<ChessPiece> {
+    match code {
+        1 => Some(ChessPiece::Pawn(color)),
+        2 => Some(ChessPiece::Knight(color)),
+        3 => Some(ChessPiece::Bishop(color)),
+        4 => Some(ChessPiece::Rook(color)),
+        5 => Some(ChessPiece::Queen(color)),
+        6 => Some(ChessPiece::King(color)),
+        _ => None,
+    }
+}
+
+impl TableEntry {
+    fn encode(&self) -> u64 {
+        /// Packs everything but the key into one word: the move in the low 19 bits, then the
+        /// depth, the bound and the score.
+        let mv = self.best_move.map_or(0, |mv| {
+            let square = |(x, y): (usize, usize)| (y * 8 + x) as u64;
+            square(mv.source)
+                | square(mv.destination) << 6
+                | piece_code(mv.promotion) << 12
+                | piece_code(mv.dropped) << 15
+                | 1 << 18
+        });
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let score = self.score as i16 as u16 as u64;
+        ENTRY_USED | mv | (self.depth.min(255) as u64) << 19 | bound << 27 | score << 29
+    }
+    fn decode(key: u64, data: u64, color: Color) -> TableEntry {
+        /// Promotions and drops are always made by the side to move, which is passed as color.
+        let square = |bits: u64| ((bits % 8) as usize, (bits / 8 % 8) as usize);
+        let best_move = (data >> 18 & 1 == 1).then(|| Move {
+            source: square(data),
+            destination: square(data >> 6),
+            promotion: piece_from_code(data >> 12 & 7, color),
+            dropped: piece_from_code(data >> 15 & 7, color),
+        });
+        let bound = match data >> 27 & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        TableEntry {
+            key,
+            depth: (data >> 19 & 255) as u32,
+            score: (data >> 29) as u16 as i16 as i32,
+            bound,
+            best_move,
+        }
+    }
+}
+
+/// Remembers the results of earlier searches by position hash. Search threads share it without
+/// locks: each slot holds the entry and the entry xor-ed with its key, so a slot torn by two
+/// threads writing at once no longer matches any key and is ignored.
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let size = (megabytes.max(1) * 1024 * 1024) / std::mem::size_of::<[AtomicU64; 2]>();
+        Self {
+            entries: (0..size)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+    pub fn clear(&self) {
+        for [check, data] in &self.entries {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+    fn probe(&self, key: u64, color: Color) -> Option<TableEntry> {
+        let [check, data] = &self.entries[(key % self.entries.len() as u64) as usize];
+        let data = data.load(Ordering::Relaxed);
+        let matches = data & ENTRY_USED != 0 && check.load(Ordering::Relaxed) ^ data == key;
+        matches.then(|| TableEntry::decode(key, data, color))
+    }
+    fn store(&self, entry: TableEntry) {
+        let [check, data] = &self.entries[(entry.key % self.entries.len() as u64) as usize];
+        let encoded = entry.encode();
+        check.store(entry.key ^ encoded, Ordering::Relaxed);
+        data.store(encoded, Ordering::Relaxed);
+    }
+}
+
+pub struct Searcher {
+    table: TranspositionTable,
+    stop: Arc<AtomicBool>,
+    threads: usize,
+    multi_pv: usize,
+    time: Arc<dyn TimeSource + Send + Sync>,
+}
+
+impl Searcher {
+    pub fn new(hash_megabytes: usize) -> Self {
+        Self {
+            table: TranspositionTable::new(hash_megabytes),
+            stop: Arc::new(AtomicBool::new(false)),
+            threads: 1,
+            multi_pv: 1,
+            time: Arc::new(WallClock::new()),
+        }
+    }
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        /// The flag can be set from another thread to make a running search return as soon as possible.
+        /// It is not cleared by the search, so reset it before starting the next one.
+        self.stop.clone()
+    }
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.table = TranspositionTable::new(megabytes);
+    }
+    pub fn set_threads(&mut self, threads: usize) {
+        // Sets how many threads search together (Lazy SMP). Helper threads search the same
+        // position and only share what they find through the transposition table, so their
+        // timing changes the result. With a single thread, the default, searches are
+        // deterministic.
+        self.threads = threads.max(1);
+    }
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        // Sets how many of the best moves are searched with their own line and score. Every line
+        // after the first costs about as much as another search, with the moves of the better
+        // lines left out.
+        self.multi_pv = lines.max(1);
+    }
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+    pub fn set_time_source(&mut self, time: Arc<dyn TimeSource + Send + Sync>) {
+        // Sets the clock that time limits are measured with, e.g. a ManualTime in tests.
+        self.time = time;
+    }
+    pub fn clear(&mut self) {
+        /// Forgets everything learned from earlier searches, e.g. when a new game starts.
+        self.table.clear();
+    }
+    pub fn search(
+        &mut self,
+        board: &ChessBoard,
+        game_history: &[u64],
+        limits: &SearchLimits,
+        mut report: impl FnMut(&SearchInfo),
+    ) -> Option<Move> {
+        /// Searches the position with iterative deepening until a limit is reached or the stop flag is set,
+        /// calling `report` after every completed depth. `game_history` holds the hashes of the positions
+        /// played before this one, so that repetitions are scored as draws. Node counts and limits
+        /// cover all threads.
+        /// Returns None if there are no legal moves.
+        let legal_moves = board.legal_moves();
+        let first_move = *legal_moves.first()?;
+
+        let start = self.time.now();
+        let mut manager = TimeManager::new(board.faction_decider(), limits);
+        let hard_limits = manager.clone();
+        let mut history = game_history.to_vec();
+        history.push(board.zobrist_hash());
+        let root_moves: Vec<Move> = legal_moves
+            .iter()
+            .filter(|mv| limits.search_moves.contains(mv))
+            .copied()
+            .collect();
+        let first_move = root_moves.first().copied().unwrap_or(first_move);
+        let candidates = match root_moves.len() {
+            0 => legal_moves.len(),
+            count => count,
+        };
+        let multi_pv = self.multi_pv.min(candidates);
+
+        let max_depth = limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let finished = AtomicBool::new(false);
+        let total_nodes = AtomicU64::new(0);
+        let worker = || Worker {
+            table: &self.table,
+            stop: &self.stop,
+            finished: &finished,
+            total_nodes: &total_nodes,
+            root_moves: &root_moves,
+            excluded: vec![],
+            aborted: false,
+            nodes: 0,
+            counted_nodes: 0,
+            time: &*self.time,
+            start,
+            time_manager: hard_limits.clone(),
+            node_limit: limits.nodes,
+            history: history.clone(),
+            killers: [[None; 2]; MAX_PLY],
+        };
+        thread::scope(|scope| {
+            for id in 1..self.threads {
+                let mut helper = worker();
+                scope.spawn(move || helper.help(board, max_depth, id));
+            }
+            let mut main = worker();
+            let mut best_move = first_move;
+            for depth in 1..=max_depth {
+                let mut lines = vec![];
+                main.excluded.clear();
+                while lines.len() < multi_pv {
+                    let mut pv = vec![];
+                    let score = main.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+                    match pv.first() {
+                        Some(mv) if !main.stopped() => main.excluded.push(*mv),
+                        _ => break,
+                    }
+                    lines.push((score, pv));
+                }
+                if main.stopped() || lines.is_empty() {
+                    break;
+                }
+                // A later line can come out better than an earlier one at the same depth.
+                lines.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+                let (score, pv) = &lines[0];
+                let changed = pv[0] != best_move;
+                best_move = pv[0];
+                manager.iteration_done(changed && depth > 1, *score);
+                main.count_nodes();
+                for (index, (score, pv)) in lines.into_iter().enumerate() {
+                    let info = SearchInfo {
+                        depth,
+                        multi_pv: index + 1,
+                        score: Score::from_internal(score),
+                        nodes: total_nodes.load(Ordering::Relaxed),
+                        time: self.time.now().saturating_sub(start),
+                        pv,
+                    };
+                    report(&info);
+                }
+                if !manager.start_iteration(self.time.now().saturating_sub(start)) {
+                    break;
+                }
+            }
+            finished.store(true, Ordering::Relaxed);
+            Some(best_move)
+        })
+    }
+    pub fn top_moves(
+        &mut self,
+        board: &ChessBoard,
+        game_history: &[u64],
+        limits: &SearchLimits,
+        count: usize,
+    ) -> Vec<SearchInfo> {
+        /// Searches the best `count` moves and returns their lines from the deepest completed
+        /// iteration, best first. Fewer are returned if the position has fewer legal moves.
+        let multi_pv = self.multi_pv;
+        self.multi_pv = count.max(1);
+        let mut lines: Vec<SearchInfo> = vec![];
+        self.search(board, game_history, limits, |info| {
+            if info.multi_pv == 1 {
+                lines.clear();
+            }
+            lines.push(info.clone());
+        });
+        self.multi_pv = multi_pv;
+        lines
+    }
+}
+
+/// The state of one search thread.
+struct Worker<'a> {
+    table: &'a TranspositionTable,
+    stop: &'a AtomicBool,
+    /// Set when the main thread is done, to stop the helpers.
+    finished: &'a AtomicBool,
+    total_nodes: &'a AtomicU64,
+    /// The root moves the search may choose from, all legal moves if empty.
+    root_moves: &'a [Move],
+    /// Root moves left out because they already lead an earlier line of this iteration.
+    excluded: Vec<Move>,
+    aborted: bool,
+    nodes: u64,
+    /// How many of this thread's nodes have been added to total_nodes.
+    counted_nodes: u64,
+    time: &'a (dyn TimeSource + Send + Sync),
+    /// The time source's reading when the search started.
+    start: Duration,
+    /// Only used for its hard limit, which doesn't change during a search.
+    time_manager: TimeManager,
+    node_limit: Option<u64>,
+    history: Vec<u64>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+}
+
+impl Worker<'_> {
+    fn help(&mut self, board: &ChessBoard, max_depth: u32, id: usize) {
+        // Every other helper starts a depth ahead, so that the threads spread over more depths.
+        for depth in 1 + id as u32 % 2..=max_depth {
+            let mut pv = vec![];
+            self.negamax(board, depth, 0, -INFINITY, INFINITY, &mut pv);
+            if self.stopped() {
+                break;
+            }
+        }
+        self.count_nodes();
+    }
+    fn stopped(&self) -> bool {
+        self.aborted || self.stop.load(Ordering::Relaxed) || self.finished.load(Ordering::Relaxed)
+    }
+    fn count_nodes(&mut self) {
+        self.total_nodes
+            .fetch_add(self.nodes - self.counted_nodes, Ordering::Relaxed);
+        self.counted_nodes = self.nodes;
+    }
+    fn check_limits(&mut self) {
+        self.count_nodes();
+        let nodes = self.total_nodes.load(Ordering::Relaxed);
+        if self.node_limit.is_some_and(|limit| nodes >= limit)
+            || self
+                .time_manager
+                .out_of_time(self.time.now().saturating_sub(self.start))
+        {
+            self.aborted = true;
+        }
+    }
+    fn negamax(
+        &mut self,
+        board: &ChessBoard,
+        mut depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(2048) {
+            self.check_limits();
+        }
+        if self.stopped() {
+            return 0;
+        }
+        let hash = board.zobrist_hash();
+        if ply > 0 {
+            let previous = &self.history[..self.history.len() - 1];
+            if board.halfmove_clock >= 100 || previous.contains(&hash) {
+                return 0;
+            }
+            if let Some(score) = variant_score(board, ply) {
+                return score;
+            }
+            if let Some(score) = self.bitbase_score(board, ply) {
+                return score;
+            }
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board);
+        }
+
+        let in_check = board.is_in_check();
+        if in_check {
+            depth += 1;
+        }
+        if depth == 0 {
+            return self.quiesce(board, ply, alpha, beta);
+        }
+
+        let entry = self.table.probe(hash, board.faction_decider());
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = board.legal_moves();
+        if moves.is_empty() {
+            return if in_check {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+        if ply == 0 {
+            if !self.root_moves.is_empty() {
+                moves.retain(|mv| self.root_moves.contains(mv));
+            }
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        let table_move = entry.and_then(|entry| entry.best_move);
+        self.order_moves(board, &mut moves, table_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for mv in moves {
+            let mut child = board.clone();
+            child.make_move(&mv);
+            self.history.push(child.zobrist_hash());
+            let mut child_pv = vec![];
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.history.pop();
+            if self.stopped() {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend(child_pv);
+            }
+            if alpha >= beta {
+                if !is_capture(board, &mv) && self.killers[ply][0] != Some(mv) {
+                    self.killers[ply][1] = self.killers[ply][0];
+                    self.killers[ply][0] = Some(mv);
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.table.store(TableEntry {
+            key: hash,
+            depth,
+            score: score_to_table(best_score, ply),
+            bound,
+            best_move,
+        });
+        best_score
+    }
+    fn bitbase_score(&self, board: &ChessBoard, ply: usize) -> Option<i32> {
+        // Only captures and pawn moves can reach a new table, so other positions aren't probed.
+        if board.halfmove_clock != 0 {
+            return None;
+        }
+        match probe_simple_endgame(board)? {
+            Outcome::Win(color) if color == board.faction_decider() => {
+                Some(BITBASE_WIN - ply as i32)
+            }
+            Outcome::Win(_) => Some(-BITBASE_WIN + ply as i32),
+            Outcome::Draw => Some(0),
+        }
+    }
+    fn quiesce(&mut self, board: &ChessBoard, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(2048) {
+            self.check_limits();
+        }
+        if self.stopped() {
+            return 0;
+        }
+        if let Some(score) = variant_score(board, ply) {
+            return score;
+        }
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = board
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| is_capture(board, mv) || mv.promotion.is_some())
+            .collect();
+        self.order_moves(board, &mut moves, None, ply);
+        for mv in moves {
+            let mut child = board.clone();
+            child.make_move(&mv);
+            let score = -self.quiesce(&child, ply + 1, -beta, -alpha);
+            if self.stopped() {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+    fn order_moves(
+        &self,
+        board: &ChessBoard,
+        moves: &mut [Move],
+        table_move: Option<Move>,
+        ply: usize,
+    ) {
+        let killers = self.killers[ply];
+        moves.sort_by_cached_key(|mv| {
+            if Some(*mv) == table_move {
+                return i32::MIN;
+            }
+            let attacker = board.board[mv.source.1][mv.source.0].map_or(0, |p| piece_value(&p));
+            let victim = match board.board[mv.destination.1][mv.destination.0] {
+                _ if !is_capture(board, mv) => 0,
+                Some(piece) => piece_value(&piece),
+                None => 100,
+            };
+            let promotion = mv.promotion.map_or(0, |piece| piece_value(&piece));
+            if victim > 0 || promotion > 0 {
+                -(100_000 + victim * 10 - attacker / 10 + promotion)
+            } else if killers.contains(&Some(*mv)) {
+                -50_000
+            } else {
+                0
+            }
+        });
+    }
+}
+
+fn variant_score(board: &ChessBoard, ply: usize) -> Option<i32> {
+    /// The score of a position that a variant rule has decided, like a king reaching the hill.
+    let score = match board.variant_outcome()? {
+        Outcome::Draw => 0,
+        Outcome::Win(color) if color == board.faction_decider() => MATE_SCORE - ply as i32,
+        Outcome::Win(_) => -MATE_SCORE + ply as i32,
+    };
+    Some(score)
+}
+
+fn is_capture(board: &ChessBoard, mv: &Move) -> bool {
+    // A king moving onto its own rook is castling in Chess960.
+    matches!(board.board[mv.destination.1][mv.destination.0], Some(piece) if piece.color() != board.faction_decider())
+        || (matches!(
+            board.board[mv.source.1][mv.source.0],
+            Some(ChessPiece::Pawn(_))
+        ) && mv.source.0 != mv.destination.0)
+}
+
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply as i32
+    } else if score < -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualTime;
+
+    fn best_move(fen: &str, depth: u32) -> (Move, Score) {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        let mut searcher = Searcher::new(1);
+        let mut score = Score::Centipawns(0);
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+        let mv = searcher
+            .search(&board, &[], &limits, |info| score = info.score)
+            .unwrap();
+        (mv, score)
+    }
+
+    #[test]
+    fn finds_mate_in_one_test() {
+        let (mv, score) = best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(mv.to_uci(), "a1a8");
+        assert_eq!(score, Score::Mate(1));
+    }
+
+    #[test]
+    fn takes_hanging_queen_test() {
+        let (mv, _) = best_move("k7/8/8/3q4/8/8/3R4/K7 w - - 0 1", 3);
+        assert_eq!(mv.to_uci(), "d2d5");
+    }
+
+    #[test]
+    fn table_entry_test() {
+        let entry = TableEntry {
+            key: 0x1234_5678_9abc_def0,
+            depth: 12,
+            score: -MATE_SCORE + 7,
+            bound: Bound::Lower,
+            best_move: Some(Move {
+                source: (6, 1),
+                destination: (7, 0),
+                promotion: Some(ChessPiece::Knight(Color::White)),
+                dropped: None,
+            }),
+        };
+        let table = TranspositionTable::new(1);
+        table.store(entry);
+        assert_eq!(table.probe(entry.key, Color::White), Some(entry));
+        assert_eq!(table.probe(entry.key + 1, Color::White), None);
+        table.clear();
+        assert_eq!(table.probe(entry.key, Color::White), None);
+    }
+
+    #[test]
+    fn threads_test() {
+        let board = ChessBoard::from_fen("k7/8/8/3q4/8/8/3R4/K7 w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(5),
+            ..Default::default()
+        };
+        let mut searcher = Searcher::new(1);
+        searcher.set_threads(4);
+        let mut nodes = 0;
+        let mv = searcher.search(&board, &[], &limits, |info| nodes = info.nodes);
+        assert_eq!(mv.unwrap().to_uci(), "d2d5");
+        assert!(nodes > 0);
+
+        // A single thread searches the same tree every time.
+        let board = ChessBoard::create();
+        let run = || {
+            let mut infos = vec![];
+            Searcher::new(1).search(&board, &[], &limits, |info| infos.push(info.clone()));
+            infos
+                .into_iter()
+                .map(|info| (info.nodes, info.pv))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn time_limits_test() {
+        let board = ChessBoard::create();
+        let time = ManualTime::new();
+        let mut searcher = Searcher::new(1);
+        searcher.set_time_source(Arc::new(time.clone()));
+        let limits = SearchLimits {
+            white_time: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        // Each iteration takes a second of the two second soft limit, so the search stops after
+        // the second or, if that one changed its mind, the third.
+        let mut depths = vec![];
+        let mv = searcher.search(&board, &[], &limits, |info| {
+            depths.push(info.depth);
+            time.advance(Duration::from_secs(1));
+        });
+        assert!(mv.is_some());
+        assert!(depths == [1, 2] || depths == [1, 2, 3], "{:?}", depths);
+
+        // A clock that moves on with every reading reaches the hard limit inside an iteration,
+        // and the search still returns the best move of the last one it completed.
+        #[derive(Default)]
+        struct Ticking(AtomicU64);
+        impl TimeSource for Ticking {
+            fn now(&self) -> Duration {
+                Duration::from_millis(self.0.fetch_add(10, Ordering::Relaxed))
+            }
+        }
+        searcher.set_time_source(Arc::new(Ticking::default()));
+        let limits = SearchLimits {
+            movetime: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let mut depths = vec![];
+        let mv = searcher.search(&board, &[], &limits, |info| depths.push(info.depth));
+        assert!(mv.is_some());
+        assert!(!depths.is_empty() && depths.len() < MAX_DEPTH as usize);
+    }
+
+    #[test]
+    fn multi_pv_test() {
+        let board = ChessBoard::from_fen("k7/8/8/3q4/8/8/3R4/K7 w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let mut searcher = Searcher::new(1);
+        let lines = searcher.top_moves(&board, &[], &limits, 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0].to_uci(), "d2d5");
+        let first_moves: Vec<Move> = lines.iter().map(|line| line.pv[0]).collect();
+        assert!(!first_moves[1..].contains(&first_moves[0]) && first_moves[1] != first_moves[2]);
+        let scores: Vec<i32> = lines
+            .iter()
+            .map(|line| match line.score {
+                Score::Centipawns(cp) => cp,
+                Score::Mate(_) => panic!("no mate here"),
+            })
+            .collect();
+        assert!(scores[0] > scores[1] + 500 && scores[1] >= scores[2]);
+        assert_eq!(searcher.multi_pv(), 1);
+
+        // With only two legal moves there are only two lines.
+        let board = ChessBoard::from_fen("k7/8/2K5/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(searcher.top_moves(&board, &[], &limits, 5).len(), 2);
+    }
+
+    #[test]
+    fn no_moves_test() {
+        let board = ChessBoard::from_fen("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut searcher = Searcher::new(1);
+        assert_eq!(
+            searcher.search(&board, &[], &SearchLimits::default(), |_| {}),
+            None
+        );
+    }
+}
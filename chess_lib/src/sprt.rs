@@ -0,0 +1,201 @@
+use crate::game::Outcome;
+use crate::Color;
+
+/// The results of a match from the point of view of the first player.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    pub fn add(&mut self, outcome: Outcome, color: Color) {
+        /// Counts the outcome of a game the first player played with `color`.
+        let counter = match outcome {
+            Outcome::Draw => &mut self.draws,
+            Outcome::Win(winner) if winner == color => &mut self.wins,
+            Outcome::Win(_) => &mut self.losses,
+        };
+        *counter += 1;
+    }
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+    pub fn score(&self) -> f64 {
+        /// The fraction of the points won, 0.5 before any games.
+        let games = self.games();
+        if games == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / games as f64
+    }
+    fn variance(&self) -> f64 {
+        // The variance of the result of a single game.
+        let games = self.games() as f64;
+        let (wins, draws) = (self.wins as f64 / games, self.draws as f64 / games);
+        let score = self.score();
+        wins + draws / 4.0 - score * score
+    }
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        /// The Elo difference the score corresponds to and the margin of its 95% confidence
+        /// interval. None while every game has been won or lost, when it is infinite.
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = elo_from_score((score - 1.96 * deviation).max(f64::EPSILON));
+        let high = elo_from_score((score + 1.96 * deviation).min(1.0 - f64::EPSILON));
+        Some((elo_from_score(score), (high - low) / 2.0))
+    }
+    pub fn los(&self) -> f64 {
+        /// The likelihood of superiority: the probability that the first player is the stronger
+        /// one, judged from the decisive games.
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        let difference = self.wins as f64 - self.losses as f64;
+        0.5 * (1.0 + erf(difference / (2.0 * decisive).sqrt()))
+    }
+}
+
+pub fn elo_from_score(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// What a sequential probability ratio test has concluded so far.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SprtResult {
+    Continue,
+    /// The difference is elo0 rather than elo1: a change being tested failed.
+    AcceptH0,
+    /// The difference is elo1 rather than elo0: a change being tested passed.
+    AcceptH1,
+}
+
+/// A sequential probability ratio test of whether the first player is elo0 or elo1 Elo stronger
+/// than the second, stopping the match as soon as the results are clear enough for the error
+/// rates alpha (false positives) and beta (false negatives).
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    pub fn bounds(&self) -> (f64, f64) {
+        /// The log-likelihood ratios at which H0 and H1 are accepted.
+        let lower = (self.beta / (1.0 - self.alpha)).ln();
+        let upper = ((1.0 - self.beta) / self.alpha).ln();
+        (lower, upper)
+    }
+    pub fn llr(&self, results: &MatchScore) -> f64 {
+        /// The log-likelihood ratio of H1 over H0 for the results, in the usual normal
+        /// approximation of the trinomial model. 0 until both a win and a loss have been seen.
+        if results.wins == 0 || results.losses == 0 {
+            return 0.0;
+        }
+        let (score0, score1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        let variance = results.variance() / results.games() as f64;
+        (score1 - score0) * (2.0 * results.score() - score0 - score1) / (2.0 * variance)
+    }
+    pub fn result(&self, results: &MatchScore) -> SprtResult {
+        let llr = self.llr(results);
+        let (lower, upper) = self.bounds();
+        match llr {
+            _ if llr >= upper => SprtResult::AcceptH1,
+            _ if llr <= lower => SprtResult::AcceptH0,
+            _ => SprtResult::Continue,
+        }
+    }
+}
+
+fn erf(x: f64) -> f64 {
+    // Abramowitz and Stegun 7.1.26, accurate to about 1e-7.
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    value.copysign(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn elo_test() {
+        let mut results = MatchScore::default();
+        assert_eq!(results.elo(), None);
+        results.add(Outcome::Win(Color::Black), Color::Black);
+        results.add(Outcome::Win(Color::White), Color::Black);
+        results.add(Outcome::Draw, Color::White);
+        assert_eq!((results.wins, results.draws, results.losses), (1, 1, 1));
+        assert!(close(results.elo().unwrap().0, 0.0));
+        assert!(close(results.los(), 0.5));
+        let results = MatchScore {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        let (elo, margin) = results.elo().unwrap();
+        assert!(close(elo, 147.19));
+        assert!(margin > 50.0 && margin < 100.0);
+        assert!(results.los() > 0.99);
+        assert!(close(score_from_elo(elo_from_score(0.3)), 0.3));
+        assert!(close(erf(0.5), 0.5205));
+    }
+
+    #[test]
+    fn sprt_test() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!(close(lower, -2.944) && close(upper, 2.944));
+        let even = MatchScore {
+            wins: 100,
+            draws: 200,
+            losses: 100,
+        };
+        assert!(sprt.llr(&even) < 0.0);
+        assert_eq!(sprt.result(&even), SprtResult::Continue);
+        let worse = MatchScore {
+            wins: 200,
+            draws: 400,
+            losses: 400,
+        };
+        assert_eq!(sprt.result(&worse), SprtResult::AcceptH0);
+        let better = MatchScore {
+            wins: 450,
+            draws: 400,
+            losses: 300,
+        };
+        assert_eq!(sprt.result(&better), SprtResult::AcceptH1);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::display::figurine;
+use crate::{ChessBoard, Color};
+
+/// An arrow from one square to another, e.g. to show a move in a report.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arrow {
+    pub source: (usize, usize),
+    pub destination: (usize, usize),
+    pub color: String,
+}
+
+/// Options for render_svg. Colors are any SVG color value, like "#f0d9b5" or "red".
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SvgOptions {
+    pub square_size: u32,
+    pub coordinates: bool,
+    /// The side shown at the bottom of the board.
+    pub perspective: Color,
+    pub light_color: String,
+    pub dark_color: String,
+    /// Highlights and arrows on squares off the board are left out.
+    pub highlights: Vec<((usize, usize), String)>,
+    pub arrows: Vec<Arrow>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            square_size: 45,
+            coordinates: true,
+            perspective: Color::White,
+            light_color: "#f0d9b5".to_string(),
+            dark_color: "#b58863".to_string(),
+            highlights: vec![],
+            arrows: vec![],
+        }
+    }
+}
+
+pub fn render_svg(board: &ChessBoard, options: &SvgOptions) -> String {
+    /// Draws the board as a standalone SVG image, with highlighted squares and arrows on top of the pieces.
+    let size = options.square_size as f64;
+    let margin = if options.coordinates { size / 2.0 } else { 0.0 };
+    let total = size * 8.0 + margin * 2.0;
+    // Top left corner of a square in the image, taking the perspective into account.
+    let corner = |square: (usize, usize)| {
+        let (column, row) = match options.perspective {
+            Color::White => (square.0, square.1),
+            Color::Black => (7 - square.0, 7 - square.1),
+        };
+        (margin + column as f64 * size, margin + row as f64 * size)
+    };
+    let center = |square: (usize, usize)| {
+        let (x, y) = corner(square);
+        (x + size / 2.0, y + size / 2.0)
+    };
+    let on_board = |square: &(usize, usize)| square.0 < 8 && square.1 < 8;
+    let arrows: Vec<&Arrow> = options
+        .arrows
+        .iter()
+        .filter(|arrow| on_board(&arrow.source) && on_board(&arrow.destination))
+        .collect();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total}\" height=\"{total}\" viewBox=\"0 0 {total} {total}\">\n"
+    );
+    svg.push_str("<defs>\n");
+    for (i, arrow) in arrows.iter().enumerate() {
+        svg.push_str(&format!(
+            "<marker id=\"arrowhead{i}\" markerWidth=\"4\" markerHeight=\"4\" refX=\"2\" refY=\"2\" orient=\"auto\"><path d=\"M0,0 L4,2 L0,4 z\" fill=\"{}\"/></marker>\n",
+            escape(&arrow.color)
+        ));
+    }
+    svg.push_str("</defs>\n");
+    if options.coordinates {
+        svg.push_str(&format!(
+            "<rect x=\"0\" y=\"0\" width=\"{total}\" height=\"{total}\" fill=\"#404040\"/>\n"
+        ));
+    }
+
+    for y in 0..8 {
+        for x in 0..8 {
+            let (left, top) = corner((x, y));
+            let fill = if (x + y) % 2 == 0 {
+                &options.light_color
+            } else {
+                &options.dark_color
+            };
+            svg.push_str(&format!(
+                "<rect x=\"{left}\" y=\"{top}\" width=\"{size}\" height=\"{size}\" fill=\"{}\"/>\n",
+                escape(fill)
+            ));
+        }
+    }
+    for (square, color) in options
+        .highlights
+        .iter()
+        .filter(|(square, _)| on_board(square))
+    {
+        let (left, top) = corner(*square);
+        svg.push_str(&format!(
+            "<rect x=\"{left}\" y=\"{top}\" width=\"{size}\" height=\"{size}\" fill=\"{}\" fill-opacity=\"0.5\"/>\n",
+            escape(color)
+        ));
+    }
+    for (y, row) in board.board.iter().enumerate() {
+        for (x, square) in row.iter().enumerate() {
+            if let Some(piece) = square {
+                let (cx, cy) = center((x, y));
+                svg.push_str(&format!(
+                    "<text x=\"{cx}\" y=\"{cy}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                    size * 0.8,
+                    figurine(piece)
+                ));
+            }
+        }
+    }
+    for (i, arrow) in arrows.iter().enumerate() {
+        let (x1, y1) = center(arrow.source);
+        let (x2, y2) = center(arrow.destination);
+        // Stop the line short of the center so the arrowhead ends there.
+        let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+        let shorten = size * 0.3;
+        let (x2, y2) = (
+            x2 - (x2 - x1) / length * shorten,
+            y2 - (y2 - y1) / length * shorten,
+        );
+        svg.push_str(&format!(
+            "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"{}\" stroke-width=\"{}\" stroke-opacity=\"0.8\" marker-end=\"url(#arrowhead{i})\"/>\n",
+            escape(&arrow.color),
+            size / 6.0
+        ));
+    }
+    if options.coordinates {
+        for i in 0..8 {
+            let (x, _) = center((i, 0));
+            let (_, y) = center((0, i));
+            svg.push_str(&format!(
+                "<text x=\"{x}\" y=\"{}\" font-size=\"{}\" fill=\"#e0e0e0\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                total - margin / 2.0,
+                margin * 0.6,
+                (b'a' + i as u8) as char
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{y}\" font-size=\"{}\" fill=\"#e0e0e0\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                margin / 2.0,
+                margin * 0.6,
+                8 - i
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svg_contents_test() {
+        let board = ChessBoard::create();
+        let options = SvgOptions {
+            coordinates: false,
+            highlights: vec![((4, 4), "yellow".to_string())],
+            arrows: vec![Arrow {
+                source: (4, 6),
+                destination: (4, 4),
+                color: "green".to_string(),
+            }],
+            ..Default::default()
+        };
+        let svg = render_svg(&board, &options);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"360\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 65);
+        assert_eq!(svg.matches("<text").count(), 32);
+        assert!(
+            svg.contains("<rect x=\"180\" y=\"180\" width=\"45\" height=\"45\" fill=\"yellow\"")
+        );
+        assert!(svg.contains("<line x1=\"202.5\" y1=\"292.5\""));
+        assert!(svg.contains("♔"));
+    }
+
+    #[test]
+    fn svg_perspective_test() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let options = SvgOptions {
+            coordinates: false,
+            perspective: Color::Black,
+            ..Default::default()
+        };
+        let svg = render_svg(&board, &options);
+        // a1 is in the top right corner when Black is at the bottom.
+        assert!(svg.contains("<text x=\"337.5\" y=\"22.5\""));
+
+        let options = SvgOptions {
+            highlights: vec![((8, 0), "red".to_string())],
+            arrows: vec![Arrow {
+                source: (0, 0),
+                destination: (0, 9),
+                color: "red".to_string(),
+            }],
+            ..options
+        };
+        let svg = render_svg(&board, &options);
+        assert!(!svg.contains("red"));
+    }
+}
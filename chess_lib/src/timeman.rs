@@ -0,0 +1,136 @@
+use crate::search::SearchLimits;
+use crate::Color;
+use std::time::Duration;
+
+/// Time kept back on the clock for the time it takes to send the move.
+const RESERVE: Duration = Duration::from_millis(50);
+/// How many moves the rest of the time is spread over when the time control doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// A score this much lower than the previous iteration's counts as a fail low.
+const FAIL_LOW_MARGIN: i32 = 30;
+
+/// Decides how long a search may take. Iterations start only before the soft limit, which grows
+/// while the best move keeps changing or the score drops, and the search is aborted at the hard
+/// limit. All times are measured from the start of the search.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimeManager {
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+    /// Percent of the soft limit currently allowed.
+    scale: u32,
+    best_move_changes: u32,
+    last_score: Option<i32>,
+}
+
+impl TimeManager {
+    pub fn new(color: Color, limits: &SearchLimits) -> TimeManager {
+        /// The limits for a search by `color`: none in infinite mode, exactly `movetime` if it is
+        /// set, and otherwise a share of the remaining clock time.
+        let (soft, hard) = match (limits.infinite, limits.movetime) {
+            (true, _) => (None, None),
+            (false, Some(movetime)) => (Some(movetime), Some(movetime)),
+            (false, None) => match clock_limits(color, limits) {
+                Some((soft, hard)) => (Some(soft), Some(hard)),
+                None => (None, None),
+            },
+        };
+        TimeManager {
+            soft,
+            hard,
+            scale: 100,
+            best_move_changes: 0,
+            last_score: None,
+        }
+    }
+    pub fn soft_limit(&self) -> Option<Duration> {
+        /// The soft limit with the current extensions, never past the hard limit.
+        let soft = self.soft.map(|soft| soft * self.scale / 100);
+        soft.zip(self.hard).map(|(soft, hard)| soft.min(hard))
+    }
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+    pub fn iteration_done(&mut self, best_move_changed: bool, score: i32) {
+        // Takes the result of a completed iteration into account. Changes of the best move count
+        // less the longer ago they were, and a fail low extends the time by half.
+        self.best_move_changes = self.best_move_changes / 2 + 2 * best_move_changed as u32;
+        let fail_low = self
+            .last_score
+            .is_some_and(|last| score < last - FAIL_LOW_MARGIN);
+        self.last_score = Some(score);
+        let scale = (100 + 15 * self.best_move_changes) * if fail_low { 3 } else { 2 } / 2;
+        self.scale = scale.min(300);
+    }
+    pub fn start_iteration(&self, elapsed: Duration) -> bool {
+        /// Whether there is time left to start another iteration.
+        self.soft_limit().is_none_or(|limit| elapsed < limit)
+    }
+    pub fn out_of_time(&self, elapsed: Duration) -> bool {
+        /// Whether the search has to stop right away.
+        self.hard.is_some_and(|limit| elapsed >= limit)
+    }
+}
+
+fn clock_limits(color: Color, limits: &SearchLimits) -> Option<(Duration, Duration)> {
+    let (time, increment) = match color {
+        Color::White => (limits.white_time?, limits.white_increment),
+        Color::Black => (limits.black_time?, limits.black_increment),
+    };
+    let available = time.saturating_sub(RESERVE.min(time / 10));
+    let moves_to_go = limits.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let soft = (time / moves_to_go + increment.unwrap_or_default() * 3 / 4).min(available);
+    // The hard limit is three times the soft one but keeps half of the clock for the moves that
+    // follow, unless the soft limit alone takes more, as with one move to go.
+    let hard = (soft * 3).min(available / 2).max(soft);
+    Some((soft, hard))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(time: u64, increment: u64, moves_to_go: Option<u32>) -> SearchLimits {
+        SearchLimits {
+            white_time: Some(Duration::from_millis(time)),
+            white_increment: Some(Duration::from_millis(increment)),
+            moves_to_go,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn limits_test() {
+        let manager = TimeManager::new(Color::White, &limits(60_000, 1000, None));
+        assert_eq!(manager.soft_limit(), Some(Duration::from_millis(2750)));
+        assert_eq!(manager.hard_limit(), Some(Duration::from_millis(8250)));
+        let last_move = TimeManager::new(Color::White, &limits(10_000, 0, Some(1)));
+        assert_eq!(last_move.soft_limit(), Some(Duration::from_millis(9950)));
+        assert_eq!(last_move.hard_limit(), Some(Duration::from_millis(9950)));
+        let black = TimeManager::new(Color::Black, &limits(60_000, 0, None));
+        assert_eq!(black.hard_limit(), None);
+        assert!(black.start_iteration(Duration::from_secs(3600)));
+        let movetime = SearchLimits {
+            movetime: Some(Duration::from_secs(1)),
+            ..limits(60_000, 0, None)
+        };
+        let manager = TimeManager::new(Color::White, &movetime);
+        assert!(!manager.out_of_time(Duration::from_millis(999)));
+        assert!(manager.out_of_time(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn extension_test() {
+        let mut manager = TimeManager::new(Color::White, &limits(60_000, 0, None));
+        let soft = Duration::from_millis(2000);
+        manager.iteration_done(false, 20);
+        assert_eq!(manager.soft_limit(), Some(soft));
+        manager.iteration_done(true, 25);
+        assert_eq!(manager.soft_limit(), Some(soft * 130 / 100));
+        assert!(!manager.start_iteration(soft * 130 / 100));
+        manager.iteration_done(false, -50);
+        assert_eq!(manager.soft_limit(), Some(soft * 172 / 100));
+        manager.iteration_done(false, -50);
+        manager.iteration_done(false, -50);
+        assert_eq!(manager.soft_limit(), Some(soft));
+    }
+}
@@ -0,0 +1,614 @@
+use crate::bitbase::probe_simple_endgame;
+use crate::clock::{Clock, TimeControl};
+use crate::game::{Game, Outcome};
+use crate::pgn::{read_games, PgnError};
+use crate::search::{Score, SearchLimits, Searcher};
+use crate::{ChessBoard, Color, Move};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an engine gets to start up and to answer isready.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long past its clock time an engine may take before it is considered hung.
+const GRACE: Duration = Duration::from_secs(1);
+/// Mate scores count as this many centipawns for adjudication.
+const MATE_VALUE: i32 = 100_000;
+
+/// Reasons a player could not produce a move.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayerError {
+    /// The engine could not be started or written to.
+    Io(String),
+    /// The engine stopped talking, e.g. because it crashed.
+    Disconnected,
+    /// The engine took far longer than its time allowed.
+    Timeout,
+    /// The engine answered with a move that can't be played.
+    IllegalMove(String),
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayerError::Io(error) => write!(f, "{}", error),
+            PlayerError::Disconnected => write!(f, "the engine disconnected"),
+            PlayerError::Timeout => write!(f, "the engine did not answer in time"),
+            PlayerError::IllegalMove(mv) => write!(f, "illegal move '{}'", mv),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+/// A move chosen by a player with its score from the player's point of view, if it gave one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PlayerMove {
+    pub mv: Move,
+    pub score: Option<Score>,
+}
+
+/// Something that can play games of a match: an engine in another process or a configuration of
+/// this library's search.
+pub trait Player {
+    fn name(&self) -> &str;
+    fn new_game(&mut self) -> Result<(), PlayerError>;
+    fn play(
+        &mut self,
+        start: &ChessBoard,
+        moves: &[Move],
+        limits: &SearchLimits,
+    ) -> Result<PlayerMove, PlayerError>;
+}
+
+/// An engine that speaks UCI on its standard input and output.
+pub struct UciPlayer {
+    /// Picks a move in the position reached by playing `moves` from `start`.
+    name: String,
+    /// Limits every search is capped at on top of the match's, e.g. a depth.
+    limits: SearchLimits,
+    child: Child,
+    input: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl UciPlayer {
+    pub fn start(
+        name: &str,
+        command: &str,
+        args: &[String],
+        options: &[(String, String)],
+    ) -> Result<UciPlayer, PlayerError> {
+        /// Starts the engine, waits for its handshake and sets the given UCI options.
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| PlayerError::Io(format!("can not start {}: {}", command, error)))?;
+        let input = child.stdin.take().ok_or(PlayerError::Disconnected)?;
+        let output = child.stdout.take().ok_or(PlayerError::Disconnected)?;
+        // The engine's output is read on its own thread so that waiting for it can time out.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(output).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut player = UciPlayer {
+            name: name.to_string(),
+            limits: SearchLimits::default(),
+            child,
+            input,
+            lines,
+        };
+        player.send("uci")?;
+        player.wait_for("uciok", STARTUP_TIMEOUT)?;
+        for (option, value) in options {
+            player.send(&format!("setoption name {} value {}", option, value))?;
+        }
+        player.ready()?;
+        Ok(player)
+    }
+    pub fn with_limits(mut self, limits: SearchLimits) -> UciPlayer {
+        // Caps every search of the engine at the given depth, nodes or move time.
+        self.limits = limits;
+        self
+    }
+    fn send(&mut self, command: &str) -> Result<(), PlayerError> {
+        writeln!(self.input, "{}", command)
+            .and_then(|_| self.input.flush())
+            .map_err(|_| PlayerError::Disconnected)
+    }
+    fn receive(&mut self, deadline: Instant) -> Result<String, PlayerError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.lines
+            .recv_timeout(timeout)
+            .map_err(|error| match error {
+                RecvTimeoutError::Timeout => PlayerError::Timeout,
+                RecvTimeoutError::Disconnected => PlayerError::Disconnected,
+            })
+    }
+    fn wait_for(&mut self, token: &str, timeout: Duration) -> Result<(), PlayerError> {
+        let deadline = Instant::now() + timeout;
+        while self.receive(deadline)?.trim() != token {}
+        Ok(())
+    }
+    fn ready(&mut self) -> Result<(), PlayerError> {
+        self.send("isready")?;
+        self.wait_for("readyok", STARTUP_TIMEOUT)
+    }
+}
+
+impl Player for UciPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn new_game(&mut self) -> Result<(), PlayerError> {
+        self.send("ucinewgame")?;
+        self.ready()
+    }
+    fn play(
+        &mut self,
+        start: &ChessBoard,
+        moves: &[Move],
+        limits: &SearchLimits,
+    ) -> Result<PlayerMove, PlayerError> {
+        let mut board = start.clone();
+        let mut position = format!("position fen {}", start.to_fen());
+        if !moves.is_empty() {
+            position.push_str(" moves");
+        }
+        for mv in moves {
+            position.push(' ');
+            position.push_str(&mv.to_uci());
+            board.make_move(mv);
+        }
+        let limits = capped(&self.limits, limits);
+        self.send(&position)?;
+        self.send(&go_command(&limits))?;
+        let deadline = Instant::now() + thinking_time(board.faction_decider(), &limits) + GRACE;
+        let mut score = None;
+        loop {
+            let line = self.receive(deadline)?;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("info") => score = parse_score(&line).or(score),
+                Some("bestmove") => {
+                    let uci = tokens.next().unwrap_or("(none)");
+                    let mv = board
+                        .parse_uci_move(uci)
+                        .ok_or_else(|| PlayerError::IllegalMove(uci.to_string()))?;
+                    return Ok(PlayerMove { mv, score });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for UciPlayer {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // Give the engine a moment to exit on its own before killing it.
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                _ => return,
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn go_command(limits: &SearchLimits) -> String {
+    // The go command for the limits, with times in milliseconds.
+    let mut go = "go".to_string();
+    let times = [
+        ("wtime", limits.white_time),
+        ("btime", limits.black_time),
+        ("winc", limits.white_increment),
+        ("binc", limits.black_increment),
+        ("movetime", limits.movetime),
+    ];
+    for (name, time) in times {
+        if let Some(time) = time {
+            go.push_str(&format!(" {} {}", name, time.as_millis()));
+        }
+    }
+    let counts = [
+        ("movestogo", limits.moves_to_go.map(u64::from)),
+        ("depth", limits.depth.map(u64::from)),
+        ("nodes", limits.nodes),
+    ];
+    for (name, count) in counts {
+        if let Some(count) = count {
+            go.push_str(&format!(" {} {}", name, count));
+        }
+    }
+    if !limits.search_moves.is_empty() {
+        go.push_str(" searchmoves");
+        for mv in &limits.search_moves {
+            go.push(' ');
+            go.push_str(&mv.to_uci());
+        }
+    }
+    if limits.infinite {
+        go.push_str(" infinite");
+    }
+    go
+}
+
+fn parse_score(info: &str) -> Option<Score> {
+    let tokens: Vec<&str> = info.split_whitespace().collect();
+    let index = tokens.iter().position(|token| *token == "score")?;
+    let value = tokens.get(index + 2)?.parse().ok()?;
+    match *tokens.get(index + 1)? {
+        "cp" => Some(Score::Centipawns(value)),
+        "mate" => Some(Score::Mate(value)),
+        _ => None,
+    }
+}
+
+fn capped(own: &SearchLimits, limits: &SearchLimits) -> SearchLimits {
+    // The match's limits with a player's own depth, node and time limits taking precedence.
+    SearchLimits {
+        depth: own.depth.or(limits.depth),
+        nodes: own.nodes.or(limits.nodes),
+        movetime: own.movetime.or(limits.movetime),
+        ..limits.clone()
+    }
+}
+
+fn thinking_time(color: Color, limits: &SearchLimits) -> Duration {
+    // The longest a move may take: the movetime, or else the side's whole clock. Depth and node
+    // limited searches get a generous fixed allowance.
+    let clock = match color {
+        Color::White => limits.white_time,
+        Color::Black => limits.black_time,
+    };
+    limits.movetime.or(clock).unwrap_or(Duration::from_secs(60))
+}
+
+/// A player backed by this library's search, with its own settings.
+pub struct LibraryPlayer {
+    name: String,
+    searcher: Searcher,
+    /// Limits every search is capped at on top of the match's, e.g. a depth.
+    limits: SearchLimits,
+}
+
+impl LibraryPlayer {
+    pub fn new(name: &str, hash_mb: usize, threads: usize, limits: SearchLimits) -> LibraryPlayer {
+        let mut searcher = Searcher::new(hash_mb);
+        searcher.set_threads(threads);
+        LibraryPlayer {
+            name: name.to_string(),
+            searcher,
+            limits,
+        }
+    }
+}
+
+impl Player for LibraryPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn new_game(&mut self) -> Result<(), PlayerError> {
+        self.searcher.clear();
+        Ok(())
+    }
+    fn play(
+        &mut self,
+        start: &ChessBoard,
+        moves: &[Move],
+        limits: &SearchLimits,
+    ) -> Result<PlayerMove, PlayerError> {
+        let mut board = start.clone();
+        let mut history = vec![];
+        for mv in moves {
+            history.push(board.zobrist_hash());
+            board.make_move(mv);
+        }
+        let limits = capped(&self.limits, limits);
+        let mut score = None;
+        let best = self.searcher.search(&board, &history, &limits, |info| {
+            if info.multi_pv == 1 {
+                score = Some(info.score);
+            }
+        });
+        let mv = best.ok_or_else(|| PlayerError::IllegalMove("(none)".to_string()))?;
+        Ok(PlayerMove { mv, score })
+    }
+}
+
+/// When a match ends games early instead of playing them out. Every rule is off by default.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Adjudication {
+    /// A side loses once its own score has been at most minus the given centipawns for the given
+    /// number of its moves in a row.
+    pub resign: Option<(u32, i32)>,
+    /// From the first of the given moves on, the game is drawn once both sides' scores have
+    /// stayed within the given centipawns of zero for the given number of moves each.
+    pub draw: Option<(u32, u32, i32)>,
+    /// The game is drawn after this many moves.
+    pub max_moves: Option<u32>,
+    /// Positions the endgame bitbases know are decided by them.
+    pub bitbases: bool,
+}
+
+/// Keeps track of the scores of a game for the Adjudication rules.
+struct Adjudicator {
+    rules: Adjudication,
+    /// How many moves in a row each side has been losing and has seen a drawn position.
+    losing: [u32; 2],
+    drawish: [u32; 2],
+}
+
+impl Adjudicator {
+    fn new(rules: Adjudication) -> Adjudicator {
+        Adjudicator {
+            rules,
+            losing: [0; 2],
+            drawish: [0; 2],
+        }
+    }
+    fn record(&mut self, color: Color, score: Option<Score>) {
+        // Counts a move by `color` with its score from the mover's point of view.
+        let side = index(color);
+        let value = score.map(|score| match score {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE_VALUE,
+            Score::Mate(_) => -MATE_VALUE,
+        });
+        let (losing, drawish) = match (value, self.rules.resign, self.rules.draw) {
+            (Some(value), resign, draw) => (
+                resign.is_some_and(|(_, score)| value <= -score),
+                draw.is_some_and(|(_, _, score)| value.abs() <= score),
+            ),
+            (None, ..) => (false, false),
+        };
+        self.losing[side] = if losing { self.losing[side] + 1 } else { 0 };
+        self.drawish[side] = if drawish { self.drawish[side] + 1 } else { 0 };
+    }
+    fn decide(&self, board: &ChessBoard) -> Option<Outcome> {
+        // The outcome the rules give the position, before the side to move plays.
+        let move_number = board.turn.div_ceil(2);
+        if let Some((moves, _)) = self.rules.resign {
+            for color in [Color::White, Color::Black] {
+                if self.losing[index(color)] >= moves {
+                    return Some(Outcome::Win(color.opposite()));
+                }
+            }
+        }
+        if let Some((after, moves, _)) = self.rules.draw {
+            if move_number > after && self.drawish.iter().all(|count| *count >= moves) {
+                return Some(Outcome::Draw);
+            }
+        }
+        if self.rules.bitbases {
+            if let Some(outcome) = probe_simple_endgame(board) {
+                return Some(outcome);
+            }
+        }
+        match self.rules.max_moves {
+            Some(max_moves) if move_number > max_moves => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+}
+
+fn index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// A position to start games from, with the moves that lead to it, which become part of the
+/// games.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Opening {
+    pub start: ChessBoard,
+    pub moves: Vec<Move>,
+}
+
+impl Opening {
+    pub fn from_board(start: ChessBoard) -> Opening {
+        Opening {
+            start,
+            moves: vec![],
+        }
+    }
+}
+
+pub fn read_openings(text: &str) -> Result<Vec<Opening>, PgnError> {
+    /// Reads an opening suite, either PGN games whose moves are the openings or one FEN or EPD
+    /// position per line. Lines starting with '#' are skipped in the latter.
+    if text.trim_start().starts_with('[') || text.trim_start().starts_with("1.") {
+        let mut openings = vec![];
+        for game in read_games(text) {
+            let moves = game.replay()?.into_iter().map(|(_, mv)| mv).collect();
+            let start = game.starting_position()?;
+            openings.push(Opening { start, moves });
+        }
+        return Ok(openings);
+    }
+    let mut openings = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // EPD lines only have the first four fields of a FEN, followed by operations.
+        let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        let epd = format!("{} 0 1", fields.join(" "));
+        let board = ChessBoard::from_fen(line)
+            .or_else(|_| ChessBoard::from_fen(&epd))
+            .map_err(PgnError::InvalidFen)?;
+        openings.push(Opening::from_board(board));
+    }
+    Ok(openings)
+}
+
+pub fn play_game(
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    opening: &Opening,
+    control: Option<&TimeControl>,
+    limits: &SearchLimits,
+    adjudication: Adjudication,
+) -> Game {
+    /// Plays a game from the opening. With a time control both players are on the clock and get
+    /// their remaining times, on top of `limits`. A player that fails to answer, answers with an
+    /// illegal move or hangs past its time loses by adjudication.
+    let mut game = Game::from_position(white.name(), black.name(), opening.start.clone());
+    for mv in &opening.moves {
+        if game.play(mv).is_err() {
+            break;
+        }
+    }
+    if let Some(control) = control {
+        game = game.with_clock(Clock::with_wall_clock(control.clone()));
+    }
+    let ready = [
+        (Color::White, white.new_game()),
+        (Color::Black, black.new_game()),
+    ];
+    for (color, result) in ready {
+        if result.is_err() {
+            let _ = game.adjudicate(Outcome::Win(color.opposite()));
+            return game;
+        }
+    }
+    let mut adjudicator = Adjudicator::new(adjudication);
+    while !game.is_over() {
+        let color = game.board().faction_decider();
+        if let Some(outcome) = adjudicator.decide(game.board()) {
+            let _ = game.adjudicate(outcome);
+            break;
+        }
+        let mut limits = limits.clone();
+        if let Some(clock) = game.clock_mut() {
+            if clock.running().is_none() {
+                clock.start(color);
+            }
+            limits.white_time = Some(clock.remaining(&Color::White));
+            limits.black_time = Some(clock.remaining(&Color::Black));
+            limits.white_increment = Some(clock.stage(&Color::White).increment);
+            limits.black_increment = Some(clock.stage(&Color::Black).increment);
+            limits.moves_to_go = clock.moves_to_go(&color);
+        }
+        let (start, moves) = (game.starting_position(), game.moves());
+        let played = match color {
+            Color::White => white.play(start, moves, &limits),
+            Color::Black => black.play(start, moves, &limits),
+        };
+        match played {
+            Ok(PlayerMove { mv, score }) if game.board().legal_moves().contains(&mv) => {
+                // A move that comes too late ends the game on time inside play.
+                if game.play(&mv).is_ok() {
+                    adjudicator.record(color, score);
+                }
+            }
+            Err(PlayerError::Timeout) if game.check_time().is_some() => {}
+            _ => {
+                let _ = game.adjudicate(Outcome::Win(color.opposite()));
+            }
+        }
+    }
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uci_text_test() {
+        let board = ChessBoard::create();
+        let limits = SearchLimits {
+            white_time: Some(Duration::from_secs(10)),
+            black_time: Some(Duration::from_millis(9500)),
+            moves_to_go: Some(20),
+            search_moves: vec![board.parse_uci_move("e2e4").unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(
+            go_command(&limits),
+            "go wtime 10000 btime 9500 movestogo 20 searchmoves e2e4"
+        );
+        assert_eq!(
+            parse_score("info depth 12 score cp -35 nodes 1000 pv e7e5"),
+            Some(Score::Centipawns(-35))
+        );
+        assert_eq!(
+            parse_score("info depth 3 score mate -2 pv"),
+            Some(Score::Mate(-2))
+        );
+        assert_eq!(parse_score("info string hello"), None);
+    }
+
+    #[test]
+    fn adjudication_test() {
+        let rules = Adjudication {
+            resign: Some((2, 500)),
+            draw: Some((10, 2, 10)),
+            max_moves: Some(40),
+            bitbases: false,
+        };
+        let mut adjudicator = Adjudicator::new(rules);
+        let board = ChessBoard::create();
+        adjudicator.record(Color::White, Some(Score::Centipawns(-600)));
+        adjudicator.record(Color::Black, Some(Score::Centipawns(600)));
+        assert_eq!(adjudicator.decide(&board), None);
+        adjudicator.record(Color::White, Some(Score::Mate(-3)));
+        assert_eq!(adjudicator.decide(&board), Some(Outcome::Win(Color::Black)));
+        let mut adjudicator = Adjudicator::new(rules);
+        for color in [Color::White, Color::Black, Color::White, Color::Black] {
+            adjudicator.record(color, Some(Score::Centipawns(5)));
+        }
+        // Too early in the game for a draw.
+        assert_eq!(adjudicator.decide(&board), None);
+        let mut late = board.clone();
+        late.turn = 25;
+        assert_eq!(adjudicator.decide(&late), Some(Outcome::Draw));
+        let mut adjudicator = Adjudicator::new(Adjudication {
+            bitbases: true,
+            ..Default::default()
+        });
+        let board = ChessBoard::from_fen("8/8/8/4k3/8/8/8/K6Q w - - 0 1").unwrap();
+        assert_eq!(adjudicator.decide(&board), Some(Outcome::Win(Color::White)));
+        adjudicator.record(Color::White, None);
+    }
+
+    #[test]
+    fn play_game_test() {
+        let openings = read_openings("# a suite\n8/8/8/4k3/8/8/8/K6Q w - - 0 1\n").unwrap();
+        assert_eq!(openings.len(), 1);
+        let openings = read_openings("1. e4 e5 2. Nf3 *\n\n1. d4 *").unwrap();
+        assert_eq!(openings[0].moves.len(), 3);
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..Default::default()
+        };
+        let mut white = LibraryPlayer::new("one", 1, 1, SearchLimits::default());
+        let mut black = LibraryPlayer::new("two", 1, 1, SearchLimits::default());
+        let rules = Adjudication {
+            max_moves: Some(8),
+            ..Default::default()
+        };
+        let game = play_game(&mut white, &mut black, &openings[0], None, &limits, rules);
+        assert!(game.is_over());
+        assert!(game.moves().len() >= 3);
+        assert_eq!(game.moves()[..3], openings[0].moves[..]);
+        assert!(game.to_pgn().contains("[White \"one\"]"));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::game::Outcome;
+use crate::{find_king, ChessBoard, ChessPiece, Color, Move};
+
+/// The rule set a board is played with. Move generation (`legal_moves`) and the game status
+/// (`variant_outcome`) ask the variant about the rules that differ from standard chess.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Giving check for the third time wins.
+    ThreeCheck,
+    /// Bringing the king to one of the four center squares wins.
+    KingOfTheHill,
+    /// No pawns and no checks. The first king to reach the 8th rank wins, unless Black reaches
+    /// it right after White, which is a draw.
+    RacingKings,
+    /// Captured pieces change sides and can be dropped back on the board instead of moving.
+    Crazyhouse,
+    /// Captures explode, removing the capturing piece and every piece next to the captured one
+    /// except pawns. Kings can not capture, and exploding the enemy king wins.
+    Atomic,
+    /// Captures are compulsory and the king is an ordinary piece. Losing all pieces, or having no
+    /// legal move, wins.
+    Antichess,
+}
+
+const CENTER: [(usize, usize); 4] = [(3, 3), (4, 3), (3, 4), (4, 4)];
+
+impl Variant {
+    pub fn name(&self) -> &'static str {
+        /// The name used for the variant in the PGN Variant tag.
+        match self {
+            Variant::Standard => "Standard",
+            Variant::ThreeCheck => "Three-check",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::RacingKings => "Racing Kings",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::Atomic => "Atomic",
+            Variant::Antichess => "Antichess",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Variant> {
+        /// Reads a variant name as written by name, ignoring case, spaces and dashes, so that
+        /// "threeCheck" and "kingofthehill" are understood as well.
+        let name: String = name
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match name.as_str() {
+            "standard" | "chess" => Some(Variant::Standard),
+            "threecheck" | "3check" => Some(Variant::ThreeCheck),
+            "kingofthehill" | "koth" => Some(Variant::KingOfTheHill),
+            "racingkings" => Some(Variant::RacingKings),
+            "crazyhouse" | "zh" => Some(Variant::Crazyhouse),
+            "atomic" => Some(Variant::Atomic),
+            "antichess" | "giveaway" | "losers" | "suicide" => Some(Variant::Antichess),
+            _ => None,
+        }
+    }
+    pub fn starting_fen(&self) -> &'static str {
+        match self {
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1",
+            Variant::RacingKings => "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1",
+            Variant::Crazyhouse => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            _ => crate::fen::STARTING_FEN,
+        }
+    }
+    pub(crate) fn retain_legal(&self, board: &ChessBoard, moves: &mut Vec<Move>) {
+        /// Removes the pseudo-legal moves the variant does not allow. Normally that means moves
+        /// leaving the own king in check. In Racing Kings no move may give check either.
+        match self {
+            Variant::RacingKings => moves.retain(|mv| {
+                let mut after = board.clone();
+                after.apply_move(mv);
+                board.keeps_king_safe(mv)
+                    && !after.king_attacked(&board.faction_decider().opposite())
+            }),
+            Variant::Atomic => moves.retain(|mv| atomic_legal(board, mv)),
+            Variant::Antichess => {
+                let is_capture = |mv: &Move| {
+                    let target = board.board[mv.destination.1][mv.destination.0];
+                    let en_passant = board.en_passant == Some(mv.destination)
+                        && matches!(
+                            board.board[mv.source.1][mv.source.0],
+                            Some(ChessPiece::Pawn(_))
+                        );
+                    target.is_some() || en_passant
+                };
+                if moves.iter().any(is_capture) {
+                    moves.retain(is_capture);
+                }
+            }
+            _ => moves.retain(|mv| board.keeps_king_safe(mv)),
+        }
+    }
+    pub(crate) fn in_check(&self, board: &ChessBoard, color: &Color) -> bool {
+        /// Whether the king of the given color is in check. Antichess has no checks, and in atomic
+        /// chess kings standing next to each other can not be checked, since taking one would
+        /// explode the other.
+        match self {
+            Variant::Antichess => false,
+            Variant::Atomic => !kings_touch(board) && board.king_attacked(color),
+            _ => board.king_attacked(color),
+        }
+    }
+}
+
+fn kings_touch(board: &ChessBoard) -> bool {
+    let white = find_king(&board.board, board.white_king_pos, &Color::White);
+    let black = find_king(&board.board, board.black_king_pos, &Color::Black);
+    match (white, black) {
+        (Some(white), Some(black)) => {
+            white.0.abs_diff(black.0) <= 1 && white.1.abs_diff(black.1) <= 1
+        }
+        _ => false,
+    }
+}
+
+fn atomic_legal(board: &ChessBoard, mv: &Move) -> bool {
+    let color = board.faction_decider();
+    let source = board.board[mv.source.1][mv.source.0];
+    let target = board.board[mv.destination.1][mv.destination.0];
+    if matches!(source, Some(ChessPiece::King(_)))
+        && target.is_some_and(|piece| piece.color() != color)
+    {
+        return false;
+    }
+    let mut after = board.clone();
+    after.apply_move(mv);
+    let own_king = find_king(&after.board, (0, 0), &color);
+    let enemy_king = find_king(&after.board, (0, 0), &color.opposite());
+    match (own_king, enemy_king) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(_), Some(_)) => !Variant::Atomic.in_check(&after, &color),
+    }
+}
+
+impl ChessBoard {
+    pub fn create_variant(variant: Variant) -> Self {
+        /// Creates the starting position of the given variant.
+        let mut board = ChessBoard::from_fen(variant.starting_fen()).unwrap();
+        board.variant = variant;
+        board
+    }
+    pub fn variant_outcome(&self) -> Option<Outcome> {
+        /// Returns the result if the game has been decided by a rule of the variant, like a third
+        /// check or a king reaching the hill. Checkmate and stalemate are left to is_checkmate
+        /// and is_stalemate.
+        let king = |color: Color| match color {
+            Color::White => self.white_king_pos,
+            Color::Black => self.black_king_pos,
+        };
+        match self.variant {
+            Variant::Standard | Variant::Crazyhouse => None,
+            Variant::Atomic => {
+                if find_king(&self.board, self.white_king_pos, &Color::White).is_none() {
+                    Some(Outcome::Win(Color::Black))
+                } else if find_king(&self.board, self.black_king_pos, &Color::Black).is_none() {
+                    Some(Outcome::Win(Color::White))
+                } else {
+                    None
+                }
+            }
+            // Having no pieces left also means having no legal moves.
+            Variant::Antichess => {
+                let stuck = self.legal_moves().is_empty();
+                stuck.then(|| Outcome::Win(self.faction_decider()))
+            }
+            Variant::ThreeCheck => {
+                if self.white_checks >= 3 {
+                    Some(Outcome::Win(Color::White))
+                } else if self.black_checks >= 3 {
+                    Some(Outcome::Win(Color::Black))
+                } else {
+                    None
+                }
+            }
+            Variant::KingOfTheHill => [Color::White, Color::Black]
+                .into_iter()
+                .find(|color| CENTER.contains(&king(*color)))
+                .map(Outcome::Win),
+            Variant::RacingKings => {
+                let white_home = king(Color::White).1 == 0;
+                let black_home = king(Color::Black).1 == 0;
+                match (white_home, black_home) {
+                    (true, true) => Some(Outcome::Draw),
+                    (false, true) => Some(Outcome::Win(Color::Black)),
+                    (false, false) => None,
+                    (true, false) => {
+                        // Black gets one more move to reach the last rank and draw.
+                        let black_can_draw = self.faction_decider() == Color::Black
+                            && self
+                                .legal_moves()
+                                .iter()
+                                .any(|mv| mv.source == king(Color::Black) && mv.destination.1 == 0);
+                        if black_can_draw {
+                            None
+                        } else {
+                            Some(Outcome::Win(Color::White))
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pub(crate) fn explode(&mut self, square: (usize, usize)) {
+        /// Removes the piece on the square of an atomic capture together with every piece around
+        /// it that is not a pawn.
+        let (x, y) = (square.0 as i32, square.1 as i32);
+        for ny in y - 1..=y + 1 {
+            for nx in x - 1..=x + 1 {
+                if !(0..8).contains(&nx) || !(0..8).contains(&ny) {
+                    continue;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                let piece = match self.board[ny][nx] {
+                    Some(ChessPiece::Pawn(_)) if (nx, ny) != square => continue,
+                    Some(piece) => piece,
+                    None => continue,
+                };
+                self.board[ny][nx] = None;
+                self.promoted[ny][nx] = false;
+                match piece.color() {
+                    Color::Black => self.removed_black.push(piece),
+                    Color::White => self.removed_white.push(piece),
+                }
+                self.castling_rights.remove_rook((nx, ny));
+            }
+        }
+    }
+    pub(crate) fn count_check(&mut self, mover: &Color) {
+        if self.variant == Variant::ThreeCheck && self.king_attacked(&mover.opposite()) {
+            match mover {
+                Color::White => self.white_checks += 1,
+                Color::Black => self.black_checks += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut ChessBoard, moves: &[&str]) {
+        for uci in moves {
+            let mv = board
+                .parse_uci_move(uci)
+                .unwrap_or_else(|| panic!("{} is not legal", uci));
+            board.make_move(&mv);
+        }
+    }
+
+    #[test]
+    fn three_check_test() {
+        let mut board = ChessBoard::create_variant(Variant::ThreeCheck);
+        play(&mut board, &["e2e4", "e7e5", "f1c4", "d7d6", "c4f7"]);
+        assert_eq!(board.white_checks, 1);
+        assert_eq!(board.variant_outcome(), None);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/ppp2Bpp/3p4/4p3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 0 3"
+        );
+        board.white_checks = 3;
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+
+        let board =
+            ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 1+3 0 1")
+                .unwrap();
+        assert_eq!(board.variant, Variant::ThreeCheck);
+        assert_eq!(board.white_checks, 2);
+        assert_ne!(
+            board.zobrist_hash(),
+            ChessBoard::create_variant(Variant::ThreeCheck).zobrist_hash()
+        );
+    }
+
+    #[test]
+    fn king_of_the_hill_test() {
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/4K3/8/8 w - - 0 1").unwrap();
+        board.variant = Variant::KingOfTheHill;
+        assert_eq!(board.variant_outcome(), None);
+        play(&mut board, &["e3d4"]);
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+        board.variant = Variant::Standard;
+        assert_eq!(board.variant_outcome(), None);
+    }
+
+    #[test]
+    fn crazyhouse_test() {
+        let mut board = ChessBoard::create_variant(Variant::Crazyhouse);
+        play(&mut board, &["e2e4", "d7d5", "e4d5", "d8d5"]);
+        assert_eq!(
+            board.pocket(&Color::White),
+            vec![ChessPiece::Pawn(Color::White)]
+        );
+        assert_eq!(
+            board.pocket(&Color::Black),
+            vec![ChessPiece::Pawn(Color::Black)]
+        );
+        assert_eq!(
+            board.to_fen(),
+            "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w KQkq - 0 3"
+        );
+        let drop = board.parse_san("P@e4").unwrap();
+        assert_eq!(
+            drop,
+            Move::drop_piece(ChessPiece::Pawn(Color::White), (4, 4))
+        );
+        assert_eq!(drop.to_uci(), "P@e4");
+        assert_eq!(board.to_san(&drop), "P@e4");
+        assert!(board.parse_san("P@e8").is_none());
+        assert!(board.parse_san("N@e4").is_none());
+        board.make_move(&drop);
+        assert!(board.pocket(&Color::White).is_empty());
+        assert_eq!(board.board[4][4], Some(ChessPiece::Pawn(Color::White)));
+
+        // Only drops that block the check are legal here.
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/r3K3[N] w - - 0 1").unwrap();
+        let drops: Vec<String> = board
+            .legal_moves()
+            .iter()
+            .filter(|mv| mv.dropped.is_some())
+            .map(Move::to_uci)
+            .collect();
+        assert_eq!(drops, vec!["N@b1", "N@c1", "N@d1"]);
+    }
+
+    #[test]
+    fn crazyhouse_promotion_test() {
+        let fen = "k7/6P1/8/8/8/8/3K4/6r1[] w - - 0 1";
+        let mut board = ChessBoard::from_fen(fen).unwrap();
+        assert_eq!(board.variant, Variant::Crazyhouse);
+        play(&mut board, &["g7g8q"]);
+        assert!(board.promoted[0][6]);
+        assert_eq!(board.to_fen(), "k5Q~1/8/8/8/8/8/3K4/6r1[] b - - 0 1");
+        assert_eq!(ChessBoard::from_fen(&board.to_fen()).unwrap(), board);
+        play(&mut board, &["a8a7", "d2d3", "g1g8"]);
+        // The captured queen goes into Black's pocket as a pawn.
+        assert_eq!(
+            board.pocket(&Color::Black),
+            vec![ChessPiece::Pawn(Color::Black)]
+        );
+        assert!(!board.promoted[0][6]);
+        assert!(!board.is_insufficient_material());
+    }
+
+    #[test]
+    fn atomic_test() {
+        let mut board = ChessBoard::from_fen("3qk3/2p5/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+        board.variant = Variant::Atomic;
+        play(&mut board, &["d1d8"]);
+        // The rook, the queen and the king next to them explode, the pawn survives.
+        assert_eq!(board.board[0][3], None);
+        assert_eq!(board.board[0][4], None);
+        assert_eq!(board.board[1][2], Some(ChessPiece::Pawn(Color::Black)));
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+
+        // Taking the knight would explode the own king, and kings can not capture at all.
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/3n4/3RK3 w - - 0 1").unwrap();
+        board.variant = Variant::Atomic;
+        assert!(board.parse_uci_move("d1d2").is_none());
+        assert!(board.parse_uci_move("e1d2").is_none());
+        assert!(board.parse_uci_move("e1f2").is_some());
+
+        // Touching kings can not be checked.
+        let mut board = ChessBoard::from_fen("4r3/8/8/8/8/8/3kK3/8 w - - 0 1").unwrap();
+        assert!(board.is_in_check());
+        board.variant = Variant::Atomic;
+        assert!(!board.is_in_check());
+    }
+
+    #[test]
+    fn antichess_test() {
+        let mut board = ChessBoard::create_variant(Variant::Antichess);
+        play(&mut board, &["e2e3", "b7b5"]);
+        assert_eq!(
+            board.legal_moves(),
+            vec![Move::new((5, 7), (1, 3))],
+            "capturing is compulsory"
+        );
+
+        let mut board = ChessBoard::from_fen("8/8/8/8/8/8/8/kR6 b - - 0 1").unwrap();
+        board.variant = Variant::Antichess;
+        assert!(!board.is_in_check());
+        play(&mut board, &["a1b1"]);
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+
+        let mut board = ChessBoard::from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1").unwrap();
+        board.variant = Variant::Antichess;
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+
+        let mut board = ChessBoard::from_fen("8/1P6/8/8/8/8/8/7k w - - 0 1").unwrap();
+        board.variant = Variant::Antichess;
+        assert_eq!(board.legal_moves().len(), 5);
+        assert_eq!(board.parse_san("b8=K"), board.parse_uci_move("b7b8k"));
+    }
+
+    #[test]
+    fn racing_kings_test() {
+        let board = ChessBoard::create_variant(Variant::RacingKings);
+        assert_eq!(board.legal_moves().len(), 21);
+        // Ra3 would check the black king along the 3rd rank, so it is not allowed.
+        let board = ChessBoard::from_fen("8/8/8/8/8/5k2/8/R3K3 w - - 0 1").unwrap();
+        let mut racing = board.clone();
+        racing.variant = Variant::RacingKings;
+        assert!(board.parse_uci_move("a1a3").is_some());
+        assert!(racing.parse_uci_move("a1a3").is_none());
+
+        let mut board = ChessBoard::from_fen("8/4K3/8/8/8/8/6k1/8 w - - 0 1").unwrap();
+        board.variant = Variant::RacingKings;
+        play(&mut board, &["e7e8"]);
+        assert_eq!(board.variant_outcome(), Some(Outcome::Win(Color::White)));
+
+        let mut board = ChessBoard::from_fen("8/4K1k1/8/8/8/8/8/8 w - - 0 1").unwrap();
+        board.variant = Variant::RacingKings;
+        play(&mut board, &["e7d8"]);
+        assert_eq!(board.variant_outcome(), None);
+        play(&mut board, &["g7g8"]);
+        assert_eq!(board.variant_outcome(), Some(Outcome::Draw));
+    }
+}